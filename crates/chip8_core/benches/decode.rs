@@ -0,0 +1,125 @@
+// Compares the cached, enum-dispatched interpreter against decoding every
+// instruction into a `Box<dyn Instruction>`, as `emulate_cycle` used to.
+//
+//   cargo bench -p chip8_core --bench decode
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use chip8_core::asm;
+use chip8_core::chip8::Chip8;
+use chip8_core::quirks::Quirks;
+use chip8_core::{decode, Instruction};
+
+// Arithmetic, skips, a call, sprite drawing and a BCD store into data, the
+// last of which goes through the cache invalidation on every loop. The same
+// as the mixed workload in the engine benchmark.
+const WORKLOAD: &str = "
+main:
+    LD V0, 0x00
+    LD V1, 0x00
+    LD V2, 0x00
+loop:
+    ADD V0, 0x01
+    LD V3, V0
+    AND V3, V1
+    XOR V3, V0
+    SHR V3, V3
+    SE V3, 0x10
+    ADD V1, 0x03
+    SNE V1, 0x40
+    LD V1, 0x00
+    CALL step
+    LD I, digits
+    LD B, V0
+    LD F, V2
+    DRW V0, V1, 0x5
+    JMP to loop
+step:
+    ADD V2, 0x01
+    LD V4, 0x0F
+    AND V2, V4
+    RET
+digits:
+    db 0, 0, 0
+";
+
+const CYCLES: u32 = 2_000_000;
+
+fn main() {
+    let rom = asm::assemble(WORKLOAD, "workload", 0x200).expect("workload assembles");
+
+    let boxed = measure(|| {
+        let mut count = 0;
+        for opcode in 0..=u16::MAX {
+            let instruction: Box<dyn Instruction> = Box::new(decode(black_box(opcode), 0));
+            black_box(instruction);
+            count += 1;
+        }
+        count
+    });
+    let enumerated = measure(|| {
+        let mut count = 0;
+        for opcode in 0..=u16::MAX {
+            black_box(decode(black_box(opcode), 0));
+            count += 1;
+        }
+        count
+    });
+    report("decode all opcodes", ("boxed", boxed), ("enum", enumerated));
+
+    let boxed = measure(|| {
+        let mut chip8 = machine(&rom);
+        for _ in 0..CYCLES {
+            boxed_cycle(&mut chip8);
+        }
+        chip8.v[0] as usize
+    });
+    let cached = measure(|| {
+        let mut chip8 = machine(&rom);
+        chip8.run_frame(CYCLES).expect("workload runs");
+        chip8.v[0] as usize
+    });
+    report(&format!("run {} instructions", CYCLES), ("boxed", boxed), ("cached", cached));
+}
+
+fn machine(rom: &[u8]) -> Chip8 {
+    let mut chip8 = Chip8::new();
+    chip8.quirks = Quirks::CHIP_48;
+    chip8.load_rom(rom).expect("workload fits");
+    chip8
+}
+
+// The old per-cycle path: fetch, allocate a decoded instruction, call it
+// through the vtable. The workload never faults, waits or runs `F000 NNNN`.
+fn boxed_cycle(chip8: &mut Chip8) {
+    let pc = chip8.pc as usize;
+    let opcode = (chip8.memory[pc] as u16) << 8 | chip8.memory[pc + 1] as u16;
+    chip8.pc += 2;
+    let instruction: Box<dyn Instruction> = Box::new(decode(opcode, 0));
+    chip8.execute_instruction(&*instruction).expect("workload runs");
+}
+
+// The best of five runs.
+fn measure<F: FnMut() -> usize>(mut run: F) -> Duration {
+    (0..5)
+        .map(|_| {
+            let start = Instant::now();
+            black_box(run());
+            start.elapsed()
+        })
+        .min()
+        .unwrap_or_default()
+}
+
+fn report(name: &str, (before_name, before): (&str, Duration), (after_name, after): (&str, Duration)) {
+    println!(
+        "{:<28} {:>11} {:>9.2?}  {:>6} {:>9.2?}  {:.2}x",
+        name,
+        before_name,
+        before,
+        after_name,
+        after,
+        before.as_secs_f64() / after.as_secs_f64().max(f64::MIN_POSITIVE)
+    );
+}
@@ -0,0 +1,145 @@
+// Compares the block engine against the interpreter on workloads of different
+// shapes, from long straight-line runs to code that rewrites itself every
+// loop, the engine's worst case.
+//
+//   cargo bench -p chip8_core --bench engine
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use chip8_core::asm;
+use chip8_core::chip8::Chip8;
+use chip8_core::engine::{Engine, EngineKind};
+use chip8_core::quirks::Quirks;
+
+// Arithmetic, skips, a call, sprite drawing and a BCD store into data.
+const MIXED: &str = "
+main:
+    LD V0, 0x00
+    LD V1, 0x00
+    LD V2, 0x00
+loop:
+    ADD V0, 0x01
+    LD V3, V0
+    AND V3, V1
+    XOR V3, V0
+    SHR V3, V3
+    SE V3, 0x10
+    ADD V1, 0x03
+    SNE V1, 0x40
+    LD V1, 0x00
+    CALL step
+    LD I, digits
+    LD B, V0
+    LD F, V2
+    DRW V0, V1, 0x5
+    JMP to loop
+step:
+    ADD V2, 0x01
+    LD V4, 0x0F
+    AND V2, V4
+    RET
+digits:
+    db 0, 0, 0
+";
+
+// Long blocks of register arithmetic closed by a jump.
+const STRAIGHT: &str = "
+loop:
+    ADD V0, 0x01
+    ADD V1, V0
+    XOR V2, V1
+    OR V3, V2
+    AND V4, V3
+    SUB V5, V4
+    SHL V6, V6
+    ADD V7, 0x03
+    LD V8, V7
+    SUBN V9, V8
+    ADD VA, V9
+    SHR VB, VB
+    ADD I, V0
+    LD VC, 0x10
+    ADD VD, VC
+    XOR VE, VD
+    JMP to loop
+";
+
+// Short blocks: a counted inner loop and a call per iteration.
+const BRANCHY: &str = "
+main:
+    LD V1, 0x00
+outer:
+    LD V0, 0x08
+inner:
+    ADD V0, 0xFF
+    SE V0, 0x00
+    JMP to inner
+    CALL bump
+    JMP to outer
+bump:
+    ADD V1, 0x01
+    SNE V1, 0x80
+    LD V1, 0x00
+    RET
+";
+
+// Stores into its own code every loop, dropping the block it runs.
+const SELF_MODIFYING: &str = "
+loop:
+    LD I, patch
+    LD V0, 0x70
+    LD V1, 0x01
+    LD [I], V1
+    ADD V2, 0x01
+patch:
+    ADD V0, 0x01
+    JMP to loop
+";
+
+const CYCLES: u32 = 2_000_000;
+
+fn main() {
+    let workloads = [("mixed", MIXED), ("straight-line", STRAIGHT), ("branchy", BRANCHY), ("self-modifying", SELF_MODIFYING)];
+    for (name, source) in workloads {
+        let rom = asm::assemble(source, name, 0x200).expect("workload assembles");
+        let (interpreted, blocks) = measure(|kind| {
+            let mut chip8 = machine(&rom);
+            let mut engine = Engine::new(kind);
+            let mut budget = CYCLES;
+            engine.run(&mut chip8, &mut budget).expect("workload runs");
+            chip8.v[0] as usize
+        });
+        println!(
+            "{:<16} {} instructions  interpreter {:>9.2?}  blocks {:>9.2?}  {:.2}x",
+            name,
+            CYCLES,
+            interpreted,
+            blocks,
+            interpreted.as_secs_f64() / blocks.as_secs_f64().max(f64::MIN_POSITIVE)
+        );
+    }
+}
+
+fn machine(rom: &[u8]) -> Chip8 {
+    let mut chip8 = Chip8::new();
+    chip8.quirks = Quirks::CHIP_48;
+    chip8.load_rom(rom).expect("workload fits");
+    chip8
+}
+
+// The best of ten runs of each engine, taking turns so that both see the same
+// load on the machine.
+fn measure<F: FnMut(EngineKind) -> usize>(mut run: F) -> (Duration, Duration) {
+    let mut time = |kind| {
+        let start = Instant::now();
+        black_box(run(kind));
+        start.elapsed()
+    };
+    let mut best = (Duration::MAX, Duration::MAX);
+    for _ in 0..10 {
+        best.0 = best.0.min(time(EngineKind::Interpreter));
+        best.1 = best.1.min(time(EngineKind::Blocks));
+    }
+    best
+}
@@ -0,0 +1,751 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::rc::Rc;
+
+// Includes and macro expansions nested deeper than this are assumed to recurse.
+const MAX_DEPTH: usize = 16;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AsmError {
+    pub file: String,
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}:{}: {}", self.file, self.line, self.column, self.message)
+    }
+}
+
+impl Error for AsmError {}
+
+#[derive(Debug, Clone, PartialEq)]
+enum TokenKind {
+    Ident(String),
+    Number(i64),
+    Str(Vec<u8>),
+    Punct(char),
+}
+
+#[derive(Debug, Clone)]
+struct Token {
+    kind: TokenKind,
+    column: usize,
+}
+
+impl Token {
+    fn ident(&self) -> Option<&str> {
+        match &self.kind {
+            TokenKind::Ident(name) => Some(name),
+            _ => None,
+        }
+    }
+
+    fn is_keyword(&self, keyword: &str) -> bool {
+        self.ident().is_some_and(|name| name.eq_ignore_ascii_case(keyword))
+    }
+
+    fn is_punct(&self, c: char) -> bool {
+        self.kind == TokenKind::Punct(c)
+    }
+}
+
+// `;` starts a comment. Columns are 1-based.
+fn tokenize(text: &str) -> Result<Vec<Token>, (usize, String)> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut tokens = Vec::new();
+    let mut pos = 0;
+
+    while pos < chars.len() {
+        let c = chars[pos];
+        let column = pos + 1;
+        if c.is_whitespace() {
+            pos += 1;
+            continue;
+        }
+        if c == ';' {
+            break;
+        }
+
+        let kind = if c.is_ascii_alphabetic() || c == '_' || c == '.' {
+            let start = pos;
+            while pos < chars.len() && (chars[pos].is_ascii_alphanumeric() || chars[pos] == '_' || chars[pos] == '.') {
+                pos += 1;
+            }
+            TokenKind::Ident(chars[start..pos].iter().collect())
+        } else if c.is_ascii_digit() {
+            let start = pos;
+            while pos < chars.len() && (chars[pos].is_ascii_alphanumeric() || chars[pos] == '_') {
+                pos += 1;
+            }
+            let raw: String = chars[start..pos].iter().filter(|&&c| c != '_').collect();
+            TokenKind::Number(parse_number(&raw).ok_or_else(|| (column, format!("invalid number '{}'", raw)))?)
+        } else if c == '"' {
+            pos += 1;
+            let mut bytes = Vec::new();
+            loop {
+                match chars.get(pos) {
+                    None => return Err((column, "unterminated string".to_string())),
+                    Some('"') => break,
+                    Some('\\') => {
+                        let escaped = match chars.get(pos + 1) {
+                            Some('n') => '\n',
+                            Some('0') => '\0',
+                            Some(&c @ ('"' | '\\')) => c,
+                            _ => return Err((pos + 1, "invalid escape in string".to_string())),
+                        };
+                        bytes.push(escaped as u8);
+                        pos += 2;
+                    }
+                    Some(&c) if c.is_ascii() => {
+                        bytes.push(c as u8);
+                        pos += 1;
+                    }
+                    Some(_) => return Err((pos + 1, "strings must be ASCII".to_string())),
+                }
+            }
+            pos += 1;
+            TokenKind::Str(bytes)
+        } else if ",:=+-[]()".contains(c) {
+            pos += 1;
+            TokenKind::Punct(c)
+        } else {
+            return Err((column, format!("unexpected character '{}'", c)));
+        };
+        tokens.push(Token { kind, column });
+    }
+    Ok(tokens)
+}
+
+fn parse_number(raw: &str) -> Option<i64> {
+    let lower = raw.to_ascii_lowercase();
+    if let Some(hex) = lower.strip_prefix("0x") {
+        i64::from_str_radix(hex, 16).ok()
+    } else if let Some(binary) = lower.strip_prefix("0b") {
+        i64::from_str_radix(binary, 2).ok()
+    } else {
+        lower.parse().ok()
+    }
+}
+
+// Splits operands at top-level commas.
+fn split_operands(tokens: &[Token]) -> Vec<Vec<Token>> {
+    if tokens.is_empty() {
+        return Vec::new();
+    }
+    tokens.split(|token| token.is_punct(',')).map(|operand| operand.to_vec()).collect()
+}
+
+#[derive(Debug, Clone)]
+struct Location {
+    file: Rc<str>,
+    line: usize,
+}
+
+impl Location {
+    fn error(&self, column: usize, message: impl Into<String>) -> AsmError {
+        AsmError {
+            file: self.file.to_string(),
+            line: self.line,
+            column,
+            message: message.into(),
+        }
+    }
+}
+
+struct Macro {
+    params: Vec<String>,
+    body: Vec<(Location, Vec<Token>)>,
+}
+
+enum Statement {
+    Label { name: String, column: usize },
+    Constant { name: String, column: usize, value: Vec<Token> },
+    Op { mnemonic: String, column: usize, operands: Vec<Vec<Token>> },
+}
+
+// Register-like operands; anything else is an expression.
+enum Operand<'a> {
+    V(u8),
+    I,
+    IndirectI,
+    Dt,
+    St,
+    K,
+    F,
+    Hf,
+    B,
+    R,
+    Long(&'a [Token]),
+    Value(&'a [Token]),
+}
+
+impl<'a> Operand<'a> {
+    fn parse(tokens: &'a [Token]) -> Operand<'a> {
+        if let [first, rest @ ..] = tokens {
+            if first.is_keyword("long") && !rest.is_empty() {
+                return Operand::Long(rest);
+            }
+        }
+        if let [open, i, close] = tokens {
+            if open.is_punct('[') && i.is_keyword("i") && close.is_punct(']') {
+                return Operand::IndirectI;
+            }
+        }
+        let [token] = tokens else {
+            return Operand::Value(tokens);
+        };
+        let Some(name) = token.ident() else {
+            return Operand::Value(tokens);
+        };
+        match name.to_ascii_uppercase().as_str() {
+            "I" => Operand::I,
+            "DT" => Operand::Dt,
+            "ST" => Operand::St,
+            "K" => Operand::K,
+            "F" => Operand::F,
+            "HF" => Operand::Hf,
+            "B" => Operand::B,
+            "R" => Operand::R,
+            register => match register.strip_prefix('V').and_then(|x| u8::from_str_radix(x, 16).ok()) {
+                Some(x) if register.len() == 2 => Operand::V(x),
+                _ => Operand::Value(tokens),
+            },
+        }
+    }
+}
+
+// Assembles the mnemonics printed by `Instruction::display`, so that the text
+// listing from the disassembler reproduces the ROM it was made from.
+struct Assembler {
+    origin: u16,
+    statements: Vec<(Location, Statement)>,
+    macros: HashMap<String, Macro>,
+    labels: HashMap<String, u16>,
+    constants: HashMap<String, (Location, Vec<Token>)>,
+}
+
+impl Assembler {
+    fn new(origin: u16) -> Self {
+        Self {
+            origin,
+            statements: Vec::new(),
+            macros: HashMap::new(),
+            labels: HashMap::new(),
+            constants: HashMap::new(),
+        }
+    }
+
+    // Reads a source file, expanding includes and macros into statements.
+    fn read(&mut self, name: &str, text: &str, directory: &Path, depth: usize) -> Result<(), AsmError> {
+        let file: Rc<str> = Rc::from(name);
+        // A macro being defined: its name, parameters, body and where it started.
+        let mut defining: Option<(String, Macro, Location, usize)> = None;
+
+        for (index, text) in text.lines().enumerate() {
+            let location = Location { file: file.clone(), line: index + 1 };
+            let tokens = tokenize(text).map_err(|(column, message)| location.error(column, message))?;
+
+            if let Some((name, mut definition, start, column)) = defining.take() {
+                if tokens.first().is_some_and(|token| token.is_keyword("endm")) {
+                    self.macros.insert(name, definition);
+                } else {
+                    definition.body.push((location, tokens));
+                    defining = Some((name, definition, start, column));
+                }
+                continue;
+            }
+
+            match tokens.as_slice() {
+                [keyword, rest @ ..] if keyword.is_keyword("macro") => {
+                    let Some((name, params)) = rest.split_first() else {
+                        return Err(location.error(keyword.column, "macro needs a name"));
+                    };
+                    let Some(name) = name.ident() else {
+                        return Err(location.error(name.column, "invalid macro name"));
+                    };
+                    let params = split_operands(params)
+                        .iter()
+                        .map(|param| match param.as_slice() {
+                            [token] if token.ident().is_some() => Ok(token.ident().unwrap_or_default().to_string()),
+                            _ => Err(location.error(param.first().map_or(keyword.column, |token| token.column), "invalid macro parameter")),
+                        })
+                        .collect::<Result<Vec<_>, _>>()?;
+                    let definition = Macro { params, body: Vec::new() };
+                    defining = Some((name.to_string(), definition, location.clone(), keyword.column));
+                }
+                [keyword, path] if keyword.is_keyword("include") => {
+                    let TokenKind::Str(path_bytes) = &path.kind else {
+                        return Err(location.error(path.column, "include needs a quoted file name"));
+                    };
+                    if depth >= MAX_DEPTH {
+                        return Err(location.error(keyword.column, "includes nested too deeply"));
+                    }
+                    let path = directory.join(String::from_utf8_lossy(path_bytes).as_ref());
+                    let included = fs::read_to_string(&path)
+                        .map_err(|e| location.error(keyword.column, format!("cannot read '{}': {}", path.display(), e)))?;
+                    let included_directory = path.parent().map(Path::to_path_buf).unwrap_or_default();
+                    self.read(&path.display().to_string(), &included, &included_directory, depth + 1)?;
+                }
+                _ => self.statement(&location, tokens, 0)?,
+            }
+        }
+
+        match defining {
+            Some((name, _, start, column)) => Err(start.error(column, format!("macro '{}' has no endm", name))),
+            None => Ok(()),
+        }
+    }
+
+    fn statement(&mut self, location: &Location, mut tokens: Vec<Token>, depth: usize) -> Result<(), AsmError> {
+        // Any number of labels may precede a statement.
+        while let [label, colon, ..] = tokens.as_slice() {
+            let (Some(name), true) = (label.ident(), colon.is_punct(':')) else {
+                break;
+            };
+            let statement = Statement::Label { name: name.to_string(), column: label.column };
+            self.statements.push((location.clone(), statement));
+            tokens.drain(..2);
+        }
+
+        let Some((first, rest)) = tokens.split_first() else {
+            return Ok(());
+        };
+        let Some(name) = first.ident() else {
+            return Err(location.error(first.column, "expected an instruction"));
+        };
+
+        if let Some((equals, value)) = rest.split_first() {
+            if equals.is_punct('=') || equals.is_keyword("equ") {
+                if value.is_empty() {
+                    return Err(location.error(equals.column, "constant needs a value"));
+                }
+                let statement = Statement::Constant { name: name.to_string(), column: first.column, value: value.to_vec() };
+                self.statements.push((location.clone(), statement));
+                return Ok(());
+            }
+        }
+
+        if let Some(definition) = self.macros.get(name) {
+            if depth >= MAX_DEPTH {
+                return Err(location.error(first.column, "macros nested too deeply"));
+            }
+            let args = split_operands(rest);
+            if args.len() != definition.params.len() {
+                return Err(location.error(
+                    first.column,
+                    format!("macro '{}' takes {} arguments, got {}", name, definition.params.len(), args.len()),
+                ));
+            }
+            let expanded: Vec<(Location, Vec<Token>)> = definition.body
+                .iter()
+                .map(|(body_location, body)| {
+                    let tokens = body
+                        .iter()
+                        .flat_map(|token| {
+                            let param = token.ident().and_then(|ident| definition.params.iter().position(|param| param == ident));
+                            match param {
+                                Some(index) => args[index].clone(),
+                                None => vec![token.clone()],
+                            }
+                        })
+                        .collect();
+                    (body_location.clone(), tokens)
+                })
+                .collect();
+            for (body_location, tokens) in expanded {
+                self.statement(&body_location, tokens, depth + 1)?;
+            }
+            return Ok(());
+        }
+
+        let statement = Statement::Op {
+            mnemonic: name.to_ascii_uppercase(),
+            column: first.column,
+            operands: split_operands(rest),
+        };
+        self.statements.push((location.clone(), statement));
+        Ok(())
+    }
+
+    // First pass: lays out the program and records where each label lands.
+    fn layout(&mut self) -> Result<(), AsmError> {
+        let mut address = self.origin as usize;
+        for (location, statement) in &self.statements {
+            match statement {
+                Statement::Label { name, column } => {
+                    if self.labels.contains_key(name) || self.constants.contains_key(name) {
+                        return Err(location.error(*column, format!("'{}' is already defined", name)));
+                    }
+                    self.labels.insert(name.clone(), address as u16);
+                }
+                Statement::Constant { name, column, value } => {
+                    if self.labels.contains_key(name) || self.constants.contains_key(name) {
+                        return Err(location.error(*column, format!("'{}' is already defined", name)));
+                    }
+                    self.constants.insert(name.clone(), (location.clone(), value.clone()));
+                }
+                Statement::Op { mnemonic, column, operands } => {
+                    address += size(mnemonic, operands);
+                    if address > 0x10000 {
+                        return Err(location.error(*column, "program does not fit in 64K"));
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // Second pass: encodes every instruction and data directive.
+    fn emit(&self) -> Result<Vec<u8>, AsmError> {
+        let mut output = Vec::new();
+        for (location, statement) in &self.statements {
+            if let Statement::Op { mnemonic, column, operands } = statement {
+                self.encode(location, mnemonic, *column, operands, &mut output)?;
+            }
+        }
+        Ok(output)
+    }
+
+    fn encode(&self, location: &Location, mnemonic: &str, column: usize, operands: &[Vec<Token>], output: &mut Vec<u8>) -> Result<(), AsmError> {
+        if operands.iter().any(Vec::is_empty) {
+            return Err(location.error(column, format!("missing operand for {}", mnemonic)));
+        }
+
+        match mnemonic {
+            "DB" => {
+                for operand in operands {
+                    match operand.as_slice() {
+                        [Token { kind: TokenKind::Str(bytes), .. }] => output.extend_from_slice(bytes),
+                        _ => output.push(self.value(location, operand, -0x80, 0xFF)? as u8),
+                    }
+                }
+                return Ok(());
+            }
+            "DW" => {
+                for operand in operands {
+                    let word = self.value(location, operand, -0x8000, 0xFFFF)?;
+                    output.extend_from_slice(&word.to_be_bytes());
+                }
+                return Ok(());
+            }
+            _ => {}
+        }
+
+        // `JMP to ADDR` is how `Instruction::display` prints a plain jump.
+        let mut operands: Vec<Operand> = operands.iter().map(|operand| Operand::parse(operand)).collect();
+        if mnemonic == "JMP" || mnemonic == "JP" {
+            if let Some(Operand::Value([to, rest @ ..])) = operands.first() {
+                if to.is_keyword("to") && !rest.is_empty() {
+                    operands[0] = Operand::Value(rest);
+                }
+            }
+        }
+
+        let address = |tokens| self.value(location, tokens, 0, 0xFFF);
+        let byte = |tokens| self.value(location, tokens, -0x80, 0xFF).map(|value| value & 0xFF);
+        let nibble = |tokens| self.value(location, tokens, 0, 0xF);
+        let xy = |x: u8, y: u8| (x as u16) << 8 | (y as u16) << 4;
+        let x = |x: u8| (x as u16) << 8;
+
+        use Operand::*;
+        let opcode = match (mnemonic, operands.as_slice()) {
+            ("CLS", []) => 0x00E0,
+            ("RET", []) => 0x00EE,
+            ("SYS", [Value(nnn)]) => address(nnn)?,
+            ("JMP" | "JP", [Value(nnn)]) => 0x1000 | address(nnn)?,
+            ("JMP" | "JP", [V(0), Value(nnn)]) => 0xB000 | address(nnn)?,
+            ("CALL", [Value(nnn)]) => 0x2000 | address(nnn)?,
+            ("SE", [V(vx), Value(kk)]) => 0x3000 | x(*vx) | byte(kk)?,
+            ("SNE", [V(vx), Value(kk)]) => 0x4000 | x(*vx) | byte(kk)?,
+            ("SE", [V(vx), V(vy)]) => 0x5000 | xy(*vx, *vy),
+            ("SAVE", [V(vx), V(vy)]) => 0x5002 | xy(*vx, *vy),
+            ("LOAD", [V(vx), V(vy)]) => 0x5003 | xy(*vx, *vy),
+            ("LD", [V(vx), Value(kk)]) => 0x6000 | x(*vx) | byte(kk)?,
+            ("ADD", [V(vx), Value(kk)]) => 0x7000 | x(*vx) | byte(kk)?,
+            ("LD", [V(vx), V(vy)]) => 0x8000 | xy(*vx, *vy),
+            ("OR", [V(vx), V(vy)]) => 0x8001 | xy(*vx, *vy),
+            ("AND", [V(vx), V(vy)]) => 0x8002 | xy(*vx, *vy),
+            ("XOR", [V(vx), V(vy)]) => 0x8003 | xy(*vx, *vy),
+            ("ADD", [V(vx), V(vy)]) => 0x8004 | xy(*vx, *vy),
+            ("SUB", [V(vx), V(vy)]) => 0x8005 | xy(*vx, *vy),
+            ("SHR", [V(vx), V(vy)]) => 0x8006 | xy(*vx, *vy),
+            ("SHR", [V(vx)]) => 0x8006 | xy(*vx, *vx),
+            ("SUBN", [V(vx), V(vy)]) => 0x8007 | xy(*vx, *vy),
+            ("SHL", [V(vx), V(vy)]) => 0x800E | xy(*vx, *vy),
+            ("SHL", [V(vx)]) => 0x800E | xy(*vx, *vx),
+            ("SNE", [V(vx), V(vy)]) => 0x9000 | xy(*vx, *vy),
+            ("LD", [I, Value(nnn)]) => 0xA000 | address(nnn)?,
+            ("RND", [V(vx), Value(kk)]) => 0xC000 | x(*vx) | byte(kk)?,
+            ("DRW", [V(vx), V(vy), Value(n)]) => 0xD000 | xy(*vx, *vy) | nibble(n)?,
+            ("SKP", [V(vx)]) => 0xE09E | x(*vx),
+            ("SKNP", [V(vx)]) => 0xE0A1 | x(*vx),
+            ("LD", [I, Long(nnnn)]) => {
+                let nnnn = self.value(location, nnnn, 0, 0xFFFF)?;
+                output.extend_from_slice(&[0xF0, 0x00]);
+                output.extend_from_slice(&nnnn.to_be_bytes());
+                return Ok(());
+            }
+            ("PLANE", [Value(n)]) => 0xF001 | nibble(n)? << 8,
+            ("AUDIO", []) => 0xF002,
+            ("LD", [V(vx), Dt]) => 0xF007 | x(*vx),
+            ("LD", [V(vx), K]) => 0xF00A | x(*vx),
+            ("LD", [Dt, V(vx)]) => 0xF015 | x(*vx),
+            ("LD", [St, V(vx)]) => 0xF018 | x(*vx),
+            ("ADD", [I, V(vx)]) => 0xF01E | x(*vx),
+            ("LD", [F, V(vx)]) => 0xF029 | x(*vx),
+            ("LD", [Hf, V(vx)]) => 0xF030 | x(*vx),
+            ("LD", [B, V(vx)]) => 0xF033 | x(*vx),
+            ("PITCH", [V(vx)]) => 0xF03A | x(*vx),
+            ("LD", [IndirectI, V(vx)]) => 0xF055 | x(*vx),
+            ("LD", [V(vx), IndirectI]) => 0xF065 | x(*vx),
+            ("LD", [R, V(vx)]) => 0xF075 | x(*vx),
+            ("LD", [V(vx), R]) => 0xF085 | x(*vx),
+            ("SCD", [Value(n)]) => 0x00C0 | nibble(n)?,
+            ("SCU", [Value(n)]) => 0x00D0 | nibble(n)?,
+            ("SCR", []) => 0x00FB,
+            ("SCL", []) => 0x00FC,
+            ("EXIT", []) => 0x00FD,
+            ("LOW", []) => 0x00FE,
+            ("HIGH", []) => 0x00FF,
+            _ if is_mnemonic(mnemonic) => return Err(location.error(column, format!("invalid operands for {}", mnemonic))),
+            _ => return Err(location.error(column, format!("unknown instruction '{}'", mnemonic))),
+        };
+        output.extend_from_slice(&opcode.to_be_bytes());
+        Ok(())
+    }
+
+    // Evaluates an expression and checks that it fits in `min..=max`.
+    fn value(&self, location: &Location, tokens: &[Token], min: i64, max: i64) -> Result<u16, AsmError> {
+        let value = self.evaluate(location, tokens, 0)?;
+        if value < min || value > max {
+            let column = tokens.first().map_or(0, |token| token.column);
+            return Err(location.error(column, format!("value {} out of range {}..={}", value, min, max)));
+        }
+        Ok(value as u16)
+    }
+
+    // Expressions are numbers, labels and constants joined by `+` and `-`,
+    // with parentheses for grouping.
+    fn evaluate(&self, location: &Location, tokens: &[Token], depth: usize) -> Result<i64, AsmError> {
+        let mut pos = 0;
+        let value = self.expression(location, tokens, &mut pos, depth)?;
+        match tokens.get(pos) {
+            Some(token) => Err(location.error(token.column, "unexpected token in expression")),
+            None => Ok(value),
+        }
+    }
+
+    fn expression(&self, location: &Location, tokens: &[Token], pos: &mut usize, depth: usize) -> Result<i64, AsmError> {
+        let mut value = self.term(location, tokens, pos, depth)?;
+        while let Some(token) = tokens.get(*pos) {
+            let sign = match token.kind {
+                TokenKind::Punct('+') => 1,
+                TokenKind::Punct('-') => -1,
+                _ => break,
+            };
+            *pos += 1;
+            value += sign * self.term(location, tokens, pos, depth)?;
+        }
+        Ok(value)
+    }
+
+    fn term(&self, location: &Location, tokens: &[Token], pos: &mut usize, depth: usize) -> Result<i64, AsmError> {
+        let Some(token) = tokens.get(*pos) else {
+            let column = tokens.last().map_or(0, |token| token.column);
+            return Err(location.error(column, "expected a value"));
+        };
+        *pos += 1;
+        match &token.kind {
+            TokenKind::Number(value) => Ok(*value),
+            TokenKind::Punct('-') => Ok(-self.term(location, tokens, pos, depth)?),
+            TokenKind::Punct('(') => {
+                let value = self.expression(location, tokens, pos, depth)?;
+                match tokens.get(*pos) {
+                    Some(close) if close.is_punct(')') => {
+                        *pos += 1;
+                        Ok(value)
+                    }
+                    _ => Err(location.error(token.column, "unclosed parenthesis")),
+                }
+            }
+            TokenKind::Ident(name) => {
+                if let Some(&address) = self.labels.get(name) {
+                    return Ok(address as i64);
+                }
+                let Some((defined_at, value)) = self.constants.get(name) else {
+                    return Err(location.error(token.column, format!("undefined symbol '{}'", name)));
+                };
+                if depth >= MAX_DEPTH {
+                    return Err(location.error(token.column, format!("constant '{}' refers to itself", name)));
+                }
+                self.evaluate(defined_at, value, depth + 1)
+            }
+            _ => Err(location.error(token.column, "expected a value")),
+        }
+    }
+}
+
+// Instruction length in bytes, known before any symbol is resolved.
+fn size(mnemonic: &str, operands: &[Vec<Token>]) -> usize {
+    match mnemonic {
+        "DB" => operands
+            .iter()
+            .map(|operand| match operand.as_slice() {
+                [Token { kind: TokenKind::Str(bytes), .. }] => bytes.len(),
+                _ => 1,
+            })
+            .sum(),
+        "DW" => 2 * operands.len(),
+        "LD" if operands.get(1).is_some_and(|operand| matches!(Operand::parse(operand), Operand::Long(_))) => 4,
+        _ => 2,
+    }
+}
+
+fn is_mnemonic(mnemonic: &str) -> bool {
+    const MNEMONICS: [&str; 33] = [
+        "CLS", "RET", "SYS", "JMP", "JP", "CALL", "SE", "SNE", "LD", "ADD", "OR", "AND", "XOR", "SUB", "SHR", "SUBN",
+        "SHL", "RND", "DRW", "SKP", "SKNP", "SCD", "SCU", "SCR", "SCL", "EXIT", "LOW", "HIGH", "SAVE", "LOAD",
+        "PLANE", "AUDIO", "PITCH",
+    ];
+    MNEMONICS.contains(&mnemonic)
+}
+
+// Assembles `source` for loading at `origin`. `name` is used in error
+// messages; includes are resolved against the current directory.
+pub fn assemble(source: &str, name: &str, origin: u16) -> Result<Vec<u8>, AsmError> {
+    assemble_in(source, name, Path::new(""), origin)
+}
+
+// Assembles a file, resolving includes relative to it.
+pub fn assemble_file(path: &Path, origin: u16) -> Result<Vec<u8>, AsmError> {
+    let name = path.display().to_string();
+    let source = fs::read_to_string(path).map_err(|e| AsmError {
+        file: name.clone(),
+        line: 0,
+        column: 0,
+        message: format!("cannot read file: {}", e),
+    })?;
+    let directory = path.parent().map(Path::to_path_buf).unwrap_or_default();
+    assemble_in(&source, &name, &directory, origin)
+}
+
+fn assemble_in(source: &str, name: &str, directory: &Path, origin: u16) -> Result<Vec<u8>, AsmError> {
+    let mut assembler = Assembler::new(origin);
+    assembler.read(name, source, directory, 0)?;
+    assembler.layout()?;
+    assembler.emit()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::disasm::{self, Format};
+    use crate::random::{RandomSource, Xorshift};
+
+    fn listing(rom: &[u8]) -> String {
+        let mut text = Vec::new();
+        disasm::disassemble(rom, 0x200).write(Format::Text, &mut text).expect("writes to memory");
+        String::from_utf8(text).expect("listings are UTF-8")
+    }
+
+    fn error(source: &str) -> AsmError {
+        assemble(source, "test.asm", 0x200).expect_err("should not assemble")
+    }
+
+    #[test]
+    fn random_roms_round_trip() {
+        let mut rng = Xorshift::new(0x8);
+        for _ in 0..1000 {
+            let len = rng.next_byte(&[]) as usize + 1;
+            let rom: Vec<u8> = (0..len).map(|_| rng.next_byte(&[])).collect();
+            let text = listing(&rom);
+            let assembled = assemble(&text, "listing", 0x200).unwrap_or_else(|e| panic!("{}\n{}", e, text));
+            assert_eq!(assembled, rom, "{}", text);
+        }
+    }
+
+    // The test programs have real control flow, sprites and data to tell apart.
+    #[test]
+    fn test_programs_round_trip() {
+        let roms = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/roms");
+        for name in ["opcodes", "flags", "quirks", "keypad", "beep", "logo"] {
+            let rom = assemble_file(&roms.join(format!("{}.asm", name)), 0x200).expect("test program assembles");
+            let text = listing(&rom);
+            assert_eq!(assemble(&text, name, 0x200), Ok(rom), "{}", text);
+        }
+    }
+
+    #[test]
+    fn macros_substitute_their_arguments() {
+        let source = "
+            macro draw_at x, y, rows
+                LD V0, x
+                LD V1, y
+                DRW V0, V1, rows
+            endm
+            draw_at 0x10, 0x08, 5
+            draw_at 1, 2, 3
+        ";
+        let expected = [0x60, 0x10, 0x61, 0x08, 0xD0, 0x15, 0x60, 0x01, 0x61, 0x02, 0xD0, 0x13];
+        assert_eq!(assemble(source, "test.asm", 0x200), Ok(expected.to_vec()));
+        assert_eq!(error("macro twice a\nLD V0, a\nendm\ntwice 1, 2").message, "macro 'twice' takes 1 arguments, got 2");
+        assert_eq!(error("CLS\nmacro open\nCLS").line, 2);
+    }
+
+    #[test]
+    fn constants_and_labels_in_expressions() {
+        let source = "
+            SPEED = 4
+            LIMIT equ SPEED + 2
+            start:
+                LD V0, SPEED
+                SE V0, LIMIT - 1
+                JP start + 2
+            table:
+                db SPEED, (LIMIT - SPEED) + 0x10
+        ";
+        let expected = [0x60, 0x04, 0x30, 0x05, 0x12, 0x02, 0x04, 0x12];
+        assert_eq!(assemble(source, "test.asm", 0x200), Ok(expected.to_vec()));
+        assert_eq!(error("A = B\nB = A\nLD V0, A").message, "constant 'A' refers to itself");
+        assert_eq!(error("x:\nx:").message, "'x' is already defined");
+    }
+
+    #[test]
+    fn includes_resolve_against_the_including_file() {
+        let directory = std::env::temp_dir().join(format!("chip8-asm-include-{}", std::process::id()));
+        fs::create_dir_all(directory.join("lib")).expect("can create a temporary directory");
+        fs::write(directory.join("main.asm"), "CALL helper\ninclude \"lib/helper.asm\"\nCLS\n").expect("can write");
+        fs::write(directory.join("lib/helper.asm"), "helper:\ninclude \"ret.asm\"\n").expect("can write");
+        fs::write(directory.join("lib/ret.asm"), "RET\nBOGUS\n").expect("can write");
+
+        let result = assemble_file(&directory.join("main.asm"), 0x200);
+        let e = result.expect_err("the innermost include has an error");
+        assert!(e.file.ends_with("ret.asm"), "{}", e);
+        assert_eq!((e.line, e.column), (2, 1));
+
+        fs::write(directory.join("lib/ret.asm"), "RET\n").expect("can write");
+        let result = assemble_file(&directory.join("main.asm"), 0x200);
+        fs::remove_dir_all(&directory).expect("can clean up");
+        assert_eq!(result, Ok(vec![0x22, 0x02, 0x00, 0xEE, 0x00, 0xE0]));
+    }
+
+    #[test]
+    fn errors_point_at_the_line_and_column() {
+        let e = error("CLS\n\n    LD V0, nowhere ; comment\n");
+        assert_eq!((e.file.as_str(), e.line, e.column), ("test.asm", 3, 12));
+        assert_eq!(e.message, "undefined symbol 'nowhere'");
+        assert_eq!(e.to_string(), "test.asm:3:12: undefined symbol 'nowhere'");
+
+        // Errors in a macro body point into the body.
+        let e = error("macro bad\n  LD V0, $\nendm\nbad");
+        assert_eq!((e.line, e.column), (2, 10));
+        let e = error("macro bad\n  LD V0, oops\nendm\nCLS\nbad");
+        assert_eq!((e.line, e.column), (2, 10));
+    }
+}
@@ -0,0 +1,218 @@
+use std::f32::consts::TAU;
+use std::fs::File;
+use std::io::{self, BufWriter, Seek, SeekFrom, Write};
+use std::str::FromStr;
+
+use crate::chip8::Chip8;
+
+pub const SAMPLE_RATE: u32 = 44100;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Waveform {
+    #[default]
+    Square,
+    Sine,
+    Triangle,
+    Sawtooth,
+}
+
+impl Waveform {
+    // `phase` is in the range [0, 1).
+    fn sample(&self, phase: f32) -> f32 {
+        match self {
+            Waveform::Square => if phase < 0.5 { 1.0 } else { -1.0 },
+            Waveform::Sine => (phase * TAU).sin(),
+            Waveform::Triangle => 1.0 - 4.0 * (phase - 0.5).abs(),
+            Waveform::Sawtooth => 2.0 * phase - 1.0,
+        }
+    }
+}
+
+impl FromStr for Waveform {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "square" => Ok(Waveform::Square),
+            "sine" => Ok(Waveform::Sine),
+            "triangle" => Ok(Waveform::Triangle),
+            "sawtooth" | "saw" => Ok(Waveform::Sawtooth),
+            _ => Err(format!("unknown waveform '{}'", s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ToneSettings {
+    pub frequency: f32,
+    // Between 0.0 and 1.0.
+    pub volume: f32,
+    pub waveform: Waveform,
+    pub muted: bool,
+}
+
+impl Default for ToneSettings {
+    fn default() -> Self {
+        Self {
+            frequency: 440.0,
+            volume: 0.25,
+            waveform: Waveform::Square,
+            muted: false,
+        }
+    }
+}
+
+// Produces the buzzer signal. A tone sounds while the sound timer is running;
+// once an XO-CHIP program has loaded a non-empty audio pattern, the pattern is
+// played back at the rate set by the pitch register instead.
+pub struct ToneGenerator {
+    pub settings: ToneSettings,
+    sample_rate: u32,
+    active: bool,
+    pattern: [u8; 16],
+    pitch: u8,
+    phase: f32,
+}
+
+impl ToneGenerator {
+    pub fn new(sample_rate: u32, settings: ToneSettings) -> Self {
+        Self {
+            settings,
+            sample_rate,
+            active: false,
+            pattern: [0; 16],
+            pitch: 64,
+            phase: 0.0,
+        }
+    }
+
+    pub fn update(&mut self, chip8: &Chip8) {
+        self.active = chip8.sound_active();
+        self.pattern = chip8.audio_pattern;
+        self.pitch = chip8.pitch;
+    }
+
+    pub fn fill(&mut self, out: &mut [f32]) {
+        if !self.active || self.settings.muted {
+            out.fill(0.0);
+            self.phase = 0.0;
+            return;
+        }
+
+        let use_pattern = self.pattern.iter().any(|&byte| byte != 0);
+        // The pattern holds 128 one-bit samples played at 4000 * 2^((pitch - 64) / 48) Hz.
+        let step = if use_pattern {
+            4000.0 * 2f32.powf((self.pitch as f32 - 64.0) / 48.0) / 128.0 / self.sample_rate as f32
+        } else {
+            self.settings.frequency / self.sample_rate as f32
+        };
+
+        for sample in out.iter_mut() {
+            let value = if use_pattern {
+                let bit = (self.phase * 128.0) as usize;
+                if self.pattern[bit / 8] & (0x80 >> (bit % 8)) != 0 { 1.0 } else { -1.0 }
+            } else {
+                self.settings.waveform.sample(self.phase)
+            };
+            *sample = value * self.settings.volume;
+            self.phase = (self.phase + step).fract();
+        }
+    }
+}
+
+// Receives the machine state once per 60 Hz frame.
+pub trait AudioSink {
+    fn frame(&mut self, chip8: &Chip8) -> Result<(), String>;
+    fn set_muted(&mut self, muted: bool);
+    fn finish(&mut self) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+pub struct NullSink;
+
+impl AudioSink for NullSink {
+    fn frame(&mut self, _chip8: &Chip8) -> Result<(), String> {
+        Ok(())
+    }
+
+    fn set_muted(&mut self, _muted: bool) {}
+}
+
+// Records 16-bit mono PCM, one frame's worth of samples per call.
+pub struct WavSink {
+    writer: BufWriter<File>,
+    generator: ToneGenerator,
+    samples_written: u32,
+    frames: u64,
+    buffer: Vec<f32>,
+}
+
+impl WavSink {
+    pub fn create(path: &str, settings: ToneSettings) -> io::Result<Self> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        write_wav_header(&mut writer, 0)?;
+        Ok(Self {
+            writer,
+            generator: ToneGenerator::new(SAMPLE_RATE, settings),
+            samples_written: 0,
+            frames: 0,
+            buffer: Vec::new(),
+        })
+    }
+
+    fn write_frame(&mut self, chip8: &Chip8) -> io::Result<()> {
+        // Spread the rounding so the file stays in step with 60 frames per second.
+        let start = self.frames * SAMPLE_RATE as u64 / 60;
+        self.frames += 1;
+        let end = self.frames * SAMPLE_RATE as u64 / 60;
+
+        self.buffer.resize((end - start) as usize, 0.0);
+        self.generator.update(chip8);
+        self.generator.fill(&mut self.buffer);
+        for &sample in &self.buffer {
+            let value = (sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16;
+            self.writer.write_all(&value.to_le_bytes())?;
+        }
+        self.samples_written += self.buffer.len() as u32;
+        Ok(())
+    }
+}
+
+impl AudioSink for WavSink {
+    fn frame(&mut self, chip8: &Chip8) -> Result<(), String> {
+        self.write_frame(chip8).map_err(|e| format!("failed to write WAV data: {}", e))
+    }
+
+    fn set_muted(&mut self, muted: bool) {
+        self.generator.settings.muted = muted;
+    }
+
+    fn finish(&mut self) -> Result<(), String> {
+        let data_size = self.samples_written * 2;
+        self.writer.seek(SeekFrom::Start(0))
+            .and_then(|_| write_wav_header(&mut self.writer, data_size))
+            .and_then(|_| self.writer.flush())
+            .map_err(|e| format!("failed to finish WAV file: {}", e))
+    }
+}
+
+fn write_wav_header<W: Write>(writer: &mut W, data_size: u32) -> io::Result<()> {
+    let channels: u16 = 1;
+    let bits_per_sample: u16 = 16;
+    let block_align = channels * bits_per_sample / 8;
+
+    writer.write_all(b"RIFF")?;
+    writer.write_all(&(36 + data_size).to_le_bytes())?;
+    writer.write_all(b"WAVE")?;
+    writer.write_all(b"fmt ")?;
+    writer.write_all(&16u32.to_le_bytes())?;
+    writer.write_all(&1u16.to_le_bytes())?;
+    writer.write_all(&channels.to_le_bytes())?;
+    writer.write_all(&SAMPLE_RATE.to_le_bytes())?;
+    writer.write_all(&(SAMPLE_RATE * block_align as u32).to_le_bytes())?;
+    writer.write_all(&block_align.to_le_bytes())?;
+    writer.write_all(&bits_per_sample.to_le_bytes())?;
+    writer.write_all(b"data")?;
+    writer.write_all(&data_size.to_le_bytes())
+}
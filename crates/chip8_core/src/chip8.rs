@@ -0,0 +1,2028 @@
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::error::{Chip8Error, ErrorPolicies, ErrorPolicy};
+use crate::font::{FontStyle, BIG_FONT, BIG_FONT_SIZE, DEFAULT_FONT_BASE, FONT_SIZE};
+use crate::quirks::Quirks;
+use crate::random::{self, RandomSource, Xorshift};
+
+pub const PROGRAM_START: usize = 0x200;
+pub const MEMORY_SIZE: usize = 0x1000;
+pub const XO_CHIP_MEMORY_SIZE: usize = 0x10000;
+pub const PLANE_COUNT: usize = 4;
+pub const SCREEN_WIDTH: usize = 128;
+pub const SCREEN_HEIGHT: usize = 64;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum RomError {
+    Empty,
+    TooLarge { size: usize, max: usize },
+}
+
+impl fmt::Display for RomError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RomError::Empty => write!(f, "ROM is empty"),
+            RomError::TooLarge { size, max } => {
+                write!(f, "ROM is {} bytes but only {} bytes fit above {:#X}", size, max, PROGRAM_START)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AccessKind {
+    Read,
+    Write,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryAccess {
+    pub address: usize,
+    pub kind: AccessKind,
+    pub value: u8,
+}
+
+// State of an `FX0A` in progress. As on the COSMAC VIP, the key is only
+// stored in VX once it has been pressed and released again.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyWait {
+    Press { x: u8 },
+    Release { x: u8, key: u8 },
+}
+
+pub trait Instruction {
+    fn execute(&self, chip8: &mut Chip8) -> Result<(), Chip8Error>;
+    fn display(&self) -> String;
+}
+
+pub struct Chip8 {
+    // Code that writes here directly rather than through `write_memory` must
+    // call `invalidate_code` afterwards.
+    pub memory: Vec<u8>,
+    pub v: [u8; 16], 
+    pub i: u16, // Index register
+    pub pc: u16, // Program counter
+    // Laid out with a row stride of `width()`, so only the first
+    // `width() * height()` pixels are in use in low-resolution mode. Each
+    // pixel holds one bit per bitplane, i.e. an index into a 16-colour palette.
+    pub gfx: [u8; SCREEN_WIDTH * SCREEN_HEIGHT],
+    pub hires: bool,
+    pub delay_timer: u8, 
+    pub sound_timer: u8, 
+    pub stack: [u16; 16], 
+    pub sp: usize,
+    pub keypad: [u8; 16],
+    pub font_style: FontStyle,
+    pub font_base: u16,
+    pub error_policies: ErrorPolicies,
+    pub quirks: Quirks,
+    pub waiting_for_vblank: bool,
+    // Set by `FX0A`; no instructions run until it is cleared.
+    pub key_wait: Option<KeyWait>,
+    pub exited: bool,
+    pub rpl: [u8; 16],
+    pub plane_mask: u8,
+    pub audio_pattern: [u8; 16],
+    pub pitch: u8,
+    // Seeded from entropy; replace it for reproducible runs.
+    pub rng: Box<dyn RandomSource>,
+    // When set, data reads and writes made by instructions are appended to
+    // `access_log`; whoever enables it is responsible for draining the log.
+    pub log_memory_access: bool,
+    pub access_log: Vec<MemoryAccess>,
+    pub(crate) rom: Vec<u8>,
+    instruction_address: u16,
+    // Instructions decoded so far, by address. Sized to `memory` on first use
+    // and cleared by `invalidate_code`.
+    code_cache: Vec<Option<Op>>,
+    // Changes whenever the code may have been replaced wholesale, including
+    // for a new machine, so other decoders know to start over.
+    pub(crate) code_epoch: u64,
+    // The lowest and highest addresses written by instructions since the
+    // owner last took it.
+    pub(crate) written: Option<(usize, usize)>,
+}
+
+fn next_code_epoch() -> u64 {
+    static EPOCH: AtomicU64 = AtomicU64::new(0);
+    EPOCH.fetch_add(1, Ordering::Relaxed)
+}
+
+impl Chip8 {
+    pub fn new() -> Self {
+        Self::with_memory_size(MEMORY_SIZE)
+    }
+
+    // XO-CHIP programs expect `XO_CHIP_MEMORY_SIZE` bytes of memory.
+    pub fn with_memory_size(size: usize) -> Self {
+        let mut chip8 = Self {
+            memory: vec![0; size],
+            v: [0; 16],
+            i: 0,
+            pc: PROGRAM_START as u16,
+            gfx: [0; SCREEN_WIDTH * SCREEN_HEIGHT],
+            hires: false,
+            delay_timer: 0,
+            sound_timer: 0,
+            stack: [0; 16],
+            sp: 0,
+            keypad: [0; 16],
+            font_style: FontStyle::default(),
+            font_base: DEFAULT_FONT_BASE,
+            error_policies: ErrorPolicies::default(),
+            quirks: Quirks::default(),
+            waiting_for_vblank: false,
+            key_wait: None,
+            exited: false,
+            rpl: [0; 16],
+            plane_mask: 1,
+            audio_pattern: [0; 16],
+            pitch: 64,
+            rng: Box::new(Xorshift::new(random::entropy_seed())),
+            log_memory_access: false,
+            access_log: Vec::new(),
+            rom: Vec::new(),
+            instruction_address: PROGRAM_START as u16,
+            code_cache: Vec::new(),
+            code_epoch: next_code_epoch(),
+            written: None,
+        };
+        chip8.install_font();
+        chip8
+    }
+
+    pub fn reset(&mut self) {
+        let mut fresh = Self::with_memory_size(self.memory.len());
+        // A base written straight into `font_base` that doesn't fit leaves
+        // the default font in place.
+        let _ = fresh.set_font(self.font_style, self.font_base);
+        fresh.error_policies = self.error_policies;
+        fresh.quirks = self.quirks;
+        fresh.log_memory_access = self.log_memory_access;
+        fresh.rng = self.rng.clone_box();
+
+        let rom = std::mem::take(&mut self.rom);
+        fresh.memory[PROGRAM_START..PROGRAM_START + rom.len()].copy_from_slice(&rom);
+        fresh.rom = rom;
+        *self = fresh;
+    }
+
+    // Both fonts must fit below the program area:
+    // `font_base + FONT_SIZE + BIG_FONT_SIZE <= 0x200`. A base that doesn't
+    // leave room for them is rejected and the current font kept.
+    pub fn set_font(&mut self, style: FontStyle, base: u16) -> Result<(), Chip8Error> {
+        let end = base as usize + FONT_SIZE + BIG_FONT_SIZE;
+        if end > PROGRAM_START {
+            return Err(Chip8Error::FontOutOfBounds { base, end });
+        }
+        let old_base = self.font_base as usize;
+        self.memory[old_base..old_base + FONT_SIZE + BIG_FONT_SIZE].fill(0);
+        self.font_style = style;
+        self.font_base = base;
+        self.install_font();
+        self.invalidate_code();
+        Ok(())
+    }
+
+    fn install_font(&mut self) {
+        let base = self.font_base as usize;
+        self.memory[base..base + FONT_SIZE].copy_from_slice(self.font_style.sprites());
+        self.memory[base + FONT_SIZE..base + FONT_SIZE + BIG_FONT_SIZE].copy_from_slice(&BIG_FONT);
+    }
+
+    pub fn big_font_base(&self) -> u16 {
+        self.font_base + FONT_SIZE as u16
+    }
+
+    pub fn execute_instruction(&mut self, instruction: &dyn Instruction) -> Result<(), Chip8Error> {
+        instruction.execute(self)
+    }
+
+    // True while the CPU is stalled waiting for the next 60 Hz tick or for
+    // a key; cycles still count, but no instructions run.
+    pub fn blocked(&self) -> bool {
+        self.waiting_for_vblank || self.key_wait.is_some()
+    }
+
+    // For frontends that want to show the program is waiting on the player.
+    pub fn awaiting_key(&self) -> bool {
+        self.key_wait.is_some()
+    }
+
+    // On a halting fault the PC is left on the faulting instruction.
+    pub fn emulate_cycle(&mut self) -> Result<(), Chip8Error> {
+        if let Some(wait) = self.key_wait {
+            self.poll_key_wait(wait);
+            return Ok(());
+        }
+        if self.waiting_for_vblank || self.exited {
+            return Ok(());
+        }
+
+        if let Some(op) = self.code_cache.get(self.pc as usize).copied().flatten() {
+            return self.run_op(op);
+        }
+        self.instruction_address = self.pc;
+        let result = match self.fetch_decode() {
+            Ok(Some(op)) => op.execute(self),
+            Ok(None) => Ok(()),
+            Err(e) => Err(e),
+        };
+        self.settle(result)
+    }
+
+    // Runs `op` as the instruction at the PC, for callers that decoded it
+    // themselves. The caller checks `blocked` and `exited` first.
+    pub(crate) fn run_op(&mut self, op: Op) -> Result<(), Chip8Error> {
+        self.instruction_address = self.pc;
+        self.pc = self.pc.wrapping_add(op.size() as u16);
+        let result = op.execute(self);
+        self.settle(result)
+    }
+
+    // Applies the unknown opcode policy, and on a fault moves the PC back.
+    pub(crate) fn settle(&mut self, result: Result<(), Chip8Error>) -> Result<(), Chip8Error> {
+        match result {
+            Err(Chip8Error::UnknownOpcode { .. }) if self.error_policies.unknown_opcode != ErrorPolicy::Halt => Ok(()),
+            Err(e) => {
+                self.pc = self.instruction_address;
+                Err(e)
+            }
+            Ok(()) => Ok(()),
+        }
+    }
+
+    // Decodes the instruction at the PC and steps over it, caching it unless
+    // the fetch went through an error policy. `None` means there was nothing
+    // to run and the PC was left alone.
+    fn fetch_decode(&mut self) -> Result<Option<Op>, Chip8Error> {
+        let address = self.pc as usize;
+        let Some(opcode) = self.fetch()? else {
+            return Ok(None);
+        };
+        self.pc = self.pc.wrapping_add(2);
+        let operand = if opcode == 0xF000 { self.fetch_operand()? } else { 0 };
+        let op = decode(opcode, operand);
+
+        if self.code_cache.len() != self.memory.len() {
+            self.code_cache = vec![None; self.memory.len()];
+        }
+        if self.instruction_address as usize == address && address + op.size() <= self.memory.len() {
+            self.code_cache[address] = Some(op);
+        }
+        Ok(Some(op))
+    }
+
+    // Forgets every decoded instruction, e.g. after loading new code.
+    pub fn invalidate_code(&mut self) {
+        self.code_cache.fill(None);
+        self.code_epoch = next_code_epoch();
+    }
+
+    fn poll_key_wait(&mut self, wait: KeyWait) {
+        match wait {
+            KeyWait::Press { x } => {
+                if let Some(key) = self.keypad.iter().position(|&state| state != 0) {
+                    self.key_wait = Some(KeyWait::Release { x, key: key as u8 });
+                }
+            }
+            KeyWait::Release { x, key } => {
+                if self.keypad[key as usize] == 0 {
+                    self.v[x as usize] = key;
+                    self.key_wait = None;
+                }
+            }
+        }
+    }
+
+    // With the `Ignore` policy a PC past the end of memory fetches `None`,
+    // and the machine idles there.
+    fn fetch(&mut self) -> Result<Option<u16>, Chip8Error> {
+        let len = self.memory.len();
+        let pc = self.pc as usize;
+        if pc + 1 < len {
+            return Ok(Some((self.memory[pc] as u16) << 8 | self.memory[pc + 1] as u16));
+        }
+
+        match self.error_policies.pc {
+            ErrorPolicy::Halt => Err(Chip8Error::PcOutOfRange { pc: self.pc }),
+            ErrorPolicy::Ignore => Ok(None),
+            ErrorPolicy::Wrap => {
+                self.pc = (pc % len) as u16;
+                self.instruction_address = self.pc;
+                let pc = self.pc as usize;
+                Ok(Some((self.memory[pc] as u16) << 8 | self.memory[(pc + 1) % len] as u16))
+            }
+        }
+    }
+
+    // Reads the second word of `F000 NNNN` and steps over it.
+    fn fetch_operand(&mut self) -> Result<u16, Chip8Error> {
+        let pc = self.pc as usize;
+        let operand = (self.read_code(pc)? as u16) << 8 | self.read_code(pc + 1)? as u16;
+        self.pc = self.pc.wrapping_add(2);
+        Ok(operand)
+    }
+
+    // Skips the next instruction, which is four bytes long for `F000 NNNN`.
+    pub fn skip_next(&mut self) -> Result<(), Chip8Error> {
+        let pc = self.pc as usize;
+        let long = self.read_code(pc)? == 0xF0 && self.read_code(pc + 1)? == 0x00;
+        self.pc = self.pc.wrapping_add(if long { 4 } else { 2 });
+        Ok(())
+    }
+
+    // Moves the selected bitplanes by `dx`, `dy` pixels, filling the
+    // uncovered area with blank pixels.
+    pub fn scroll(&mut self, dx: isize, dy: isize) {
+        let (width, height) = (self.width() as isize, self.height() as isize);
+        let mask = self.plane_mask;
+        let source = self.gfx;
+        for y in 0..height {
+            for x in 0..width {
+                let (sx, sy) = (x - dx, y - dy);
+                let moved = if sx >= 0 && sx < width && sy >= 0 && sy < height {
+                    source[(sx + sy * width) as usize] & mask
+                } else {
+                    0
+                };
+                let index = (x + y * width) as usize;
+                self.gfx[index] = (self.gfx[index] & !mask) | moved;
+            }
+        }
+    }
+
+    pub fn read_memory(&mut self, address: usize) -> Result<u8, Chip8Error> {
+        let value = self.read_code(address)?;
+        if self.log_memory_access {
+            self.access_log.push(MemoryAccess { address, kind: AccessKind::Read, value });
+        }
+        Ok(value)
+    }
+
+    pub fn write_memory(&mut self, address: usize, value: u8) -> Result<(), Chip8Error> {
+        if let Some(address) = self.resolve_address(address)? {
+            self.memory[address] = value;
+            // The byte may be part of an instruction starting up to three
+            // bytes earlier.
+            let start = address.saturating_sub(3);
+            if let Some(entries) = self.code_cache.get_mut(start..=address) {
+                entries.fill(None);
+            }
+            self.written = Some(match self.written {
+                Some((low, high)) => (low.min(address), high.max(address)),
+                None => (address, address),
+            });
+        }
+        if self.log_memory_access {
+            self.access_log.push(MemoryAccess { address, kind: AccessKind::Write, value });
+        }
+        Ok(())
+    }
+
+    // Reads from the instruction stream, which is not logged.
+    fn read_code(&self, address: usize) -> Result<u8, Chip8Error> {
+        match self.resolve_address(address)? {
+            Some(address) => Ok(self.memory[address]),
+            None => Ok(0),
+        }
+    }
+
+    // Applies the memory policy; `None` means the access should be dropped.
+    fn resolve_address(&self, address: usize) -> Result<Option<usize>, Chip8Error> {
+        if address < self.memory.len() {
+            return Ok(Some(address));
+        }
+
+        match self.error_policies.memory {
+            ErrorPolicy::Halt => Err(Chip8Error::MemoryOutOfBounds { target: address, address: self.instruction_address }),
+            ErrorPolicy::Ignore => Ok(None),
+            ErrorPolicy::Wrap => Ok(Some(address % self.memory.len())),
+        }
+    }
+
+    pub fn push_stack(&mut self, value: u16) -> Result<(), Chip8Error> {
+        if self.sp >= self.stack.len() {
+            match self.error_policies.stack {
+                ErrorPolicy::Halt => return Err(Chip8Error::StackOverflow { address: self.instruction_address }),
+                ErrorPolicy::Ignore => return Ok(()),
+                ErrorPolicy::Wrap => self.sp = 0,
+            }
+        }
+
+        self.stack[self.sp] = value;
+        self.sp += 1;
+        Ok(())
+    }
+
+    // With the `Ignore` policy an empty stack pops `None`.
+    pub fn pop_stack(&mut self) -> Result<Option<u16>, Chip8Error> {
+        if self.sp == 0 {
+            match self.error_policies.stack {
+                ErrorPolicy::Halt => return Err(Chip8Error::StackUnderflow { address: self.instruction_address }),
+                ErrorPolicy::Ignore => return Ok(None),
+                ErrorPolicy::Wrap => self.sp = self.stack.len(),
+            }
+        }
+
+        self.sp -= 1;
+        Ok(Some(self.stack[self.sp]))
+    }
+
+    pub fn max_rom_size(&self) -> usize {
+        self.memory.len() - PROGRAM_START
+    }
+
+    pub fn load_rom(&mut self, rom: &[u8]) -> Result<(), RomError> {
+        if rom.is_empty() {
+            return Err(RomError::Empty);
+        }
+        if rom.len() > self.max_rom_size() {
+            return Err(RomError::TooLarge { size: rom.len(), max: self.max_rom_size() });
+        }
+
+        self.memory[PROGRAM_START..PROGRAM_START + rom.len()].copy_from_slice(rom);
+        self.rom = rom.to_vec();
+        self.invalidate_code();
+        Ok(())
+    }
+
+    pub fn width(&self) -> usize {
+        if self.hires { SCREEN_WIDTH } else { SCREEN_WIDTH / 2 }
+    }
+
+    pub fn height(&self) -> usize {
+        if self.hires { SCREEN_HEIGHT } else { SCREEN_HEIGHT / 2 }
+    }
+
+    pub fn get_graphics(&self) -> &[u8] {
+        &self.gfx[..self.width() * self.height()]
+    }
+
+    pub fn set_key(&mut self, key: usize, pressed: bool) {
+        self.keypad[key] = if pressed { 1 } else { 0 };
+    }
+
+    // Runs `cycles` instructions followed by one 60 Hz timer tick, stopping
+    // early if the program exits.
+    pub fn run_frame(&mut self, cycles: u32) -> Result<(), Chip8Error> {
+        for _ in 0..cycles {
+            if self.exited {
+                break;
+            }
+            self.emulate_cycle()?;
+        }
+        self.tick();
+        Ok(())
+    }
+
+    pub fn sound_active(&self) -> bool {
+        self.sound_timer > 0
+    }
+
+    pub fn tick(&mut self) {
+        self.waiting_for_vblank = false;
+        self.rng.tick();
+
+        if self.delay_timer > 0 {
+            self.delay_timer -= 1;
+        }
+
+        if self.sound_timer > 0 {
+            self.sound_timer -= 1;
+        }
+    }
+}
+
+impl Default for Chip8 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Generates `Op`, which holds any one instruction by value and dispatches
+// with a `match`, so running it needs no allocation or virtual call.
+macro_rules! ops {
+    ($($name:ident),* $(,)?) => {
+        #[derive(Debug, Clone, Copy)]
+        pub enum Op {
+            $($name($name),)*
+        }
+
+        $(impl From<$name> for Op {
+            fn from(instruction: $name) -> Self {
+                Op::$name(instruction)
+            }
+        })*
+
+        impl Instruction for Op {
+            #[inline]
+            fn execute(&self, chip8: &mut Chip8) -> Result<(), Chip8Error> {
+                match self {
+                    $(Op::$name(instruction) => instruction.execute(chip8),)*
+                }
+            }
+
+            fn display(&self) -> String {
+                match self {
+                    $(Op::$name(instruction) => instruction.display(),)*
+                }
+            }
+        }
+
+        impl Op {
+            // The instruction at `address` as a closure that runs it the way
+            // `Chip8::run_op` would, with the dispatch on the variant and the
+            // PC arithmetic done up front. The block engine's threaded code.
+            pub(crate) fn threaded(self, address: u16) -> Threaded {
+                let next = address.wrapping_add(self.size() as u16);
+                match self {
+                    $(Op::$name(instruction) => Box::new(move |chip8: &mut Chip8| {
+                        chip8.instruction_address = address;
+                        chip8.pc = next;
+                        let result = instruction.execute(chip8);
+                        chip8.settle(result)
+                    }),)*
+                }
+            }
+        }
+    };
+}
+
+pub(crate) type Threaded = Box<dyn Fn(&mut Chip8) -> Result<(), Chip8Error>>;
+
+ops! {
+    Cls, Sys, Ret, Jmp, Call, SeVxByte, SneVxByte, SeVxVy, LdVxByte, AddVxByte,
+    LdVxVy, OrVxVy, AndVxVy, XorVxVy, AddVxVy, SubVxVy, ShrVxVy, SubnVxVy, ShlVxVy,
+    SneVxVy, LdIAddr, JmpV0Addr, RndVxByte, DrwVxVyNibble, SkpVx, SknpVx, LdVxDT,
+    LdVxK, LdDTVx, LdSTVx, AddIVx, LdFVx, LdBVx, LdIVx, LdVxI, InvalidInstruction,
+    ScdNibble, Scr, Scl, Exit, Low, High, LdHfVx, LdRVx, LdVxR, ScuNibble, SaveVxVy,
+    LoadVxVy, LdILong, Plane, Audio, PitchVx,
+}
+
+impl Op {
+    // In bytes; only `F000 NNNN` is two words long.
+    pub fn size(&self) -> usize {
+        if let Op::LdILong(_) = self { 4 } else { 2 }
+    }
+}
+
+// `operand` is the word following `opcode`, only used by `F000 NNNN`.
+pub fn decode(opcode: u16, operand: u16) -> Op {
+    match opcode & 0xF000 {
+        0x0000 => match opcode {
+            0x00E0 => Op::from(Cls),
+            0x00EE => Op::from(Ret),
+            0x00FB => Op::from(Scr),
+            0x00FC => Op::from(Scl),
+            0x00FD => Op::from(Exit),
+            0x00FE => Op::from(Low),
+            0x00FF => Op::from(High),
+            _ if opcode & 0xFFF0 == 0x00C0 => Op::from(ScdNibble { n: (opcode & 0x000F) as u8 }),
+            _ if opcode & 0xFFF0 == 0x00D0 => Op::from(ScuNibble { n: (opcode & 0x000F) as u8 }),
+            _ => Op::from(Sys { address: opcode & 0x0FFF }),
+        },
+        0x1000 => Op::from(Jmp { address: opcode & 0x0FFF }),
+        0x2000 => Op::from(Call { address: opcode & 0x0FFF }),
+        0x3000 => Op::from(SeVxByte {
+            x: ((opcode & 0x0F00) >> 8) as u8,
+            byte: (opcode & 0x00FF) as u8,
+        }),
+        0x4000 => Op::from(SneVxByte {
+            x: ((opcode & 0x0F00) >> 8) as u8,
+            byte: (opcode & 0x00FF) as u8,
+        }),
+        0x5000 => match opcode & 0x000F {
+            0x0000 => Op::from(SeVxVy {
+                x: ((opcode & 0x0F00) >> 8) as u8,
+                y: ((opcode & 0x00F0) >> 4) as u8,
+            }),
+            0x0002 => Op::from(SaveVxVy {
+                x: ((opcode & 0x0F00) >> 8) as u8,
+                y: ((opcode & 0x00F0) >> 4) as u8,
+            }),
+            0x0003 => Op::from(LoadVxVy {
+                x: ((opcode & 0x0F00) >> 8) as u8,
+                y: ((opcode & 0x00F0) >> 4) as u8,
+            }),
+            _ => Op::from(InvalidInstruction { opcode }),
+        },
+        0x6000 => Op::from(LdVxByte {
+            x: ((opcode & 0x0F00) >> 8) as u8,
+            byte: (opcode & 0x00FF) as u8,
+        }),
+        0x7000 => Op::from(AddVxByte {
+            x: ((opcode & 0x0F00) >> 8) as u8,
+            byte: (opcode & 0x00FF) as u8,
+        }),
+        0x8000 => match opcode & 0x000F {
+            0x0000 => Op::from(LdVxVy {
+                x: ((opcode & 0x0F00) >> 8) as u8,
+                y: ((opcode & 0x00F0) >> 4) as u8,
+            }),
+            0x0001 => Op::from(OrVxVy {
+                x: ((opcode & 0x0F00) >> 8) as u8,
+                y: ((opcode & 0x00F0) >> 4) as u8,
+            }),
+            0x0002 => Op::from(AndVxVy {
+                x: ((opcode & 0x0F00) >> 8) as u8,
+                y: ((opcode & 0x00F0) >> 4) as u8,
+            }),
+            0x0003 => Op::from(XorVxVy {
+                x: ((opcode & 0x0F00) >> 8) as u8,
+                y: ((opcode & 0x00F0) >> 4) as u8,
+            }),
+            0x0004 => Op::from(AddVxVy {
+                x: ((opcode & 0x0F00) >> 8) as u8,
+                y: ((opcode & 0x00F0) >> 4) as u8,
+            }),
+            0x0005 => Op::from(SubVxVy {
+                x: ((opcode & 0x0F00) >> 8) as u8,
+                y: ((opcode & 0x00F0) >> 4) as u8,
+            }),
+            0x0006 => Op::from(ShrVxVy {
+                x: ((opcode & 0x0F00) >> 8) as u8,
+                y: ((opcode & 0x00F0) >> 4) as u8,
+            }),
+            0x0007 => Op::from(SubnVxVy {
+                x: ((opcode & 0x0F00) >> 8) as u8,
+                y: ((opcode & 0x00F0) >> 4) as u8,
+            }),
+            0x000E => Op::from(ShlVxVy {
+                x: ((opcode & 0x0F00) >> 8) as u8,
+                y: ((opcode & 0x00F0) >> 4) as u8,
+            }),
+            _ => Op::from(InvalidInstruction { opcode }),
+        },
+        0x9000 => Op::from(SneVxVy {
+            x: ((opcode & 0x0F00) >> 8) as u8,
+            y: ((opcode & 0x00F0) >> 4) as u8,
+        }),
+        0xA000 => Op::from(LdIAddr { address: opcode & 0x0FFF }),
+        0xB000 => Op::from(JmpV0Addr { address: opcode & 0x0FFF }),
+        0xC000 => Op::from(RndVxByte {
+            x: ((opcode & 0x0F00) >> 8) as u8,
+            byte: (opcode & 0x00FF) as u8,
+        }),
+        0xD000 => Op::from(DrwVxVyNibble {
+            x: ((opcode & 0x0F00) >> 8) as u8,
+            y: ((opcode & 0x00F0) >> 4) as u8,
+            n: (opcode & 0x000F) as u8,
+        }),
+        0xE000 => match opcode & 0x00FF {
+            0x009E => Op::from(SkpVx {
+                x: ((opcode & 0x0F00) >> 8) as u8,
+            }),
+            0x00A1 => Op::from(SknpVx {
+                x: ((opcode & 0x0F00) >> 8) as u8,
+            }),
+            _ => Op::from(InvalidInstruction { opcode }),
+        },
+        0xF000 => match opcode & 0x00FF {
+            0x0000 if opcode == 0xF000 => Op::from(LdILong {
+                address: operand,
+            }),
+            0x0001 => Op::from(Plane {
+                n: ((opcode & 0x0F00) >> 8) as u8,
+            }),
+            0x0002 if opcode == 0xF002 => Op::from(Audio),
+            0x0007 => Op::from(LdVxDT {
+                x: ((opcode & 0x0F00) >> 8) as u8,
+            }),
+            0x000A => Op::from(LdVxK {
+                x: ((opcode & 0x0F00) >> 8) as u8,
+            }),
+            0x0015 => Op::from(LdDTVx {
+                x: ((opcode & 0x0F00) >> 8) as u8,
+            }),
+            0x0018 => Op::from(LdSTVx {
+                x: ((opcode & 0x0F00) >> 8) as u8,
+            }),
+            0x001E => Op::from(AddIVx {
+                x: ((opcode & 0x0F00) >> 8) as u8,
+            }),
+            0x0029 => Op::from(LdFVx {
+                x: ((opcode & 0x0F00) >> 8) as u8,
+            }),
+            0x0030 => Op::from(LdHfVx {
+                x: ((opcode & 0x0F00) >> 8) as u8,
+            }),
+            0x0033 => Op::from(LdBVx {
+                x: ((opcode & 0x0F00) >> 8) as u8,
+            }),
+            0x003A => Op::from(PitchVx {
+                x: ((opcode & 0x0F00) >> 8) as u8,
+            }),
+            0x0055 => Op::from(LdIVx {
+                x: ((opcode & 0x0F00) >> 8) as u8,
+            }),
+            0x0065 => Op::from(LdVxI {
+                x: ((opcode & 0x0F00) >> 8) as u8,
+            }),
+            0x0075 => Op::from(LdRVx {
+                x: ((opcode & 0x0F00) >> 8) as u8,
+            }),
+            0x0085 => Op::from(LdVxR {
+                x: ((opcode & 0x0F00) >> 8) as u8,
+            }),
+            _ => Op::from(InvalidInstruction { opcode }),
+        },
+        _ => Op::from(InvalidInstruction { opcode }),
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Cls;
+impl Instruction for Cls {
+    fn execute(&self, chip8: &mut Chip8) -> Result<(), Chip8Error> {
+        let mask = chip8.plane_mask;
+        for i in chip8.gfx.iter_mut() {
+            *i &= !mask;
+        }
+        Ok(())
+    }
+
+    fn display(&self) -> String {
+        "CLS".to_string()
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Sys {
+    address: u16,
+}
+
+impl Instruction for Sys {
+    // Calls into COSMAC VIP machine code, which cannot be emulated here.
+    fn execute(&self, chip8: &mut Chip8) -> Result<(), Chip8Error> {
+        Err(Chip8Error::UnknownOpcode { opcode: self.address, address: chip8.instruction_address })
+    }
+
+    fn display(&self) -> String {
+        format!("SYS {:#X}", self.address)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Ret;
+impl Instruction for Ret {
+    fn execute(&self, chip8: &mut Chip8) -> Result<(), Chip8Error> {
+        if let Some(address) = chip8.pop_stack()? {
+            chip8.pc = address;
+        }
+        Ok(())
+    }
+
+    fn display(&self) -> String {
+        "RET".to_string()
+    }
+}
+
+
+#[derive(Debug, Clone, Copy)]
+pub struct Jmp {
+    address: u16,
+}
+
+impl Instruction for Jmp {
+    fn execute(&self, chip8: &mut Chip8) -> Result<(), Chip8Error> {
+        chip8.pc = self.address;
+        Ok(())
+    }
+
+    fn display(&self) -> String {
+        format!("JMP to {:#X}", self.address)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Call {
+    address: u16,
+}
+
+impl Instruction for Call {
+    fn execute(&self, chip8: &mut Chip8) -> Result<(), Chip8Error> {
+        chip8.push_stack(chip8.pc)?;
+        chip8.pc = self.address;
+        Ok(())
+    }
+
+    fn display(&self) -> String {
+        format!("CALL {:#X}", self.address)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct SeVxByte {
+    x: u8,
+    byte: u8,
+}
+
+impl Instruction for SeVxByte {
+    fn execute(&self, chip8: &mut Chip8) -> Result<(), Chip8Error> {
+        if chip8.v[self.x as usize] == self.byte {
+            chip8.skip_next()?;
+        }
+        Ok(())
+    }
+
+    fn display(&self) -> String {
+        format!("SE V{:X}, {:#X}", self.x, self.byte)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct SneVxByte {
+    x: u8,
+    byte: u8,
+}
+
+impl Instruction for SneVxByte {
+    fn execute(&self, chip8: &mut Chip8) -> Result<(), Chip8Error> {
+        if chip8.v[self.x as usize] != self.byte {
+            chip8.skip_next()?;
+        }
+        Ok(())
+    }
+
+    fn display(&self) -> String {
+        format!("SNE V{:X}, {:#X}", self.x, self.byte)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct SeVxVy {
+    x: u8,
+    y: u8,
+}
+
+impl Instruction for SeVxVy {
+    fn execute(&self, chip8: &mut Chip8) -> Result<(), Chip8Error> {
+        if chip8.v[self.x as usize] == chip8.v[self.y as usize] {
+            chip8.skip_next()?;
+        }
+        Ok(())
+    }
+
+    fn display(&self) -> String {
+        format!("SE V{:X}, V{:X}", self.x, self.y)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct LdVxByte {
+    x: u8,
+    byte: u8,
+}
+
+impl Instruction for LdVxByte {
+    fn execute(&self, chip8: &mut Chip8) -> Result<(), Chip8Error> {
+        chip8.v[self.x as usize] = self.byte;
+        Ok(())
+    }
+
+    fn display(&self) -> String {
+        format!("LD V{:X}, {:#X}", self.x, self.byte)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct AddVxByte {
+    x: u8,
+    byte: u8,
+}
+
+impl Instruction for AddVxByte {
+    fn execute(&self, chip8: &mut Chip8) -> Result<(), Chip8Error> {
+        chip8.v[self.x as usize] = chip8.v[self.x as usize].wrapping_add(self.byte);
+        Ok(())
+    }
+
+    fn display(&self) -> String {
+        format!("ADD V{:X}, {:#X}", self.x, self.byte)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct LdVxVy {
+    x: u8,
+    y: u8,
+}
+
+impl Instruction for LdVxVy {
+    fn execute(&self, chip8: &mut Chip8) -> Result<(), Chip8Error> {
+        chip8.v[self.x as usize] = chip8.v[self.y as usize];
+        Ok(())
+    }
+
+    fn display(&self) -> String {
+        format!("LD V{:X}, V{:X}", self.x, self.y)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct OrVxVy {
+    x: u8,
+    y: u8,
+}
+
+impl Instruction for OrVxVy {
+    fn execute(&self, chip8: &mut Chip8) -> Result<(), Chip8Error> {
+        chip8.v[self.x as usize] |= chip8.v[self.y as usize];
+        if chip8.quirks.logic_resets_vf {
+            chip8.v[0xF] = 0;
+        }
+        Ok(())
+    }
+
+    fn display(&self) -> String {
+        format!("OR V{:X}, V{:X}", self.x, self.y)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct AndVxVy {
+    x: u8,
+    y: u8,
+}
+
+impl Instruction for AndVxVy {
+    fn execute(&self, chip8: &mut Chip8) -> Result<(), Chip8Error> {
+        chip8.v[self.x as usize] &= chip8.v[self.y as usize];
+        if chip8.quirks.logic_resets_vf {
+            chip8.v[0xF] = 0;
+        }
+        Ok(())
+    }
+
+    fn display(&self) -> String {
+        format!("AND V{:X}, V{:X}", self.x, self.y)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct XorVxVy {
+    x: u8,
+    y: u8,
+}
+
+impl Instruction for XorVxVy {
+    fn execute(&self, chip8: &mut Chip8) -> Result<(), Chip8Error> {
+        chip8.v[self.x as usize] ^= chip8.v[self.y as usize];
+        if chip8.quirks.logic_resets_vf {
+            chip8.v[0xF] = 0;
+        }
+        Ok(())
+    }
+
+    fn display(&self) -> String {
+        format!("XOR V{:X}, V{:X}", self.x, self.y)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct AddVxVy {
+    x: u8,
+    y: u8,
+}
+
+impl Instruction for AddVxVy {
+    fn execute(&self, chip8: &mut Chip8) -> Result<(), Chip8Error> {
+        let (result, overflow) = chip8.v[self.x as usize].overflowing_add(chip8.v[self.y as usize]);
+        // With VF as the target the flag wins, so it is written last.
+        chip8.v[self.x as usize] = result;
+        chip8.v[0xF] = if overflow { 1 } else { 0 };
+        Ok(())
+    }
+
+    fn display(&self) -> String {
+        format!("ADD V{:X}, V{:X}", self.x, self.y)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct SubVxVy {
+    x: u8,
+    y: u8,
+}
+
+impl Instruction for SubVxVy {
+    fn execute(&self, chip8: &mut Chip8) -> Result<(), Chip8Error> {
+        let (result, overflow) = chip8.v[self.x as usize].overflowing_sub(chip8.v[self.y as usize]);
+        chip8.v[self.x as usize] = result;
+        chip8.v[0xF] = if overflow { 0 } else { 1 };
+        Ok(())
+    }
+
+    fn display(&self) -> String {
+        format!("SUB V{:X}, V{:X}", self.x, self.y)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct ShrVxVy {
+    x: u8,
+    y: u8,
+}
+
+impl Instruction for ShrVxVy {
+    fn execute(&self, chip8: &mut Chip8) -> Result<(), Chip8Error> {
+        let source = if chip8.quirks.shift_uses_vy { self.y } else { self.x };
+        let value = chip8.v[source as usize];
+        chip8.v[self.x as usize] = value >> 1;
+        chip8.v[0xF] = value & 0x1;
+        Ok(())
+    }
+
+    fn display(&self) -> String {
+        format!("SHR V{:X}, V{:X}", self.x, self.y)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct SubnVxVy {
+    x: u8,
+    y: u8,
+}
+
+impl Instruction for SubnVxVy {
+    fn execute(&self, chip8: &mut Chip8) -> Result<(), Chip8Error> {
+        let (result, overflow) = chip8.v[self.y as usize].overflowing_sub(chip8.v[self.x as usize]);
+        chip8.v[self.x as usize] = result;
+        chip8.v[0xF] = if overflow { 0 } else { 1 };
+        Ok(())
+    }
+
+    fn display(&self) -> String {
+        format!("SUBN V{:X}, V{:X}", self.x, self.y)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct ShlVxVy {
+    x: u8,
+    y: u8,
+}
+
+impl Instruction for ShlVxVy {
+    fn execute(&self, chip8: &mut Chip8) -> Result<(), Chip8Error> {
+        let source = if chip8.quirks.shift_uses_vy { self.y } else { self.x };
+        let value = chip8.v[source as usize];
+        chip8.v[self.x as usize] = value << 1;
+        chip8.v[0xF] = value >> 7;
+        Ok(())
+    }
+
+    fn display(&self) -> String {
+        format!("SHL V{:X}, V{:X}", self.x, self.y)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct SneVxVy {
+    x: u8,
+    y: u8,
+}
+
+impl Instruction for SneVxVy {
+    fn execute(&self, chip8: &mut Chip8) -> Result<(), Chip8Error> {
+        if chip8.v[self.x as usize] != chip8.v[self.y as usize] {
+            chip8.skip_next()?;
+        }
+        Ok(())
+    }
+
+    fn display(&self) -> String {
+        format!("SNE V{:X}, V{:X}", self.x, self.y)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct LdIAddr {
+    address: u16,
+}
+
+impl Instruction for LdIAddr {
+    fn execute(&self, chip8: &mut Chip8) -> Result<(), Chip8Error> {
+        chip8.i = self.address;
+        Ok(())
+    }
+
+    fn display(&self) -> String {
+        format!("LD I, {:#X}", self.address)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct JmpV0Addr {
+    address: u16,
+}
+
+impl Instruction for JmpV0Addr {
+    fn execute(&self, chip8: &mut Chip8) -> Result<(), Chip8Error> {
+        let register = if chip8.quirks.jump_uses_vx { (self.address >> 8) as usize } else { 0 };
+        chip8.pc = self.address + (chip8.v[register] as u16);
+        Ok(())
+    }
+
+    fn display(&self) -> String {
+        format!("JMP V0, {:#X}", self.address)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct RndVxByte {
+    x: u8,
+    byte: u8,
+}
+
+impl Instruction for RndVxByte {
+    fn execute(&self, chip8: &mut Chip8) -> Result<(), Chip8Error> {
+        chip8.v[self.x as usize] = chip8.rng.next_byte(&chip8.memory) & self.byte;
+        Ok(())
+    }
+
+    fn display(&self) -> String {
+        format!("RND V{:X}, {:#X}", self.x, self.byte)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct DrwVxVyNibble {
+    x: u8,
+    y: u8,
+    n: u8,
+}
+
+impl Instruction for DrwVxVyNibble {
+    // The starting position wraps around the screen; the rest of the sprite
+    // is clipped or wraps depending on `Quirks::clip_sprites`. Sprite data
+    // wraps around the end of memory like the address bus does.
+    fn execute(&self, chip8: &mut Chip8) -> Result<(), Chip8Error> {
+        let (width, height) = (chip8.width(), chip8.height());
+        let x = chip8.v[self.x as usize] as usize % width;
+        let y = chip8.v[self.y as usize] as usize % height;
+        // `DXY0` draws a 16x16 sprite stored as two bytes per row, or with
+        // the quirk an 8x16 one in low resolution.
+        let (rows, bytes_per_row) = match self.n {
+            0 if !chip8.hires && chip8.quirks.lores_dxy0_is_8x16 => (16, 1),
+            0 => (16, 2),
+            n => (n as usize, 1),
+        };
+
+        let sprite_width = bytes_per_row * 8;
+        let memory_size = chip8.memory.len();
+
+        // Each selected plane takes its own copy of the sprite data, in plane order.
+        let mut collided_rows = 0;
+        let mut clipped_rows = 0;
+        let mut address = chip8.i as usize;
+        for plane in 0..PLANE_COUNT {
+            let bit = 1 << plane;
+            if chip8.plane_mask & bit == 0 {
+                continue;
+            }
+            for yline in 0..rows {
+                if chip8.quirks.clip_sprites && y + yline >= height {
+                    clipped_rows += rows - yline;
+                    break;
+                }
+                let row_address = address + yline * bytes_per_row;
+                let mut pixel = chip8.read_memory(row_address % memory_size)? as u16;
+                if bytes_per_row == 2 {
+                    pixel = pixel << 8 | chip8.read_memory((row_address + 1) % memory_size)? as u16;
+                }
+                let mut collided = false;
+                for xline in 0..sprite_width {
+                    if chip8.quirks.clip_sprites && x + xline >= width {
+                        break;
+                    }
+                    if (pixel & (1 << (sprite_width - 1 - xline))) != 0 {
+                        let index = (x + xline) % width + ((y + yline) % height) * width;
+                        collided |= chip8.gfx[index] & bit != 0;
+                        chip8.gfx[index] ^= bit;
+                    }
+                }
+                collided_rows += collided as usize;
+            }
+            address += rows * bytes_per_row;
+        }
+        // SUPER-CHIP counts rows clipped at the bottom as collisions too.
+        chip8.v[0xF] = if chip8.hires && chip8.quirks.collision_counts_rows {
+            (collided_rows + clipped_rows).min(0xFF) as u8
+        } else {
+            (collided_rows > 0) as u8
+        };
+        chip8.waiting_for_vblank = chip8.quirks.display_wait;
+        Ok(())
+    }
+
+    fn display(&self) -> String {
+        format!("DRW V{:X}, V{:X}, {:#X}", self.x, self.y, self.n)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct SkpVx {
+    x: u8,
+}
+
+impl Instruction for SkpVx {
+    fn execute(&self, chip8: &mut Chip8) -> Result<(), Chip8Error> {
+        if chip8.keypad[(chip8.v[self.x as usize] & 0xF) as usize] != 0 {
+            chip8.skip_next()?;
+        }
+        Ok(())
+    }
+
+    fn display(&self) -> String {
+        format!("SKP V{:X}", self.x)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct SknpVx {
+    x: u8,
+}
+
+impl Instruction for SknpVx {
+    fn execute(&self, chip8: &mut Chip8) -> Result<(), Chip8Error> {
+        if chip8.keypad[(chip8.v[self.x as usize] & 0xF) as usize] == 0 {
+            chip8.skip_next()?;
+        }
+        Ok(())
+    }
+
+    fn display(&self) -> String {
+        format!("SKNP V{:X}", self.x)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct LdVxDT {
+    x: u8,
+}
+
+impl Instruction for LdVxDT {
+    fn execute(&self, chip8: &mut Chip8) -> Result<(), Chip8Error> {
+        chip8.v[self.x as usize] = chip8.delay_timer;
+        Ok(())
+    }
+
+    fn display(&self) -> String {
+        format!("LD V{:X}, DT", self.x)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct LdVxK {
+    x: u8,
+}
+
+impl Instruction for LdVxK {
+    // Blocks in `Chip8::emulate_cycle` until a key is pressed and released.
+    fn execute(&self, chip8: &mut Chip8) -> Result<(), Chip8Error> {
+        chip8.key_wait = Some(KeyWait::Press { x: self.x });
+        Ok(())
+    }
+
+    fn display(&self) -> String {
+        format!("LD V{:X}, K", self.x)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct LdDTVx {
+    x: u8,
+}
+
+impl Instruction for LdDTVx {
+    fn execute(&self, chip8: &mut Chip8) -> Result<(), Chip8Error> {
+        chip8.delay_timer = chip8.v[self.x as usize];
+        Ok(())
+    }
+
+    fn display(&self) -> String {
+        format!("LD DT, V{:X}", self.x)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct LdSTVx {
+    x: u8,
+}
+
+impl Instruction for LdSTVx {
+    fn execute(&self, chip8: &mut Chip8) -> Result<(), Chip8Error> {
+        chip8.sound_timer = chip8.v[self.x as usize];
+        Ok(())
+    }
+
+    fn display(&self) -> String {
+        format!("LD ST, V{:X}", self.x)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct AddIVx {
+    x: u8,
+}
+
+impl Instruction for AddIVx {
+    fn execute(&self, chip8: &mut Chip8) -> Result<(), Chip8Error> {
+        chip8.i = chip8.i.wrapping_add(chip8.v[self.x as usize] as u16);
+        Ok(())
+    }
+
+    fn display(&self) -> String {
+        format!("ADD I, V{:X}", self.x)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct LdFVx {
+    x: u8,
+}
+
+impl Instruction for LdFVx {
+    fn execute(&self, chip8: &mut Chip8) -> Result<(), Chip8Error> {
+        chip8.i = chip8.font_base + (chip8.v[self.x as usize] & 0xF) as u16 * 5;
+        Ok(())
+    }
+
+    fn display(&self) -> String {
+        format!("LD F, V{:X}", self.x)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct LdBVx {
+    x: u8,
+}
+
+impl Instruction for LdBVx {
+    fn execute(&self, chip8: &mut Chip8) -> Result<(), Chip8Error> {
+        let vx = chip8.v[self.x as usize];
+        let i = chip8.i as usize;
+        chip8.write_memory(i, vx / 100)?;
+        chip8.write_memory(i + 1, (vx / 10) % 10)?;
+        chip8.write_memory(i + 2, (vx % 100) % 10)?;
+        Ok(())
+    }
+
+    fn display(&self) -> String {
+        format!("LD B, V{:X}", self.x)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct LdIVx {
+    x: u8,
+}
+
+impl Instruction for LdIVx {
+    fn execute(&self, chip8: &mut Chip8) -> Result<(), Chip8Error> {
+        for i in 0..=self.x {
+            chip8.write_memory(chip8.i as usize + i as usize, chip8.v[i as usize])?;
+        }
+        chip8.i = chip8.i.wrapping_add(chip8.quirks.load_store_increment.amount(self.x));
+        Ok(())
+    }
+
+    fn display(&self) -> String {
+        format!("LD [I], V{:X}", self.x)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct LdVxI {
+    x: u8,
+}
+
+impl Instruction for LdVxI {
+    fn execute(&self, chip8: &mut Chip8) -> Result<(), Chip8Error> {
+        for i in 0..=self.x {
+            chip8.v[i as usize] = chip8.read_memory(chip8.i as usize + i as usize)?;
+        }
+        chip8.i = chip8.i.wrapping_add(chip8.quirks.load_store_increment.amount(self.x));
+        Ok(())
+    }
+
+    fn display(&self) -> String {
+        format!("LD V{:X}, [I]", self.x)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct InvalidInstruction {
+    opcode: u16,
+}
+
+impl Instruction for InvalidInstruction {
+    fn execute(&self, chip8: &mut Chip8) -> Result<(), Chip8Error> {
+        Err(Chip8Error::UnknownOpcode { opcode: self.opcode, address: chip8.instruction_address })
+    }
+
+    fn display(&self) -> String {
+        format!("Invalid instruction {:04X}", self.opcode)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct ScdNibble {
+    n: u8,
+}
+
+impl Instruction for ScdNibble {
+    fn execute(&self, chip8: &mut Chip8) -> Result<(), Chip8Error> {
+        chip8.scroll(0, self.n as isize);
+        Ok(())
+    }
+
+    fn display(&self) -> String {
+        format!("SCD {:#X}", self.n)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Scr;
+
+impl Instruction for Scr {
+    fn execute(&self, chip8: &mut Chip8) -> Result<(), Chip8Error> {
+        chip8.scroll(4, 0);
+        Ok(())
+    }
+
+    fn display(&self) -> String {
+        "SCR".to_string()
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Scl;
+
+impl Instruction for Scl {
+    fn execute(&self, chip8: &mut Chip8) -> Result<(), Chip8Error> {
+        chip8.scroll(-4, 0);
+        Ok(())
+    }
+
+    fn display(&self) -> String {
+        "SCL".to_string()
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Exit;
+
+impl Instruction for Exit {
+    fn execute(&self, chip8: &mut Chip8) -> Result<(), Chip8Error> {
+        chip8.exited = true;
+        Ok(())
+    }
+
+    fn display(&self) -> String {
+        "EXIT".to_string()
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Low;
+
+impl Instruction for Low {
+    fn execute(&self, chip8: &mut Chip8) -> Result<(), Chip8Error> {
+        chip8.hires = false;
+        chip8.gfx.fill(0);
+        Ok(())
+    }
+
+    fn display(&self) -> String {
+        "LOW".to_string()
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct High;
+
+impl Instruction for High {
+    fn execute(&self, chip8: &mut Chip8) -> Result<(), Chip8Error> {
+        chip8.hires = true;
+        chip8.gfx.fill(0);
+        Ok(())
+    }
+
+    fn display(&self) -> String {
+        "HIGH".to_string()
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct LdHfVx {
+    x: u8,
+}
+
+impl Instruction for LdHfVx {
+    fn execute(&self, chip8: &mut Chip8) -> Result<(), Chip8Error> {
+        chip8.i = chip8.big_font_base() + (chip8.v[self.x as usize] & 0xF) as u16 * 10;
+        Ok(())
+    }
+
+    fn display(&self) -> String {
+        format!("LD HF, V{:X}", self.x)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct LdRVx {
+    x: u8,
+}
+
+impl Instruction for LdRVx {
+    fn execute(&self, chip8: &mut Chip8) -> Result<(), Chip8Error> {
+        for i in 0..=self.x as usize {
+            chip8.rpl[i] = chip8.v[i];
+        }
+        Ok(())
+    }
+
+    fn display(&self) -> String {
+        format!("LD R, V{:X}", self.x)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct LdVxR {
+    x: u8,
+}
+
+impl Instruction for LdVxR {
+    fn execute(&self, chip8: &mut Chip8) -> Result<(), Chip8Error> {
+        for i in 0..=self.x as usize {
+            chip8.v[i] = chip8.rpl[i];
+        }
+        Ok(())
+    }
+
+    fn display(&self) -> String {
+        format!("LD V{:X}, R", self.x)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct ScuNibble {
+    n: u8,
+}
+
+impl Instruction for ScuNibble {
+    fn execute(&self, chip8: &mut Chip8) -> Result<(), Chip8Error> {
+        chip8.scroll(0, -(self.n as isize));
+        Ok(())
+    }
+
+    fn display(&self) -> String {
+        format!("SCU {:#X}", self.n)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct SaveVxVy {
+    x: u8,
+    y: u8,
+}
+
+impl Instruction for SaveVxVy {
+    fn execute(&self, chip8: &mut Chip8) -> Result<(), Chip8Error> {
+        let (x, y) = (self.x as usize, self.y as usize);
+        let count = x.abs_diff(y);
+        for offset in 0..=count {
+            let register = if x <= y { x + offset } else { x - offset };
+            chip8.write_memory(chip8.i as usize + offset, chip8.v[register])?;
+        }
+        Ok(())
+    }
+
+    fn display(&self) -> String {
+        format!("SAVE V{:X}, V{:X}", self.x, self.y)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct LoadVxVy {
+    x: u8,
+    y: u8,
+}
+
+impl Instruction for LoadVxVy {
+    fn execute(&self, chip8: &mut Chip8) -> Result<(), Chip8Error> {
+        let (x, y) = (self.x as usize, self.y as usize);
+        let count = x.abs_diff(y);
+        for offset in 0..=count {
+            let register = if x <= y { x + offset } else { x - offset };
+            chip8.v[register] = chip8.read_memory(chip8.i as usize + offset)?;
+        }
+        Ok(())
+    }
+
+    fn display(&self) -> String {
+        format!("LOAD V{:X}, V{:X}", self.x, self.y)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct LdILong {
+    address: u16,
+}
+
+impl Instruction for LdILong {
+    fn execute(&self, chip8: &mut Chip8) -> Result<(), Chip8Error> {
+        chip8.i = self.address;
+        Ok(())
+    }
+
+    fn display(&self) -> String {
+        format!("LD I, LONG {:#X}", self.address)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Plane {
+    n: u8,
+}
+
+impl Instruction for Plane {
+    fn execute(&self, chip8: &mut Chip8) -> Result<(), Chip8Error> {
+        chip8.plane_mask = self.n;
+        Ok(())
+    }
+
+    fn display(&self) -> String {
+        format!("PLANE {:#X}", self.n)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Audio;
+
+impl Instruction for Audio {
+    fn execute(&self, chip8: &mut Chip8) -> Result<(), Chip8Error> {
+        for offset in 0..chip8.audio_pattern.len() {
+            chip8.audio_pattern[offset] = chip8.read_memory(chip8.i as usize + offset)?;
+        }
+        Ok(())
+    }
+
+    fn display(&self) -> String {
+        "AUDIO".to_string()
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct PitchVx {
+    x: u8,
+}
+
+impl Instruction for PitchVx {
+    fn execute(&self, chip8: &mut Chip8) -> Result<(), Chip8Error> {
+        chip8.pitch = chip8.v[self.x as usize];
+        Ok(())
+    }
+
+    fn display(&self) -> String {
+        format!("PITCH V{:X}", self.x)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::quirks::{LoadStoreIncrement, Quirks};
+
+    fn execute(chip8: &mut Chip8, opcode: u16) {
+        chip8.execute_instruction(&decode(opcode, 0)).expect("executes");
+    }
+
+    const POLICIES: [ErrorPolicy; 3] = [ErrorPolicy::Halt, ErrorPolicy::Ignore, ErrorPolicy::Wrap];
+
+    // A machine with `program` at 0x200 and `policy` for every fault kind.
+    fn machine(program: &[u16], policy: ErrorPolicy) -> Chip8 {
+        let mut chip8 = Chip8::new();
+        chip8.error_policies = ErrorPolicies::all(policy);
+        for (n, opcode) in program.iter().enumerate() {
+            chip8.memory[PROGRAM_START + 2 * n..][..2].copy_from_slice(&opcode.to_be_bytes());
+        }
+        chip8
+    }
+
+    #[test]
+    fn unknown_opcode() {
+        for policy in POLICIES {
+            let mut chip8 = machine(&[0x8008], policy);
+            let result = chip8.emulate_cycle();
+            match policy {
+                ErrorPolicy::Halt => {
+                    assert_eq!(result, Err(Chip8Error::UnknownOpcode { opcode: 0x8008, address: 0x200 }));
+                    assert_eq!(chip8.pc, 0x200);
+                }
+                // Nothing to wrap, so the same as ignoring it.
+                ErrorPolicy::Ignore | ErrorPolicy::Wrap => {
+                    assert_eq!(result, Ok(()), "{}", policy);
+                    assert_eq!(chip8.pc, 0x202, "{}", policy);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn stack_overflow() {
+        for policy in POLICIES {
+            // Calls itself.
+            let mut chip8 = machine(&[0x2200], policy);
+            for _ in 0..16 {
+                chip8.emulate_cycle().expect("the stack has room");
+            }
+            let result = chip8.emulate_cycle();
+            match policy {
+                ErrorPolicy::Halt => {
+                    assert_eq!(result, Err(Chip8Error::StackOverflow { address: 0x200 }));
+                    assert_eq!(chip8.sp, 16);
+                }
+                // The return address is dropped but the call still happens.
+                ErrorPolicy::Ignore => {
+                    assert_eq!(result, Ok(()));
+                    assert_eq!(chip8.sp, 16);
+                }
+                ErrorPolicy::Wrap => {
+                    assert_eq!(result, Ok(()));
+                    assert_eq!((chip8.sp, chip8.stack[0]), (1, 0x202));
+                }
+            }
+            assert_eq!(chip8.pc, 0x200, "{}", policy);
+        }
+    }
+
+    #[test]
+    fn stack_underflow() {
+        for policy in POLICIES {
+            let mut chip8 = machine(&[0x00EE], policy);
+            chip8.stack[15] = 0x300;
+            let result = chip8.emulate_cycle();
+            match policy {
+                ErrorPolicy::Halt => {
+                    assert_eq!(result, Err(Chip8Error::StackUnderflow { address: 0x200 }));
+                    assert_eq!(chip8.pc, 0x200);
+                }
+                ErrorPolicy::Ignore => {
+                    assert_eq!(result, Ok(()));
+                    assert_eq!(chip8.pc, 0x202);
+                }
+                ErrorPolicy::Wrap => {
+                    assert_eq!(result, Ok(()));
+                    assert_eq!((chip8.pc, chip8.sp), (0x300, 15));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn store_past_the_end_of_memory() {
+        for policy in POLICIES {
+            // LD [I], V2 with I two bytes from the end.
+            let mut chip8 = machine(&[0xF255], policy);
+            chip8.quirks.load_store_increment = LoadStoreIncrement::Unchanged;
+            chip8.i = (MEMORY_SIZE - 2) as u16;
+            chip8.v[..3].copy_from_slice(&[1, 2, 3]);
+            chip8.memory[0] = 0xAA;
+            let result = chip8.emulate_cycle();
+            assert_eq!(chip8.memory[MEMORY_SIZE - 2..], [1, 2], "{}", policy);
+            match policy {
+                ErrorPolicy::Halt => {
+                    assert_eq!(result, Err(Chip8Error::MemoryOutOfBounds { target: MEMORY_SIZE, address: 0x200 }));
+                    assert_eq!((chip8.pc, chip8.memory[0]), (0x200, 0xAA));
+                }
+                ErrorPolicy::Ignore => {
+                    assert_eq!(result, Ok(()));
+                    assert_eq!((chip8.pc, chip8.memory[0]), (0x202, 0xAA));
+                }
+                ErrorPolicy::Wrap => {
+                    assert_eq!(result, Ok(()));
+                    assert_eq!((chip8.pc, chip8.memory[0]), (0x202, 3));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn load_past_the_end_of_memory() {
+        for policy in POLICIES {
+            // LD V1, [I] with I on the last byte.
+            let mut chip8 = machine(&[0xF165], policy);
+            chip8.quirks.load_store_increment = LoadStoreIncrement::Unchanged;
+            chip8.i = (MEMORY_SIZE - 1) as u16;
+            chip8.memory[MEMORY_SIZE - 1] = 0x11;
+            chip8.memory[0] = 0x22;
+            chip8.v[1] = 0xFF;
+            let result = chip8.emulate_cycle();
+            assert_eq!(chip8.v[0], 0x11, "{}", policy);
+            match policy {
+                ErrorPolicy::Halt => {
+                    assert_eq!(result, Err(Chip8Error::MemoryOutOfBounds { target: MEMORY_SIZE, address: 0x200 }));
+                    assert_eq!(chip8.v[1], 0xFF);
+                }
+                // Dropped reads come back as 0.
+                ErrorPolicy::Ignore => assert_eq!((result, chip8.v[1]), (Ok(()), 0)),
+                ErrorPolicy::Wrap => assert_eq!((result, chip8.v[1]), (Ok(()), 0x22)),
+            }
+        }
+    }
+
+    #[test]
+    fn bcd_past_the_end_of_memory() {
+        for policy in POLICIES {
+            // LD B, V0 with I on the last byte.
+            let mut chip8 = machine(&[0xF033], policy);
+            chip8.i = (MEMORY_SIZE - 1) as u16;
+            chip8.v[0] = 234;
+            chip8.memory[..2].copy_from_slice(&[0xAA, 0xBB]);
+            let result = chip8.emulate_cycle();
+            assert_eq!(chip8.memory[MEMORY_SIZE - 1], 2, "{}", policy);
+            match policy {
+                ErrorPolicy::Halt => {
+                    assert_eq!(result, Err(Chip8Error::MemoryOutOfBounds { target: MEMORY_SIZE, address: 0x200 }));
+                    assert_eq!(chip8.memory[..2], [0xAA, 0xBB]);
+                }
+                ErrorPolicy::Ignore => assert_eq!((result, &chip8.memory[..2]), (Ok(()), &[0xAA, 0xBB][..])),
+                ErrorPolicy::Wrap => assert_eq!((result, &chip8.memory[..2]), (Ok(()), &[3, 4][..])),
+            }
+        }
+    }
+
+    // Sprite data wraps around the end of memory under every policy, like
+    // the address bus does, so drawing never faults.
+    #[test]
+    fn sprite_past_the_end_of_memory() {
+        for policy in POLICIES {
+            // DRW V0, V0, 2 with I on the last byte.
+            let mut chip8 = machine(&[0xD002], policy);
+            chip8.quirks.display_wait = false;
+            chip8.i = (MEMORY_SIZE - 1) as u16;
+            chip8.memory[MEMORY_SIZE - 1] = 0x80;
+            chip8.memory[0] = 0x80;
+            assert_eq!(chip8.emulate_cycle(), Ok(()), "{}", policy);
+            assert_eq!((chip8.gfx[0], chip8.gfx[chip8.width()]), (1, 1), "{}", policy);
+        }
+    }
+
+    #[test]
+    fn pc_out_of_range() {
+        for policy in POLICIES {
+            let mut chip8 = machine(&[], policy);
+            // JMP 0x234 at the start of memory, for the PC to wrap to.
+            chip8.memory[..2].copy_from_slice(&[0x12, 0x34]);
+            chip8.pc = MEMORY_SIZE as u16;
+            let result = chip8.emulate_cycle();
+            match policy {
+                ErrorPolicy::Halt => {
+                    assert_eq!(result, Err(Chip8Error::PcOutOfRange { pc: MEMORY_SIZE as u16 }));
+                    assert_eq!(chip8.pc, MEMORY_SIZE as u16);
+                }
+                ErrorPolicy::Ignore => assert_eq!((result, chip8.pc), (Ok(()), MEMORY_SIZE as u16)),
+                ErrorPolicy::Wrap => assert_eq!((result, chip8.pc), (Ok(()), 0x234)),
+            }
+        }
+    }
+
+    #[test]
+    fn ignored_pc_out_of_range_runs_nothing() {
+        let mut chip8 = Chip8::new();
+        chip8.error_policies.pc = ErrorPolicy::Ignore;
+        // A last byte that would decode as `SYS` or an unknown opcode if read.
+        chip8.memory[MEMORY_SIZE - 1] = 0x12;
+        chip8.pc = (MEMORY_SIZE - 1) as u16;
+        for _ in 0..3 {
+            assert_eq!(chip8.emulate_cycle(), Ok(()));
+            assert_eq!(chip8.pc, (MEMORY_SIZE - 1) as u16);
+        }
+        assert_eq!(chip8.sp, 0);
+    }
+
+    #[test]
+    fn load_store_increment() {
+        let cases = [
+            (LoadStoreIncrement::Unchanged, 0x300),
+            (LoadStoreIncrement::ByX, 0x302),
+            (LoadStoreIncrement::ByXPlusOne, 0x303),
+        ];
+        for (increment, expected) in cases {
+            // LD [I], V2 then LD V2, [I].
+            for opcode in [0xF255, 0xF265] {
+                let mut chip8 = Chip8::new();
+                chip8.quirks.load_store_increment = increment;
+                chip8.i = 0x300;
+                execute(&mut chip8, opcode);
+                assert_eq!(chip8.i, expected, "{:04X} with {:?}", opcode, increment);
+            }
+        }
+    }
+
+    #[test]
+    fn lores_dxy0() {
+        for (narrow, expected_width) in [(false, 16), (true, 8)] {
+            let mut chip8 = Chip8::new();
+            chip8.quirks = Quirks::CHIP_48;
+            chip8.quirks.lores_dxy0_is_8x16 = narrow;
+            chip8.i = 0x300;
+            chip8.memory[0x300..0x320].fill(0xFF);
+            execute(&mut chip8, 0xD000);
+            let width = chip8.gfx[..chip8.width()].iter().filter(|&&pixel| pixel != 0).count();
+            let height = (0..chip8.height()).filter(|&y| chip8.gfx[y * chip8.width()] != 0).count();
+            assert_eq!((width, height), (expected_width, 16), "8x16 quirk {}", narrow);
+        }
+
+        // High resolution always draws 16x16.
+        let mut chip8 = Chip8::new();
+        chip8.quirks = Quirks::SUPER_CHIP_1_0;
+        chip8.hires = true;
+        chip8.i = 0x300;
+        chip8.memory[0x300..0x320].fill(0xFF);
+        execute(&mut chip8, 0xD000);
+        assert_eq!(chip8.gfx[..chip8.width()].iter().filter(|&&pixel| pixel != 0).count(), 16);
+    }
+
+    #[test]
+    fn set_font_rejects_a_base_without_room() {
+        let mut chip8 = Chip8::new();
+        let base = (PROGRAM_START - FONT_SIZE - BIG_FONT_SIZE) as u16;
+        assert!(chip8.set_font(FontStyle::default(), base).is_ok());
+        assert_eq!(
+            chip8.set_font(FontStyle::default(), base + 1),
+            Err(Chip8Error::FontOutOfBounds { base: base + 1, end: PROGRAM_START + 1 })
+        );
+        assert_eq!(
+            chip8.set_font(FontStyle::default(), u16::MAX),
+            Err(Chip8Error::FontOutOfBounds { base: u16::MAX, end: u16::MAX as usize + FONT_SIZE + BIG_FONT_SIZE })
+        );
+        assert_eq!(chip8.font_base, base);
+        assert_eq!(&chip8.memory[base as usize..base as usize + FONT_SIZE], FontStyle::default().sprites());
+    }
+
+    // 8XY4 to 8XYE with VF as VX: the flag overwrites the result.
+    #[test]
+    fn flag_wins_with_vf_as_vx() {
+        // (opcode, VF, V1, expected VF)
+        let cases = [
+            (0x8F14, 0xFF, 0x01, 1), // 0xFF + 1 carries
+            (0x8F14, 0x01, 0x01, 0), // 1 + 1 doesn't
+            (0x8F15, 0x05, 0x01, 1), // 5 - 1 doesn't borrow
+            (0x8F15, 0x00, 0x01, 0), // 0 - 1 does
+            (0x8F17, 0x01, 0x05, 1), // 5 - 1 doesn't borrow
+            (0x8F17, 0x05, 0x01, 0), // 1 - 5 does
+            (0x8FF6, 0x02, 0x00, 0), // shifts out a 0
+            (0x8FF6, 0x03, 0x00, 1), // shifts out a 1
+            (0x8FFE, 0x81, 0x00, 1),
+            (0x8FFE, 0x41, 0x00, 0),
+        ];
+        for quirks in [Quirks::COSMAC_VIP, Quirks::CHIP_48] {
+            for (opcode, vf, v1, expected) in cases {
+                let mut chip8 = Chip8::new();
+                chip8.quirks = quirks;
+                chip8.v[0xF] = vf;
+                chip8.v[1] = v1;
+                execute(&mut chip8, opcode);
+                assert_eq!(chip8.v[0xF], expected, "{:04X} with VF={:#X}, V1={:#X}", opcode, vf, v1);
+            }
+        }
+    }
+
+    // 8XY4 to 8XYE with VF as VY: VF is read before the flag replaces it.
+    #[test]
+    fn vf_read_before_flag_with_vf_as_vy() {
+        // (opcode, V1, VF, expected V1, expected VF)
+        let cases = [
+            (0x81F4, 0x10, 0x20, 0x30, 0),
+            (0x81F4, 0xF0, 0x20, 0x10, 1),
+            (0x81F5, 0x30, 0x10, 0x20, 1),
+            (0x81F5, 0x10, 0x30, 0xE0, 0),
+            (0x81F7, 0x10, 0x30, 0x20, 1),
+            (0x81F7, 0x30, 0x10, 0xE0, 0),
+        ];
+        for (opcode, v1, vf, expected_v1, expected_vf) in cases {
+            let mut chip8 = Chip8::new();
+            chip8.v[1] = v1;
+            chip8.v[0xF] = vf;
+            execute(&mut chip8, opcode);
+            assert_eq!((chip8.v[1], chip8.v[0xF]), (expected_v1, expected_vf), "{:04X} with V1={:#X}, VF={:#X}", opcode, v1, vf);
+        }
+
+        // Shifts only read VY with the quirk.
+        let cases = [(0x81F6, 0x05, 0x02, 1), (0x81FE, 0x81, 0x02, 1), (0x81FE, 0x41, 0x82, 0)];
+        for (opcode, vf, expected_v1, expected_vf) in cases {
+            let mut chip8 = Chip8::new();
+            chip8.quirks.shift_uses_vy = true;
+            chip8.v[1] = 0xFF;
+            chip8.v[0xF] = vf;
+            execute(&mut chip8, opcode);
+            assert_eq!((chip8.v[1], chip8.v[0xF]), (expected_v1, expected_vf), "{:04X} with VF={:#X}", opcode, vf);
+        }
+    }
+}
@@ -0,0 +1,772 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::{self, BufRead, Write};
+use std::str::FromStr;
+
+use crate::chip8::{decode, AccessKind, Chip8, Instruction};
+use crate::error::Chip8Error;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Register {
+    V(u8),
+    I,
+    Pc,
+    Sp,
+    Dt,
+    St,
+}
+
+impl Register {
+    fn read(&self, chip8: &Chip8) -> u16 {
+        match *self {
+            Register::V(x) => chip8.v[x as usize] as u16,
+            Register::I => chip8.i,
+            Register::Pc => chip8.pc,
+            Register::Sp => chip8.sp as u16,
+            Register::Dt => chip8.delay_timer as u16,
+            Register::St => chip8.sound_timer as u16,
+        }
+    }
+
+    fn parse(s: &str) -> Result<Self, String> {
+        let upper = s.to_ascii_uppercase();
+        match upper.as_str() {
+            "I" => Ok(Register::I),
+            "PC" => Ok(Register::Pc),
+            "SP" => Ok(Register::Sp),
+            "DT" => Ok(Register::Dt),
+            "ST" => Ok(Register::St),
+            _ => match upper.strip_prefix('V').map(|x| u8::from_str_radix(x, 16)) {
+                Some(Ok(x)) if x < 16 => Ok(Register::V(x)),
+                _ => Err(format!("unknown register '{}'", s)),
+            },
+        }
+    }
+}
+
+impl fmt::Display for Register {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Register::V(x) => write!(f, "V{:X}", x),
+            Register::I => f.write_str("I"),
+            Register::Pc => f.write_str("PC"),
+            Register::Sp => f.write_str("SP"),
+            Register::Dt => f.write_str("DT"),
+            Register::St => f.write_str("ST"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparison {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl Comparison {
+    fn parse(s: &str) -> Result<Self, String> {
+        match s {
+            "==" => Ok(Comparison::Eq),
+            "!=" => Ok(Comparison::Ne),
+            "<" => Ok(Comparison::Lt),
+            "<=" => Ok(Comparison::Le),
+            ">" => Ok(Comparison::Gt),
+            ">=" => Ok(Comparison::Ge),
+            _ => Err(format!("unknown comparison '{}'", s)),
+        }
+    }
+
+    fn symbol(&self) -> &'static str {
+        match self {
+            Comparison::Eq => "==",
+            Comparison::Ne => "!=",
+            Comparison::Lt => "<",
+            Comparison::Le => "<=",
+            Comparison::Gt => ">",
+            Comparison::Ge => ">=",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Condition {
+    pub register: Register,
+    pub comparison: Comparison,
+    pub value: u16,
+}
+
+impl Condition {
+    pub fn holds(&self, chip8: &Chip8) -> bool {
+        let actual = self.register.read(chip8);
+        match self.comparison {
+            Comparison::Eq => actual == self.value,
+            Comparison::Ne => actual != self.value,
+            Comparison::Lt => actual < self.value,
+            Comparison::Le => actual <= self.value,
+            Comparison::Gt => actual > self.value,
+            Comparison::Ge => actual >= self.value,
+        }
+    }
+
+    // Parses `REGISTER OP VALUE`, e.g. `V3 == 0x10`.
+    fn parse(words: &[&str]) -> Result<Self, String> {
+        match words {
+            [register, comparison, value] => Ok(Condition {
+                register: Register::parse(register)?,
+                comparison: Comparison::parse(comparison)?,
+                value: parse_number(value)?,
+            }),
+            _ => Err("expected a condition like 'V3 == 0x10'".to_string()),
+        }
+    }
+}
+
+impl FromStr for Condition {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Condition::parse(&s.split_whitespace().collect::<Vec<_>>())
+    }
+}
+
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {} {:#X}", self.register, self.comparison.symbol(), self.value)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WatchKind {
+    Read,
+    Write,
+    Access,
+}
+
+impl WatchKind {
+    fn matches(&self, kind: AccessKind) -> bool {
+        match self {
+            WatchKind::Read => kind == AccessKind::Read,
+            WatchKind::Write => kind == AccessKind::Write,
+            WatchKind::Access => true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Watchpoint {
+    pub start: usize,
+    // Inclusive.
+    pub end: usize,
+    pub kind: WatchKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StopReason {
+    Breakpoint(u16),
+    Watchpoint { address: usize, kind: AccessKind, value: u8 },
+    Condition(Condition),
+    Step,
+    Interrupted,
+    Error(Chip8Error),
+    Exited,
+    // Stepped back through the rewind history by this many frames.
+    Rewound(usize),
+}
+
+impl fmt::Display for StopReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StopReason::Breakpoint(address) => write!(f, "breakpoint at {:#05X}", address),
+            StopReason::Watchpoint { address, kind, value } => {
+                let verb = if *kind == AccessKind::Read { "read" } else { "write" };
+                write!(f, "watchpoint: {} of {:#04X} at {:#05X}", verb, value, address)
+            }
+            StopReason::Condition(condition) => write!(f, "condition {} became true", condition),
+            StopReason::Step => write!(f, "step"),
+            StopReason::Interrupted => write!(f, "interrupted"),
+            StopReason::Error(e) => write!(f, "error: {}", e),
+            StopReason::Exited => write!(f, "program exited"),
+            StopReason::Rewound(frames) => write!(f, "rewound {} frame(s)", frames),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Run,
+    Step(u32),
+    // Stop once execution is back at `pc` with the same stack depth.
+    StepOver { pc: u16, sp: usize },
+    // Stop once the stack is shallower than `sp`.
+    StepOut { sp: usize },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConsoleAction {
+    Resume,
+    Quit,
+    // Restore the state from this many frames back; the frontend owns the
+    // history and reopens the console afterwards.
+    Rewind(usize),
+}
+
+pub struct Debugger {
+    // A breakpoint with a condition only fires while the condition holds.
+    pub breakpoints: BTreeMap<u16, Option<Condition>>,
+    pub watchpoints: Vec<Watchpoint>,
+    // Fire when the condition goes from false to true, wherever the PC is.
+    pub conditions: Vec<Condition>,
+    condition_state: Vec<bool>,
+    mode: Mode,
+    resuming: bool,
+}
+
+impl Debugger {
+    pub fn new() -> Self {
+        Self {
+            breakpoints: BTreeMap::new(),
+            watchpoints: Vec::new(),
+            conditions: Vec::new(),
+            condition_state: Vec::new(),
+            mode: Mode::Run,
+            resuming: false,
+        }
+    }
+
+    pub fn attach(&self, chip8: &mut Chip8) {
+        chip8.log_memory_access = true;
+        chip8.access_log.clear();
+    }
+
+    // Executes one instruction unless a breakpoint at the PC stops it first.
+    pub fn step(&mut self, chip8: &mut Chip8) -> Option<StopReason> {
+        if chip8.exited {
+            return Some(StopReason::Exited);
+        }
+
+        let executed = !chip8.blocked();
+        // After a stop, the instruction at the PC runs before its breakpoint is checked again.
+        if executed && !std::mem::take(&mut self.resuming) {
+            if let Some(condition) = self.breakpoints.get(&chip8.pc) {
+                if condition.is_none_or(|condition| condition.holds(chip8)) {
+                    return Some(StopReason::Breakpoint(chip8.pc));
+                }
+            }
+        }
+
+        chip8.access_log.clear();
+        if let Err(e) = chip8.emulate_cycle() {
+            return Some(StopReason::Error(e));
+        }
+
+        if let Some(reason) = self.check_watchpoints(chip8) {
+            return Some(reason);
+        }
+        if let Some(reason) = self.check_conditions(chip8) {
+            return Some(reason);
+        }
+        if chip8.exited {
+            return Some(StopReason::Exited);
+        }
+        if executed {
+            self.check_mode(chip8)
+        } else {
+            None
+        }
+    }
+
+    // Runs a 60 Hz frame like `Chip8::run_frame`, stopping early when the
+    // debugger needs attention. The timers are not ticked after a stop.
+    pub fn run_frame(&mut self, chip8: &mut Chip8, cycles: u32) -> Option<StopReason> {
+        for _ in 0..cycles {
+            if let Some(reason) = self.step(chip8) {
+                return Some(reason);
+            }
+        }
+        chip8.tick();
+        None
+    }
+
+    fn check_watchpoints(&self, chip8: &Chip8) -> Option<StopReason> {
+        chip8.access_log.iter().find_map(|access| {
+            self.watchpoints
+                .iter()
+                .any(|w| (w.start..=w.end).contains(&access.address) && w.kind.matches(access.kind))
+                .then_some(StopReason::Watchpoint {
+                    address: access.address,
+                    kind: access.kind,
+                    value: access.value,
+                })
+        })
+    }
+
+    fn check_conditions(&mut self, chip8: &Chip8) -> Option<StopReason> {
+        self.condition_state.resize(self.conditions.len(), false);
+        let mut fired = None;
+        for (condition, was_true) in self.conditions.iter().zip(self.condition_state.iter_mut()) {
+            let holds = condition.holds(chip8);
+            if holds && !*was_true && fired.is_none() {
+                fired = Some(StopReason::Condition(*condition));
+            }
+            *was_true = holds;
+        }
+        fired
+    }
+
+    fn check_mode(&mut self, chip8: &Chip8) -> Option<StopReason> {
+        let done = match self.mode {
+            Mode::Run => false,
+            Mode::Step(remaining) => {
+                self.mode = Mode::Step(remaining.saturating_sub(1));
+                remaining <= 1
+            }
+            Mode::StepOver { pc, sp } => chip8.pc == pc && chip8.sp == sp,
+            Mode::StepOut { sp } => chip8.sp < sp,
+        };
+        if done {
+            self.mode = Mode::Run;
+            Some(StopReason::Step)
+        } else {
+            None
+        }
+    }
+
+    // Reads commands until the user resumes execution or quits. Commands that
+    // move execution (step, next, finish) resume and stop again when done.
+    pub fn console<R: BufRead, W: Write>(&mut self, chip8: &mut Chip8, reason: &StopReason, input: &mut R, output: &mut W) -> io::Result<ConsoleAction> {
+        writeln!(output, "stopped: {}", reason)?;
+        self.print_state(chip8, output)?;
+
+        loop {
+            write!(output, "(chip8) ")?;
+            output.flush()?;
+
+            let mut line = String::new();
+            if input.read_line(&mut line)? == 0 {
+                return Ok(ConsoleAction::Quit);
+            }
+            let words: Vec<&str> = line.split_whitespace().collect();
+            let Some((&command, args)) = words.split_first() else {
+                continue;
+            };
+
+            let result = match command {
+                "c" | "continue" => return Ok(self.resume(Mode::Run)),
+                "s" | "step" => {
+                    let count = args.first().map(|n| parse_number(n)).transpose();
+                    match count {
+                        Ok(count) => return Ok(self.resume(Mode::Step(count.unwrap_or(1).max(1) as u32))),
+                        Err(e) => Err(e),
+                    }
+                }
+                "n" | "next" => return Ok(self.step_over(chip8)),
+                "f" | "finish" => return Ok(self.resume(Mode::StepOut { sp: chip8.sp })),
+                "q" | "quit" => return Ok(ConsoleAction::Quit),
+                "rw" | "rewind" => match args.first().map(|n| parse_number(n)).transpose() {
+                    Ok(frames) => return Ok(ConsoleAction::Rewind(frames.unwrap_or(1) as usize)),
+                    Err(e) => Err(e),
+                },
+                "b" | "break" => self.add_breakpoint(args),
+                "d" | "delete" => self.delete_breakpoint(args),
+                "w" | "watch" => self.add_watchpoint(args),
+                "unwatch" => self.delete_watchpoint(args),
+                "when" => Condition::parse(args).map(|condition| {
+                    self.conditions.push(condition);
+                    self.condition_state.push(condition.holds(chip8));
+                }),
+                "unwhen" => self.delete_condition(args),
+                "i" | "info" => self.print_points(output).map_err(|e| e.to_string()),
+                "r" | "regs" => self.print_state(chip8, output).map_err(|e| e.to_string()),
+                "x" => print_memory(chip8, args, output),
+                "l" | "list" => {
+                    let start = args.first().map(|a| parse_number(a)).transpose();
+                    match start {
+                        Ok(start) => self.print_disassembly(chip8, start, 8, output).map_err(|e| e.to_string()),
+                        Err(e) => Err(e),
+                    }
+                }
+                "h" | "help" => output.write_all(HELP.as_bytes()).map_err(|e| e.to_string()),
+                _ => Err(format!("unknown command '{}'; try 'help'", command)),
+            };
+
+            if let Err(e) = result {
+                writeln!(output, "error: {}", e)?;
+            }
+        }
+    }
+
+    fn resume(&mut self, mode: Mode) -> ConsoleAction {
+        self.mode = mode;
+        self.resuming = true;
+        ConsoleAction::Resume
+    }
+
+    fn step_over(&mut self, chip8: &Chip8) -> ConsoleAction {
+        let pc = chip8.pc as usize;
+        let is_call = chip8.memory.get(pc).is_some_and(|&byte| byte & 0xF0 == 0x20);
+        if is_call {
+            self.resume(Mode::StepOver { pc: chip8.pc.wrapping_add(2), sp: chip8.sp })
+        } else {
+            self.resume(Mode::Step(1))
+        }
+    }
+
+    fn add_breakpoint(&mut self, args: &[&str]) -> Result<(), String> {
+        match args {
+            [address] => {
+                self.breakpoints.insert(parse_number(address)?, None);
+                Ok(())
+            }
+            [address, "if", condition @ ..] => {
+                let condition = Condition::parse(condition)?;
+                self.breakpoints.insert(parse_number(address)?, Some(condition));
+                Ok(())
+            }
+            _ => Err("usage: break ADDR [if REG OP VALUE]".to_string()),
+        }
+    }
+
+    fn delete_breakpoint(&mut self, args: &[&str]) -> Result<(), String> {
+        match args {
+            [] => {
+                self.breakpoints.clear();
+                Ok(())
+            }
+            [address] => match self.breakpoints.remove(&parse_number(address)?) {
+                Some(_) => Ok(()),
+                None => Err(format!("no breakpoint at {}", address)),
+            },
+            _ => Err("usage: delete [ADDR]".to_string()),
+        }
+    }
+
+    fn add_watchpoint(&mut self, args: &[&str]) -> Result<(), String> {
+        let (range, kind) = match args {
+            [range] => (*range, WatchKind::Write),
+            [range, kind] => {
+                let kind = match *kind {
+                    "r" => WatchKind::Read,
+                    "w" => WatchKind::Write,
+                    "rw" => WatchKind::Access,
+                    _ => return Err(format!("unknown watch kind '{}'; use r, w or rw", kind)),
+                };
+                (*range, kind)
+            }
+            _ => return Err("usage: watch ADDR[-END] [r|w|rw]".to_string()),
+        };
+        let (start, end) = match range.split_once('-') {
+            Some((start, end)) => (parse_number(start)? as usize, parse_number(end)? as usize),
+            None => {
+                let address = parse_number(range)? as usize;
+                (address, address)
+            }
+        };
+        self.watchpoints.push(Watchpoint { start, end, kind });
+        Ok(())
+    }
+
+    fn delete_watchpoint(&mut self, args: &[&str]) -> Result<(), String> {
+        match args {
+            [] => {
+                self.watchpoints.clear();
+                Ok(())
+            }
+            [address] => {
+                let address = parse_number(address)? as usize;
+                self.watchpoints.retain(|w| w.start != address);
+                Ok(())
+            }
+            _ => Err("usage: unwatch [ADDR]".to_string()),
+        }
+    }
+
+    fn delete_condition(&mut self, args: &[&str]) -> Result<(), String> {
+        match args {
+            [] => {
+                self.conditions.clear();
+                self.condition_state.clear();
+                Ok(())
+            }
+            [index] => {
+                let index = parse_number(index)? as usize;
+                if index >= self.conditions.len() {
+                    return Err(format!("no condition #{}", index));
+                }
+                self.conditions.remove(index);
+                if index < self.condition_state.len() {
+                    self.condition_state.remove(index);
+                }
+                Ok(())
+            }
+            _ => Err("usage: unwhen [INDEX]".to_string()),
+        }
+    }
+
+    fn print_points<W: Write>(&self, output: &mut W) -> io::Result<()> {
+        for (address, condition) in &self.breakpoints {
+            match condition {
+                Some(condition) => writeln!(output, "break {:#05X} if {}", address, condition)?,
+                None => writeln!(output, "break {:#05X}", address)?,
+            }
+        }
+        for w in &self.watchpoints {
+            let kind = match w.kind {
+                WatchKind::Read => "r",
+                WatchKind::Write => "w",
+                WatchKind::Access => "rw",
+            };
+            writeln!(output, "watch {:#05X}-{:#05X} {}", w.start, w.end, kind)?;
+        }
+        for (index, condition) in self.conditions.iter().enumerate() {
+            writeln!(output, "when #{}: {}", index, condition)?;
+        }
+        Ok(())
+    }
+
+    pub fn print_state<W: Write>(&self, chip8: &Chip8, output: &mut W) -> io::Result<()> {
+        for row in 0..2 {
+            let registers: Vec<String> = (0..8)
+                .map(|x| row * 8 + x)
+                .map(|x| format!("V{:X}={:02X}", x, chip8.v[x]))
+                .collect();
+            writeln!(output, "{}", registers.join(" "))?;
+        }
+        writeln!(
+            output,
+            "I={:03X} PC={:03X} SP={:X} DT={:02X} ST={:02X}",
+            chip8.i, chip8.pc, chip8.sp, chip8.delay_timer, chip8.sound_timer
+        )?;
+        let stack: Vec<String> = chip8.stack[..chip8.sp.min(chip8.stack.len())]
+            .iter()
+            .rev()
+            .map(|address| format!("{:03X}", address))
+            .collect();
+        writeln!(output, "call stack: [{}]", stack.join(" "))?;
+        self.print_disassembly(chip8, None, 7, output)
+    }
+
+    // Lists `count` instructions starting at `start`, or a few before and
+    // after the PC when no start is given.
+    pub fn print_disassembly<W: Write>(&self, chip8: &Chip8, start: Option<u16>, count: u16, output: &mut W) -> io::Result<()> {
+        let start = start.unwrap_or_else(|| chip8.pc.saturating_sub(6));
+        for n in 0..count {
+            let address = start.wrapping_add(n * 2);
+            let (opcode, text) = disassemble_at(chip8, address);
+            let marker = if address == chip8.pc { '>' } else { ' ' };
+            let breakpoint = if self.breakpoints.contains_key(&address) { '*' } else { ' ' };
+            writeln!(output, "{}{} {:03X}: {:04X}  {}", marker, breakpoint, address, opcode, text)?;
+        }
+        Ok(())
+    }
+}
+
+impl Default for Debugger {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn disassemble_at(chip8: &Chip8, address: u16) -> (u16, String) {
+    let byte = |offset: u16| chip8.memory.get(address.wrapping_add(offset) as usize).copied().unwrap_or(0) as u16;
+    let opcode = byte(0) << 8 | byte(1);
+    let operand = byte(2) << 8 | byte(3);
+    (opcode, decode(opcode, operand).display())
+}
+
+fn print_memory<W: Write>(chip8: &Chip8, args: &[&str], output: &mut W) -> Result<(), String> {
+    let (start, len) = match args {
+        [start] => (parse_number(start)? as usize, 16),
+        [start, len] => (parse_number(start)? as usize, parse_number(len)? as usize),
+        _ => return Err("usage: x ADDR [LEN]".to_string()),
+    };
+    let end = (start + len).min(chip8.memory.len());
+    for (row, bytes) in chip8.memory[start.min(end)..end].chunks(16).enumerate() {
+        let hex: Vec<String> = bytes.iter().map(|byte| format!("{:02X}", byte)).collect();
+        writeln!(output, "{:04X}: {}", start + row * 16, hex.join(" ")).map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+fn parse_number(s: &str) -> Result<u16, String> {
+    let parsed = match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        Some(hex) => u16::from_str_radix(hex, 16),
+        None => s.parse(),
+    };
+    parsed.map_err(|_| format!("invalid number '{}'", s))
+}
+
+const HELP: &str = "\
+c, continue              resume execution
+s, step [N]              execute N instructions (default 1)
+n, next                  step over a CALL
+f, finish                run until the current subroutine returns
+b, break ADDR [if COND]  set a breakpoint, optionally only while COND holds
+d, delete [ADDR]         remove one or all breakpoints
+w, watch ADDR[-END] [r|w|rw]
+                         stop on memory reads and/or writes (default w)
+unwatch [ADDR]           remove one or all watchpoints
+when COND                stop whenever COND becomes true, e.g. 'when V3 == 0x10'
+unwhen [INDEX]           remove one or all conditions
+i, info                  list breakpoints, watchpoints and conditions
+r, regs                  show registers, call stack and code around PC
+x ADDR [LEN]             dump memory
+l, list [ADDR]           disassemble
+rw, rewind [N]           go back N frames (default 1); 0 returns to the start
+                         of the current frame
+q, quit                  exit the emulator
+";
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Calls a subroutine that stores V0 and V1 at 0x300, then counts V0 up forever.
+    const PROGRAM: &[u16] = &[
+        0x6005, // 200: LD V0, 5
+        0x2208, // 202: CALL 0x208
+        0x7001, // 204: ADD V0, 1
+        0x1204, // 206: JP 0x204
+        0x6103, // 208: LD V1, 3
+        0xA300, // 20A: LD I, 0x300
+        0xF155, // 20C: LD [I], V1
+        0x00EE, // 20E: RET
+    ];
+
+    fn machine() -> Chip8 {
+        let rom: Vec<u8> = PROGRAM.iter().flat_map(|opcode| opcode.to_be_bytes()).collect();
+        let mut chip8 = Chip8::new();
+        chip8.load_rom(&rom).expect("program loads");
+        chip8
+    }
+
+    // Feeds `script` to the console and returns its action and everything it printed.
+    fn console(debugger: &mut Debugger, chip8: &mut Chip8, script: &str) -> (ConsoleAction, String) {
+        let mut output = Vec::new();
+        let action = debugger
+            .console(chip8, &StopReason::Interrupted, &mut script.as_bytes(), &mut output)
+            .expect("writes to a Vec");
+        (action, String::from_utf8(output).expect("output is text"))
+    }
+
+    #[test]
+    fn breakpoints_stop_before_the_instruction() {
+        let mut chip8 = machine();
+        let mut debugger = Debugger::new();
+        debugger.attach(&mut chip8);
+        assert_eq!(console(&mut debugger, &mut chip8, "b 0x208\nc\n").0, ConsoleAction::Resume);
+
+        assert_eq!(debugger.run_frame(&mut chip8, 100), Some(StopReason::Breakpoint(0x208)));
+        assert_eq!((chip8.pc, chip8.v[1]), (0x208, 0));
+
+        // Continuing runs the instruction under the breakpoint instead of stopping again.
+        assert_eq!(console(&mut debugger, &mut chip8, "c\n").0, ConsoleAction::Resume);
+        assert_eq!(debugger.step(&mut chip8), None);
+        assert_eq!((chip8.pc, chip8.v[1]), (0x20A, 3));
+        assert_eq!(debugger.run_frame(&mut chip8, 100), None);
+    }
+
+    #[test]
+    fn conditional_breakpoints_wait_for_the_condition() {
+        let mut chip8 = machine();
+        let mut debugger = Debugger::new();
+        let (_, output) = console(&mut debugger, &mut chip8, "b 0x204 if V0 == 8\ni\nc\n");
+        assert!(output.contains("break 0x204 if V0 == 0x8"), "{}", output);
+
+        assert_eq!(debugger.run_frame(&mut chip8, 100), Some(StopReason::Breakpoint(0x204)));
+        assert_eq!(chip8.v[0], 8);
+    }
+
+    #[test]
+    fn step_next_and_finish() {
+        let mut chip8 = machine();
+        let mut debugger = Debugger::new();
+        console(&mut debugger, &mut chip8, "b 0x202\nc\n");
+        assert_eq!(debugger.run_frame(&mut chip8, 100), Some(StopReason::Breakpoint(0x202)));
+
+        // Stepping over the CALL runs the whole subroutine.
+        assert_eq!(console(&mut debugger, &mut chip8, "n\n").0, ConsoleAction::Resume);
+        assert_eq!(debugger.run_frame(&mut chip8, 100), Some(StopReason::Step));
+        assert_eq!((chip8.pc, chip8.sp, chip8.v[1]), (0x204, 0, 3));
+
+        console(&mut debugger, &mut chip8, "s 3\n");
+        assert_eq!(debugger.run_frame(&mut chip8, 100), Some(StopReason::Step));
+        assert_eq!((chip8.pc, chip8.v[0]), (0x206, 7));
+
+        // Finishing from inside the subroutine stops after its RET.
+        let mut chip8 = machine();
+        let mut debugger = Debugger::new();
+        console(&mut debugger, &mut chip8, "b 0x20A\nc\n");
+        assert_eq!(debugger.run_frame(&mut chip8, 100), Some(StopReason::Breakpoint(0x20A)));
+        console(&mut debugger, &mut chip8, "f\n");
+        assert_eq!(debugger.run_frame(&mut chip8, 100), Some(StopReason::Step));
+        assert_eq!((chip8.pc, chip8.sp), (0x204, 0));
+    }
+
+    #[test]
+    fn watchpoints_and_conditions() {
+        let mut chip8 = machine();
+        let mut debugger = Debugger::new();
+        debugger.attach(&mut chip8);
+        console(&mut debugger, &mut chip8, "w 0x300-0x301\nc\n");
+        assert_eq!(
+            debugger.run_frame(&mut chip8, 100),
+            Some(StopReason::Watchpoint { address: 0x300, kind: AccessKind::Write, value: 5 })
+        );
+        assert_eq!(chip8.pc, 0x20E);
+
+        let mut chip8 = machine();
+        let mut debugger = Debugger::new();
+        console(&mut debugger, &mut chip8, "when V1 == 3\nc\n");
+        let condition = "V1 == 3".parse().expect("condition parses");
+        assert_eq!(debugger.run_frame(&mut chip8, 100), Some(StopReason::Condition(condition)));
+        assert_eq!(chip8.pc, 0x20A);
+        // The condition only fires again after it has been false.
+        assert_eq!(debugger.run_frame(&mut chip8, 100), None);
+    }
+
+    #[test]
+    fn bad_commands_print_errors_and_keep_reading() {
+        let mut chip8 = machine();
+        let mut debugger = Debugger::new();
+        let script = "frobnicate\nb\nb 0xZZ\nb 0x204 if V3 >> 1\nb 0x204 if VG == 1\nw 0x300 x\nd 0x400\nunwhen 0\ns ten\nx\n";
+        let (action, output) = console(&mut debugger, &mut chip8, script);
+
+        // Running out of input quits.
+        assert_eq!(action, ConsoleAction::Quit);
+        assert!(debugger.breakpoints.is_empty() && debugger.watchpoints.is_empty());
+        let errors: Vec<&str> = output.lines().filter_map(|line| line.split("error: ").nth(1)).collect();
+        assert_eq!(
+            errors,
+            [
+                "unknown command 'frobnicate'; try 'help'",
+                "usage: break ADDR [if REG OP VALUE]",
+                "invalid number '0xZZ'",
+                "unknown comparison '>>'",
+                "unknown register 'VG'",
+                "unknown watch kind 'x'; use r, w or rw",
+                "no breakpoint at 0x400",
+                "no condition #0",
+                "invalid number 'ten'",
+                "usage: x ADDR [LEN]",
+            ]
+        );
+    }
+
+    #[test]
+    fn parses_numbers_and_conditions() {
+        assert_eq!(parse_number("0x2A"), Ok(42));
+        assert_eq!(parse_number("42"), Ok(42));
+        assert!(parse_number("0x10000").is_err());
+        assert_eq!(
+            "pc >= 0x300".parse(),
+            Ok(Condition { register: Register::Pc, comparison: Comparison::Ge, value: 0x300 })
+        );
+        assert_eq!("V3 == 1 2".parse::<Condition>(), Err("expected a condition like 'V3 == 0x10'".to_string()));
+        assert_eq!(console(&mut Debugger::new(), &mut machine(), "rw 5\n").0, ConsoleAction::Rewind(5));
+        assert_eq!(console(&mut Debugger::new(), &mut machine(), "quit\n").0, ConsoleAction::Quit);
+    }
+}
@@ -0,0 +1,704 @@
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+use std::str::FromStr;
+
+use crate::chip8::{decode, Instruction};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Format {
+    // Mnemonics as printed by `Instruction::display`, with `db` for data.
+    #[default]
+    Text,
+    Octo,
+    Json,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "text" | "txt" => Ok(Format::Text),
+            "octo" | "8o" => Ok(Format::Octo),
+            "json" => Ok(Format::Json),
+            _ => Err(format!("unknown output format '{}'", s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LineKind {
+    Code { opcode: u16, operand: Option<u16>, target: Option<u16> },
+    Data,
+    // One row of a sprite drawn by the program, 8 or 16 pixels wide.
+    Sprite { width: u8 },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Line {
+    pub address: u16,
+    pub bytes: Vec<u8>,
+    pub kind: LineKind,
+}
+
+#[derive(Debug, Clone)]
+pub struct Listing {
+    pub origin: u16,
+    pub lines: Vec<Line>,
+    // Only addresses that start a line get a label.
+    pub labels: BTreeMap<u16, String>,
+}
+
+// Ordered by precedence when one address is reached in several ways.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum LabelKind {
+    Data,
+    Jump,
+    Subroutine,
+    Entry,
+}
+
+impl LabelKind {
+    fn name(&self, address: u16) -> String {
+        match self {
+            LabelKind::Data => format!("data_{:03X}", address),
+            LabelKind::Jump => format!("label_{:03X}", address),
+            LabelKind::Subroutine => format!("sub_{:03X}", address),
+            LabelKind::Entry => "main".to_string(),
+        }
+    }
+}
+
+// What an instruction does to the flow of control, as far as a static
+// trace can tell.
+enum Flow {
+    Next,
+    Stop,
+    Skip,
+    Jump(u16),
+    Call(u16),
+    // `BNNN`: somewhere at or after NNN, usually a table of jumps.
+    JumpIndexed(u16),
+    LoadI(u16),
+    // Leaves I pointing somewhere the trace can't follow.
+    ModifyI,
+    Draw(u8),
+}
+
+impl Flow {
+    // Mirrors `decode`; `None` for opcodes it treats as invalid.
+    fn of(opcode: u16, operand: u16) -> Option<Flow> {
+        let nnn = opcode & 0x0FFF;
+        let flow = match opcode & 0xF000 {
+            0x0000 => match opcode {
+                0x00EE | 0x00FD => Flow::Stop,
+                0x00E0 | 0x00FB | 0x00FC | 0x00FE | 0x00FF => Flow::Next,
+                _ if opcode & 0xFFE0 == 0x00C0 => Flow::Next,
+                // SYS: machine code the interpreter won't run.
+                _ => Flow::Stop,
+            },
+            0x1000 => Flow::Jump(nnn),
+            0x2000 => Flow::Call(nnn),
+            0x3000 | 0x4000 => Flow::Skip,
+            // `decode` ignores the low nibble of 9XY0, but only 0 assembles back.
+            0x9000 if opcode & 0x000F == 0 => Flow::Skip,
+            0x9000 => return None,
+            0x5000 => match opcode & 0x000F {
+                0x0 => Flow::Skip,
+                0x2 | 0x3 => Flow::Next,
+                _ => return None,
+            },
+            0x6000 | 0x7000 | 0xC000 => Flow::Next,
+            0x8000 => match opcode & 0x000F {
+                0x0..=0x7 | 0xE => Flow::Next,
+                _ => return None,
+            },
+            0xA000 => Flow::LoadI(nnn),
+            0xB000 => Flow::JumpIndexed(nnn),
+            0xD000 => Flow::Draw((opcode & 0x000F) as u8),
+            0xE000 => match opcode & 0x00FF {
+                0x9E | 0xA1 => Flow::Skip,
+                _ => return None,
+            },
+            _ => match opcode & 0x00FF {
+                0x00 if opcode == 0xF000 => Flow::LoadI(operand),
+                0x02 if opcode == 0xF002 => Flow::Next,
+                0x01 | 0x07 | 0x0A | 0x15 | 0x18 | 0x33 | 0x3A | 0x55 | 0x65 | 0x75 | 0x85 => Flow::Next,
+                0x1E | 0x29 | 0x30 => Flow::ModifyI,
+                _ => return None,
+            },
+        };
+        Some(flow)
+    }
+
+    fn target(&self) -> Option<u16> {
+        match *self {
+            Flow::Jump(target) | Flow::Call(target) | Flow::JumpIndexed(target) | Flow::LoadI(target) => Some(target),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum ByteKind {
+    Unknown,
+    // First byte of an instruction.
+    Code,
+    // Any later byte of an instruction.
+    Operand,
+}
+
+struct Analysis<'a> {
+    rom: &'a [u8],
+    origin: u16,
+    kinds: Vec<ByteKind>,
+    labels: BTreeMap<u16, LabelKind>,
+    // Sprite width in pixels for each byte drawn by a traced `DXYN`.
+    sprite_widths: Vec<u8>,
+}
+
+impl<'a> Analysis<'a> {
+    fn new(rom: &'a [u8], origin: u16) -> Self {
+        Self {
+            rom,
+            origin,
+            kinds: vec![ByteKind::Unknown; rom.len()],
+            labels: BTreeMap::new(),
+            sprite_widths: vec![0; rom.len()],
+        }
+    }
+
+    fn offset(&self, address: u16) -> Option<usize> {
+        let offset = address.checked_sub(self.origin)? as usize;
+        (offset < self.rom.len()).then_some(offset)
+    }
+
+    // Returns the opcode, the operand of `F000 NNNN` and the instruction length.
+    fn fetch(&self, address: u16) -> Option<(u16, Option<u16>, usize)> {
+        let offset = self.offset(address)?;
+        let word = |at: usize| -> Option<u16> {
+            Some((*self.rom.get(at)? as u16) << 8 | *self.rom.get(at + 1)? as u16)
+        };
+        let opcode = word(offset)?;
+        if opcode == 0xF000 {
+            Some((opcode, Some(word(offset + 2)?), 4))
+        } else {
+            Some((opcode, None, 2))
+        }
+    }
+
+    fn label(&mut self, address: u16, kind: LabelKind) {
+        if self.offset(address).is_some() {
+            let entry = self.labels.entry(address).or_insert(kind);
+            *entry = (*entry).max(kind);
+        }
+    }
+
+    // Follows every path from the entry point, marking the bytes it reaches as
+    // code. I is tracked along each path so that sprites can be sized by the
+    // `DXYN` that draws them.
+    fn trace(&mut self, entry: u16) {
+        self.label(entry, LabelKind::Entry);
+        let mut pending: Vec<(u16, Option<u16>)> = vec![(entry, None)];
+
+        while let Some((address, i)) = pending.pop() {
+            let Some(offset) = self.offset(address) else {
+                continue;
+            };
+            let Some((opcode, operand, len)) = self.fetch(address) else {
+                continue;
+            };
+            // Skip bytes already decoded, and sprites a skip might land on.
+            if self.kinds[offset..offset + len].iter().any(|&kind| kind != ByteKind::Unknown)
+                || self.sprite_widths[offset] != 0
+            {
+                continue;
+            }
+            let Some(flow) = Flow::of(opcode, operand.unwrap_or(0)) else {
+                continue;
+            };
+
+            self.kinds[offset] = ByteKind::Code;
+            self.kinds[offset + 1..offset + len].fill(ByteKind::Operand);
+
+            let next = address.wrapping_add(len as u16);
+            match flow {
+                Flow::Next => pending.push((next, i)),
+                Flow::Stop => {}
+                Flow::Skip => {
+                    let skipped = match self.fetch(next) {
+                        Some((_, _, len)) => len as u16,
+                        None => 2,
+                    };
+                    pending.push((next, i));
+                    pending.push((next.wrapping_add(skipped), i));
+                }
+                Flow::Jump(target) => {
+                    self.label(target, LabelKind::Jump);
+                    pending.push((target, i));
+                }
+                Flow::Call(target) => {
+                    self.label(target, LabelKind::Subroutine);
+                    pending.push((target, None));
+                    pending.push((next, None));
+                }
+                Flow::JumpIndexed(target) => {
+                    self.label(target, LabelKind::Jump);
+                    pending.push((target, None));
+                    // Follow a table of jumps as far as it goes.
+                    let mut entry = target;
+                    while let Some((opcode, _, _)) = self.fetch(entry) {
+                        if opcode & 0xF000 != 0x1000 {
+                            break;
+                        }
+                        pending.push((entry, None));
+                        entry = entry.wrapping_add(2);
+                    }
+                }
+                Flow::LoadI(target) => {
+                    self.label(target, LabelKind::Data);
+                    pending.push((next, Some(target)));
+                }
+                Flow::ModifyI => pending.push((next, None)),
+                Flow::Draw(n) => {
+                    if let Some(sprite) = i {
+                        self.mark_sprite(sprite, n);
+                    }
+                    pending.push((next, i));
+                }
+            }
+        }
+    }
+
+    fn mark_sprite(&mut self, address: u16, n: u8) {
+        let Some(offset) = self.offset(address) else {
+            return;
+        };
+        let (width, len) = if n == 0 { (16, 32) } else { (8, n as usize) };
+        let end = (offset + len).min(self.rom.len());
+        for sprite_width in &mut self.sprite_widths[offset..end] {
+            *sprite_width = (*sprite_width).max(width);
+        }
+    }
+
+    fn into_listing(self) -> Listing {
+        let mut lines = Vec::new();
+        let mut offset = 0;
+
+        while offset < self.rom.len() {
+            let address = self.origin.wrapping_add(offset as u16);
+            if self.kinds[offset] == ByteKind::Code {
+                if let Some((opcode, operand, len)) = self.fetch(address) {
+                    let target = Flow::of(opcode, operand.unwrap_or(0)).and_then(|flow| flow.target());
+                    lines.push(Line {
+                        address,
+                        bytes: self.rom[offset..offset + len].to_vec(),
+                        kind: LineKind::Code { opcode, operand, target },
+                    });
+                    offset += len;
+                    continue;
+                }
+            }
+
+            let len = self.data_len(offset);
+            let width = self.sprite_widths[offset];
+            let kind = if width != 0 && len == width as usize / 8 {
+                LineKind::Sprite { width }
+            } else {
+                LineKind::Data
+            };
+            lines.push(Line { address, bytes: self.rom[offset..offset + len].to_vec(), kind });
+            offset += len;
+        }
+
+        let labels = self.labels
+            .iter()
+            .filter(|(address, _)| lines.binary_search_by_key(*address, |line| line.address).is_ok())
+            .map(|(&address, kind)| (address, kind.name(address)))
+            .collect();
+
+        Listing { origin: self.origin, lines, labels }
+    }
+
+    // Sprite rows get a line each; other data is grouped eight bytes to a line.
+    // Lines never run past a label, into code, or across a change of sprite width.
+    fn data_len(&self, start: usize) -> usize {
+        let width = self.sprite_widths[start];
+        let max = if width != 0 { width as usize / 8 } else { 8 };
+        let mut len = 1;
+        while len < max && start + len < self.rom.len() {
+            let offset = start + len;
+            let address = self.origin.wrapping_add(offset as u16);
+            if self.kinds[offset] != ByteKind::Unknown
+                || self.labels.contains_key(&address)
+                || self.sprite_widths[offset] != width
+            {
+                break;
+            }
+            len += 1;
+        }
+        len
+    }
+}
+
+// Disassembles a ROM loaded at `origin`, telling code from data by tracing
+// control flow from the first instruction.
+pub fn disassemble(rom: &[u8], origin: u16) -> Listing {
+    let mut analysis = Analysis::new(rom, origin);
+    analysis.trace(origin);
+    analysis.into_listing()
+}
+
+impl Listing {
+    pub fn write<W: Write>(&self, format: Format, output: &mut W) -> io::Result<()> {
+        match format {
+            Format::Text => self.write_text(output),
+            Format::Octo => self.write_octo(output),
+            Format::Json => self.write_json(output),
+        }
+    }
+
+    // The name of the label at `address`, or the address itself.
+    fn reference(&self, address: u16) -> String {
+        match self.labels.get(&address) {
+            Some(label) => label.clone(),
+            None => format!("{:#X}", address),
+        }
+    }
+
+    // The mnemonic from `Instruction::display`, or a `db` directive for data.
+    pub fn text(&self, line: &Line) -> String {
+        match line.kind {
+            LineKind::Code { opcode, operand, target } => {
+                let mnemonic = decode(opcode, operand.unwrap_or(0)).display();
+                // Instructions with a target print it last.
+                match (target, mnemonic.rsplit_once(' ')) {
+                    (Some(target), Some((rest, _))) if self.labels.contains_key(&target) => {
+                        format!("{} {}", rest, self.reference(target))
+                    }
+                    _ => mnemonic,
+                }
+            }
+            LineKind::Data | LineKind::Sprite { .. } => {
+                let bytes: Vec<String> = line.bytes.iter().map(|byte| format!("0x{:02X}", byte)).collect();
+                format!("db {}", bytes.join(", "))
+            }
+        }
+    }
+
+    fn write_text<W: Write>(&self, output: &mut W) -> io::Result<()> {
+        writeln!(output, "; {} bytes at {:#X}", self.lines.iter().map(|line| line.bytes.len()).sum::<usize>(), self.origin)?;
+        for line in &self.lines {
+            if let Some(label) = self.labels.get(&line.address) {
+                writeln!(output, "\n{}:", label)?;
+            }
+            writeln!(output, "    {:<28}; {:03X}  {}", self.text(line), line.address, comment(line))?;
+        }
+        Ok(())
+    }
+
+    fn write_octo<W: Write>(&self, output: &mut W) -> io::Result<()> {
+        if self.origin != 0x200 {
+            writeln!(output, ":org {:#X}", self.origin)?;
+        }
+        for line in &self.lines {
+            if let Some(label) = self.labels.get(&line.address) {
+                writeln!(output, "\n: {}", label)?;
+            }
+            let text = match line.kind {
+                LineKind::Code { opcode, operand, .. } => self.octo(opcode, operand.unwrap_or(0)),
+                LineKind::Data => octo_bytes(&line.bytes),
+                LineKind::Sprite { .. } => {
+                    let rows: Vec<String> = line.bytes.iter().map(|byte| format!("0b{:08b}", byte)).collect();
+                    rows.join(" ")
+                }
+            };
+            writeln!(output, "    {:<28}# {:03X}  {}", text, line.address, comment(line))?;
+        }
+        Ok(())
+    }
+
+    fn octo(&self, opcode: u16, operand: u16) -> String {
+        let x = (opcode & 0x0F00) >> 8;
+        let y = (opcode & 0x00F0) >> 4;
+        let n = opcode & 0x000F;
+        let nn = opcode & 0x00FF;
+        let nnn = opcode & 0x0FFF;
+
+        match opcode & 0xF000 {
+            0x0000 => match opcode {
+                0x00E0 => "clear".to_string(),
+                0x00EE => "return".to_string(),
+                0x00FB => "scroll-right".to_string(),
+                0x00FC => "scroll-left".to_string(),
+                0x00FD => "exit".to_string(),
+                0x00FE => "lores".to_string(),
+                0x00FF => "hires".to_string(),
+                _ if opcode & 0xFFF0 == 0x00C0 => format!("scroll-down {}", n),
+                _ if opcode & 0xFFF0 == 0x00D0 => format!("scroll-up {}", n),
+                _ => octo_bytes(&opcode.to_be_bytes()),
+            },
+            0x1000 => format!("jump {}", self.reference(nnn)),
+            0x2000 => format!(":call {}", self.reference(nnn)),
+            // Octo conditions skip when false, so each CHIP-8 skip reads inverted.
+            0x3000 => format!("if v{:x} != {:#X} then", x, nn),
+            0x4000 => format!("if v{:x} == {:#X} then", x, nn),
+            0x5000 => match n {
+                0x0 => format!("if v{:x} != v{:x} then", x, y),
+                0x2 => format!("save v{:x} - v{:x}", x, y),
+                0x3 => format!("load v{:x} - v{:x}", x, y),
+                _ => octo_bytes(&opcode.to_be_bytes()),
+            },
+            0x6000 => format!("v{:x} := {:#X}", x, nn),
+            0x7000 => format!("v{:x} += {:#X}", x, nn),
+            0x8000 => {
+                let operator = match n {
+                    0x0 => ":=",
+                    0x1 => "|=",
+                    0x2 => "&=",
+                    0x3 => "^=",
+                    0x4 => "+=",
+                    0x5 => "-=",
+                    0x6 => ">>=",
+                    0x7 => "=-",
+                    0xE => "<<=",
+                    _ => return octo_bytes(&opcode.to_be_bytes()),
+                };
+                format!("v{:x} {} v{:x}", x, operator, y)
+            }
+            0x9000 if n == 0 => format!("if v{:x} == v{:x} then", x, y),
+            0xA000 => format!("i := {}", self.reference(nnn)),
+            0xB000 => format!("jump0 {}", self.reference(nnn)),
+            0xC000 => format!("v{:x} := random {:#X}", x, nn),
+            0xD000 => format!("sprite v{:x} v{:x} {}", x, y, n),
+            0xE000 if nn == 0x9E => format!("if v{:x} -key then", x),
+            0xE000 if nn == 0xA1 => format!("if v{:x} key then", x),
+            0xF000 => match nn {
+                0x00 if opcode == 0xF000 => format!("i := long {}", self.reference(operand)),
+                0x01 => format!("plane {}", x),
+                0x02 if opcode == 0xF002 => "audio".to_string(),
+                0x07 => format!("v{:x} := delay", x),
+                0x0A => format!("v{:x} := key", x),
+                0x15 => format!("delay := v{:x}", x),
+                0x18 => format!("buzzer := v{:x}", x),
+                0x1E => format!("i += v{:x}", x),
+                0x29 => format!("i := hex v{:x}", x),
+                0x30 => format!("i := bighex v{:x}", x),
+                0x33 => format!("bcd v{:x}", x),
+                0x3A => format!("pitch := v{:x}", x),
+                0x55 => format!("save v{:x}", x),
+                0x65 => format!("load v{:x}", x),
+                0x75 => format!("saveflags v{:x}", x),
+                0x85 => format!("loadflags v{:x}", x),
+                _ => octo_bytes(&opcode.to_be_bytes()),
+            },
+            _ => octo_bytes(&opcode.to_be_bytes()),
+        }
+    }
+
+    fn write_json<W: Write>(&self, output: &mut W) -> io::Result<()> {
+        writeln!(output, "{{")?;
+        writeln!(output, "  \"origin\": {},", self.origin)?;
+        let labels: Vec<String> = self.labels
+            .iter()
+            .map(|(address, label)| format!("{}: {}", json_string(label), address))
+            .collect();
+        writeln!(output, "  \"labels\": {{{}}},", labels.join(", "))?;
+        writeln!(output, "  \"lines\": [")?;
+        for (index, line) in self.lines.iter().enumerate() {
+            let kind = match line.kind {
+                LineKind::Code { .. } => "code",
+                LineKind::Data => "data",
+                LineKind::Sprite { .. } => "sprite",
+            };
+            let bytes: String = line.bytes.iter().map(|byte| format!("{:02X}", byte)).collect();
+            write!(
+                output,
+                "    {{\"address\": {}, \"kind\": \"{}\", \"bytes\": \"{}\", \"text\": {}",
+                line.address,
+                kind,
+                bytes,
+                json_string(&self.text(line)),
+            )?;
+            if let Some(label) = self.labels.get(&line.address) {
+                write!(output, ", \"label\": {}", json_string(label))?;
+            }
+            if let LineKind::Code { target: Some(target), .. } = line.kind {
+                write!(output, ", \"target\": {}", target)?;
+            }
+            let separator = if index + 1 < self.lines.len() { "," } else { "" };
+            writeln!(output, "}}{}", separator)?;
+        }
+        writeln!(output, "  ]")?;
+        writeln!(output, "}}")
+    }
+}
+
+// Raw opcodes for code and data, or the pixels of a sprite row.
+fn comment(line: &Line) -> String {
+    match line.kind {
+        LineKind::Sprite { .. } => line.bytes
+            .iter()
+            .map(|byte| format!("{:08b}", byte).replace('0', ".").replace('1', "#"))
+            .collect(),
+        LineKind::Code { .. } => {
+            let words: Vec<String> = line.bytes.chunks(2).map(|word| word.iter().map(|byte| format!("{:02X}", byte)).collect()).collect();
+            words.join(" ")
+        }
+        LineKind::Data => line.bytes.iter().map(|byte| format!("{:02X}", byte)).collect(),
+    }
+}
+
+fn octo_bytes(bytes: &[u8]) -> String {
+    let bytes: Vec<String> = bytes.iter().map(|byte| format!("{:#04x}", byte)).collect();
+    bytes.join(" ")
+}
+
+fn json_string(s: &str) -> String {
+    let mut escaped = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A subroutine, an endless loop with unreachable bytes after it, and a
+    // three-row sprite followed by a stray byte.
+    const ROM: &[u8] = &[
+        0x00, 0xE0, // 200: CLS
+        0x22, 0x0C, // 202: CALL 0x20C
+        0xA2, 0x10, // 204: LD I, 0x210
+        0xD0, 0x13, // 206: DRW V0, V1, 3
+        0x12, 0x08, // 208: JP 0x208
+        0x12, 0x34, // 20A: never reached
+        0x60, 0x01, // 20C: LD V0, 1
+        0x00, 0xEE, // 20E: RET
+        0x3C, 0x42, 0x3C, // 210: sprite
+        0xFF,
+    ];
+
+    fn output(format: Format) -> String {
+        let mut output = Vec::new();
+        disassemble(ROM, 0x200).write(format, &mut output).expect("writes to a Vec");
+        String::from_utf8(output).expect("output is text")
+    }
+
+    #[test]
+    fn tracing_separates_code_from_data() {
+        let listing = disassemble(ROM, 0x200);
+        let kinds: Vec<(u16, &str, usize)> = listing.lines
+            .iter()
+            .map(|line| {
+                let kind = match line.kind {
+                    LineKind::Code { .. } => "code",
+                    LineKind::Data => "data",
+                    LineKind::Sprite { .. } => "sprite",
+                };
+                (line.address, kind, line.bytes.len())
+            })
+            .collect();
+        assert_eq!(
+            kinds,
+            [
+                (0x200, "code", 2),
+                (0x202, "code", 2),
+                (0x204, "code", 2),
+                (0x206, "code", 2),
+                (0x208, "code", 2),
+                (0x20A, "data", 2),
+                (0x20C, "code", 2),
+                (0x20E, "code", 2),
+                (0x210, "sprite", 1),
+                (0x211, "sprite", 1),
+                (0x212, "sprite", 1),
+                (0x213, "data", 1),
+            ]
+        );
+
+        let labels: Vec<(u16, &str)> = listing.labels.iter().map(|(&address, label)| (address, label.as_str())).collect();
+        assert_eq!(labels, [(0x200, "main"), (0x208, "label_208"), (0x20C, "sub_20C"), (0x210, "data_210")]);
+    }
+
+    #[test]
+    fn writes_text() {
+        let expected = "\
+; 20 bytes at 0x200
+
+main:
+    CLS                         ; 200  00E0
+    CALL sub_20C                ; 202  220C
+    LD I, data_210              ; 204  A210
+    DRW V0, V1, 0x3             ; 206  D013
+
+label_208:
+    JMP to label_208            ; 208  1208
+    db 0x12, 0x34               ; 20A  1234
+
+sub_20C:
+    LD V0, 0x1                  ; 20C  6001
+    RET                         ; 20E  00EE
+
+data_210:
+    db 0x3C                     ; 210  ..####..
+    db 0x42                     ; 211  .#....#.
+    db 0x3C                     ; 212  ..####..
+    db 0xFF                     ; 213  FF
+";
+        assert_eq!(output(Format::Text), expected);
+    }
+
+    #[test]
+    fn writes_octo() {
+        let expected = "
+: main
+    clear                       # 200  00E0
+    :call sub_20C               # 202  220C
+    i := data_210               # 204  A210
+    sprite v0 v1 3              # 206  D013
+
+: label_208
+    jump label_208              # 208  1208
+    0x12 0x34                   # 20A  1234
+
+: sub_20C
+    v0 := 0x1                   # 20C  6001
+    return                      # 20E  00EE
+
+: data_210
+    0b00111100                  # 210  ..####..
+    0b01000010                  # 211  .#....#.
+    0b00111100                  # 212  ..####..
+    0xff                        # 213  FF
+";
+        assert_eq!(output(Format::Octo), expected);
+
+        // Octo starts at 0x200, so other origins need an `:org`.
+        let mut moved = Vec::new();
+        disassemble(&[0x00, 0xE0], 0x600).write(Format::Octo, &mut moved).expect("writes to a Vec");
+        assert!(moved.starts_with(b":org 0x600\n"));
+    }
+
+    #[test]
+    fn writes_json() {
+        let json = output(Format::Json);
+        let lines: Vec<&str> = json.lines().collect();
+        assert_eq!(lines[..3], ["{", "  \"origin\": 512,", "  \"labels\": {\"main\": 512, \"label_208\": 520, \"sub_20C\": 524, \"data_210\": 528},"]);
+        // Four lines of header, one per listing line and two to close.
+        assert_eq!(lines.len(), 4 + 12 + 2);
+        assert!(lines.contains(&"    {\"address\": 514, \"kind\": \"code\", \"bytes\": \"220C\", \"text\": \"CALL sub_20C\", \"target\": 524},"));
+        assert!(lines.contains(&"    {\"address\": 522, \"kind\": \"data\", \"bytes\": \"1234\", \"text\": \"db 0x12, 0x34\"},"));
+        assert!(lines.contains(&"    {\"address\": 528, \"kind\": \"sprite\", \"bytes\": \"3C\", \"text\": \"db 0x3C\", \"label\": \"data_210\"},"));
+        assert!(lines.contains(&"    {\"address\": 531, \"kind\": \"data\", \"bytes\": \"FF\", \"text\": \"db 0xFF\"}"));
+        assert_eq!(lines[lines.len() - 2..], ["  ]", "}"]);
+    }
+}
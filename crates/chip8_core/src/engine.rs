@@ -0,0 +1,619 @@
+use std::cell::RefCell;
+use std::fmt;
+use std::rc::{Rc, Weak};
+use std::str::FromStr;
+
+use crate::chip8::{decode, Chip8, Op, Threaded};
+use crate::error::Chip8Error;
+use crate::savestate;
+
+// Longest block compiled, in instructions.
+const MAX_BLOCK_LEN: usize = 64;
+// Size of the buckets blocks are filed under for invalidation, in bytes.
+const PAGE_SIZE: usize = 64;
+// Times a block can be dropped by writes before its start address is left
+// to the interpreter, since recompiling costs more than interpreting.
+const MAX_RECOMPILES: u8 = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum EngineKind {
+    // `Chip8::emulate_cycle`, one instruction at a time.
+    #[default]
+    Interpreter,
+    // Basic blocks decoded once and run back to back.
+    Blocks,
+    // Blocks, with every run repeated on a copy by the interpreter and the
+    // results compared.
+    Checked,
+}
+
+impl FromStr for EngineKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "interpreter" => Ok(EngineKind::Interpreter),
+            "blocks" => Ok(EngineKind::Blocks),
+            "check" | "checked" => Ok(EngineKind::Checked),
+            _ => Err(format!("unknown engine '{}'", s)),
+        }
+    }
+}
+
+impl fmt::Display for EngineKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EngineKind::Interpreter => write!(f, "interpreter"),
+            EngineKind::Blocks => write!(f, "blocks"),
+            EngineKind::Checked => write!(f, "check"),
+        }
+    }
+}
+
+// The block engine and the interpreter disagreed about a run.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Divergence {
+    // Where the run started.
+    pub pc: u16,
+    pub detail: String,
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "block engine diverged from the interpreter in a run from {:#X}: {}", self.pc, self.detail)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EngineError {
+    Fault(Chip8Error),
+    Divergence(Divergence),
+}
+
+impl From<Chip8Error> for EngineError {
+    fn from(e: Chip8Error) -> Self {
+        EngineError::Fault(e)
+    }
+}
+
+pub struct Engine {
+    kind: EngineKind,
+    blocks: BlockEngine,
+}
+
+impl Engine {
+    pub fn new(kind: EngineKind) -> Self {
+        Self { kind, blocks: BlockEngine::new() }
+    }
+
+    pub fn kind(&self) -> EngineKind {
+        self.kind
+    }
+
+    // Runs up to `budget` cycles, counting it down as they complete, and
+    // stops early after a cycle that leaves the machine exited. Cycles
+    // spent blocked count as they do for `emulate_cycle`.
+    pub fn run(&mut self, chip8: &mut Chip8, budget: &mut u32) -> Result<(), EngineError> {
+        match self.kind {
+            EngineKind::Interpreter => interpret(chip8, budget).map_err(EngineError::Fault),
+            EngineKind::Blocks => self.blocks.run(chip8, budget).map_err(EngineError::Fault),
+            EngineKind::Checked => self.run_checked(chip8, budget),
+        }
+    }
+
+    fn run_checked(&mut self, chip8: &mut Chip8, budget: &mut u32) -> Result<(), EngineError> {
+        let pc = chip8.pc;
+        let divergence = |detail: String| EngineError::Divergence(Divergence { pc, detail });
+
+        let mut reference = Chip8::with_memory_size(chip8.memory.len());
+        reference.error_policies = chip8.error_policies;
+        // Custom generators can only be copied, not rebuilt from their state.
+        reference.rng = chip8.rng.clone_box();
+        savestate::load(&mut reference, &savestate::save(chip8))
+            .map_err(|e| divergence(format!("cannot copy the machine: {}", e)))?;
+        let mut reference_budget = *budget;
+        let expected = interpret(&mut reference, &mut reference_budget);
+
+        let actual = self.blocks.run(chip8, budget);
+        if actual != expected {
+            return Err(divergence(format!("returned {:?}, expected {:?}", actual, expected)));
+        }
+        if *budget != reference_budget {
+            return Err(divergence(format!("left {} cycles, expected {}", budget, reference_budget)));
+        }
+        if let Some(detail) = difference(&reference, chip8) {
+            return Err(divergence(detail));
+        }
+        actual.map_err(EngineError::Fault)
+    }
+}
+
+fn interpret(chip8: &mut Chip8, budget: &mut u32) -> Result<(), Chip8Error> {
+    while *budget > 0 {
+        chip8.emulate_cycle()?;
+        *budget -= 1;
+        if chip8.exited {
+            break;
+        }
+    }
+    Ok(())
+}
+
+// The first way `actual` differs from `expected`, if any.
+fn difference(expected: &Chip8, actual: &Chip8) -> Option<String> {
+    let registers = [
+        ("PC", expected.pc, actual.pc),
+        ("I", expected.i, actual.i),
+        ("SP", expected.sp as u16, actual.sp as u16),
+        ("DT", expected.delay_timer as u16, actual.delay_timer as u16),
+        ("ST", expected.sound_timer as u16, actual.sound_timer as u16),
+    ];
+    for (name, expected, actual) in registers {
+        if expected != actual {
+            return Some(format!("{} is {:#X}, expected {:#X}", name, actual, expected));
+        }
+    }
+    if let Some(x) = (0..16).find(|&x| expected.v[x] != actual.v[x]) {
+        return Some(format!("V{:X} is {:#X}, expected {:#X}", x, actual.v[x], expected.v[x]));
+    }
+    if let Some(address) = (0..expected.memory.len()).find(|&a| expected.memory.get(a) != actual.memory.get(a)) {
+        return Some(format!("memory at {:#X} is {:#X}, expected {:#X}", address, actual.memory[address], expected.memory[address]));
+    }
+    if expected.gfx != actual.gfx || expected.hires != actual.hires {
+        return Some("the screen differs".to_string());
+    }
+    // Anything else: the stack, key waits, XO-CHIP state, the generator.
+    (savestate::save(expected) != savestate::save(actual)).then(|| "the machine state differs".to_string())
+}
+
+// Straight-line code compiled to one closure per instruction.
+struct Block {
+    steps: Vec<Step>,
+    // The start addresses of blocks run straight after this one, with the
+    // blocks themselves. A link goes dead when its target is dropped.
+    links: RefCell<Vec<(usize, Weak<Block>)>>,
+}
+
+struct Step {
+    run: Threaded,
+    // The address of the next step.
+    next: u16,
+    // Set for instructions the block may have to be left after; see
+    // `may_leave`.
+    may_leave: bool,
+}
+
+// Enough for the jump or return at the end and a side exit or two.
+const MAX_LINKS: usize = 4;
+
+impl Block {
+    fn successor(&self, start: usize) -> Option<Rc<Block>> {
+        self.links.borrow().iter().find(|&&(target, _)| target == start).and_then(|(_, block)| block.upgrade())
+    }
+
+    fn link(&self, start: usize, block: &Rc<Block>) {
+        let mut links = self.links.borrow_mut();
+        links.retain(|(target, link)| *target != start && link.strong_count() > 0);
+        if links.len() == MAX_LINKS {
+            links.remove(0);
+        }
+        links.push((start, Rc::downgrade(block)));
+    }
+}
+
+// Translates straight-line code into blocks of threaded code that end at the
+// first jump, call or return, and runs them without going back through fetch
+// and decode. A block is left early after a skip that was taken, a stall or a
+// write to memory. Each block links to the blocks that ran after it, so loops
+// and calls chain from block to block without a lookup. A write to memory
+// drops every block covering the bytes written, which kills the links into
+// them; since the block that wrote is left straight away, it never runs
+// stale code after the write.
+// Anything the blocks can't handle, such as stalls, fetches the PC error
+// policy would redirect and code that keeps being rewritten, goes through
+// `emulate_cycle`.
+pub struct BlockEngine {
+    // Indexed by start address.
+    blocks: Vec<Option<Rc<Block>>>,
+    // How often the block at each start address has been dropped.
+    drops: Vec<u8>,
+    // The start and end addresses of the blocks overlapping each page.
+    pages: Vec<Vec<(usize, usize)>>,
+    epoch: u64,
+}
+
+impl BlockEngine {
+    pub fn new() -> Self {
+        Self { blocks: Vec::new(), drops: Vec::new(), pages: Vec::new(), epoch: u64::MAX }
+    }
+
+    // Same contract as `Engine::run`.
+    pub fn run(&mut self, chip8: &mut Chip8, budget: &mut u32) -> Result<(), Chip8Error> {
+        if chip8.code_epoch != self.epoch || chip8.memory.len() != self.blocks.len() {
+            self.flush(chip8);
+        }
+
+        // The block that just ran, to follow or add its links.
+        let mut previous: Option<Rc<Block>> = None;
+        while *budget > 0 {
+            let pc = chip8.pc as usize;
+            chip8.written = None;
+            let mut result = Ok(());
+            let block = if chip8.blocked() || chip8.exited {
+                None
+            } else if let Some(block) = previous.as_ref().and_then(|block| block.successor(pc)) {
+                Some(block)
+            } else {
+                let block = self.compile(chip8, pc);
+                if let (Some(previous), Some(block)) = (&previous, &block) {
+                    previous.link(pc, block);
+                }
+                block
+            };
+            match &block {
+                Some(block) => {
+                    for step in block.steps.iter().take(*budget as usize) {
+                        result = (step.run)(chip8);
+                        if result.is_err() {
+                            break;
+                        }
+                        *budget -= 1;
+                        if step.may_leave
+                            && (chip8.pc != step.next || chip8.blocked() || chip8.exited || chip8.written.is_some())
+                        {
+                            break;
+                        }
+                    }
+                }
+                // Stay in the interpreter while the code keeps being rewritten.
+                None => loop {
+                    result = chip8.emulate_cycle();
+                    if result.is_err() {
+                        break;
+                    }
+                    *budget -= 1;
+                    if *budget == 0 || chip8.exited || !self.unstable(chip8.pc as usize) {
+                        break;
+                    }
+                },
+            }
+            previous = block;
+            if let Some((low, high)) = chip8.written.take() {
+                self.invalidate(low, high);
+                // It may have just dropped itself.
+                previous = None;
+            }
+            result?;
+            if chip8.exited {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    // True where no block starts and none will be compiled.
+    fn unstable(&self, start: usize) -> bool {
+        self.drops.get(start).is_some_and(|&drops| drops >= MAX_RECOMPILES) && self.blocks[start].is_none()
+    }
+
+    fn flush(&mut self, chip8: &Chip8) {
+        let len = chip8.memory.len();
+        self.blocks.clear();
+        self.blocks.resize_with(len, || None);
+        self.drops = vec![0; len];
+        self.pages = vec![Vec::new(); len.div_ceil(PAGE_SIZE)];
+        self.epoch = chip8.code_epoch;
+    }
+
+    // The block starting at `start`, compiling it if needed; `None` if
+    // there's no whole instruction there to start one with or the code there
+    // keeps changing.
+    fn compile(&mut self, chip8: &Chip8, start: usize) -> Option<Rc<Block>> {
+        if let Some(block) = self.blocks.get(start)? {
+            return Some(block.clone());
+        }
+        if self.drops[start] >= MAX_RECOMPILES {
+            return None;
+        }
+
+        let memory = &chip8.memory;
+        let word = |address: usize| (memory[address] as u16) << 8 | memory[address + 1] as u16;
+        let mut steps = Vec::new();
+        let mut address = start;
+        while steps.len() < MAX_BLOCK_LEN && address + 2 <= memory.len() {
+            let opcode = word(address);
+            let operand = match opcode {
+                0xF000 if address + 4 > memory.len() => break,
+                0xF000 => word(address + 2),
+                _ => 0,
+            };
+            let op = decode(opcode, operand);
+            let next = address + op.size();
+            steps.push(Step { run: op.threaded(address as u16), next: next as u16, may_leave: may_leave(&op) });
+            address = next;
+            if ends_block(&op) {
+                break;
+            }
+        }
+        if steps.is_empty() {
+            return None;
+        }
+
+        for page in &mut self.pages[start / PAGE_SIZE..=(address - 1) / PAGE_SIZE] {
+            page.push((start, address));
+        }
+        let block = Rc::new(Block { steps, links: RefCell::new(Vec::new()) });
+        self.blocks[start] = Some(block.clone());
+        Some(block)
+    }
+
+    // Drops the blocks covering any byte from `low` to `high`.
+    fn invalidate(&mut self, low: usize, high: usize) {
+        let mut stale = Vec::new();
+        for page in &self.pages[low / PAGE_SIZE..=high / PAGE_SIZE] {
+            stale.extend(page.iter().filter(|&&(start, end)| start <= high && low < end));
+        }
+        for (start, end) in stale {
+            self.blocks[start] = None;
+            self.drops[start] = self.drops[start].saturating_add(1);
+            for page in &mut self.pages[start / PAGE_SIZE..=(end - 1) / PAGE_SIZE] {
+                page.retain(|&(other, _)| other != start);
+            }
+        }
+    }
+}
+
+impl Default for BlockEngine {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn ends_block(op: &Op) -> bool {
+    matches!(op, Op::Jmp(_) | Op::Call(_) | Op::Ret(_) | Op::JmpV0Addr(_) | Op::Exit(_))
+}
+
+// Skips, which may not fall through; instructions that may stall the CPU;
+// and memory writes, which may have changed the code ahead. Faults leave the
+// block regardless.
+fn may_leave(op: &Op) -> bool {
+    matches!(
+        op,
+        Op::SeVxByte(_) | Op::SneVxByte(_) | Op::SeVxVy(_) | Op::SneVxVy(_) | Op::SkpVx(_) | Op::SknpVx(_)
+            | Op::DrwVxVyNibble(_) | Op::LdVxK(_)
+            | Op::LdBVx(_) | Op::LdIVx(_) | Op::SaveVxVy(_)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::random::CosmacVip;
+
+    // A machine with each run of opcodes stored from its address.
+    fn machine(code: &[(usize, &[u16])]) -> Chip8 {
+        let mut chip8 = Chip8::new();
+        for &(address, opcodes) in code {
+            for (n, opcode) in opcodes.iter().enumerate() {
+                chip8.memory[address + 2 * n..][..2].copy_from_slice(&opcode.to_be_bytes());
+            }
+        }
+        chip8.invalidate_code();
+        chip8
+    }
+
+    // Runs `code` for `cycles` cycles in slices of `budgets`, repeating the
+    // last, with the block engine and the interpreter side by side, and
+    // checks they agree after every slice.
+    fn run_both(code: &[(usize, &[u16])], budgets: &[u32], cycles: u32) -> Chip8 {
+        let mut chip8 = machine(code);
+        let mut reference = Chip8::new();
+        savestate::load(&mut reference, &savestate::save(&chip8)).expect("copies the machine");
+        let mut engine = BlockEngine::new();
+        let mut done = 0;
+        for n in 0.. {
+            if done >= cycles {
+                break;
+            }
+            let slice = budgets[n.min(budgets.len() - 1)];
+            let (mut budget, mut reference_budget) = (slice, slice);
+            let actual = engine.run(&mut chip8, &mut budget);
+            let expected = interpret(&mut reference, &mut reference_budget);
+            assert_eq!(actual, expected, "after {} cycles", done);
+            assert_eq!(budget, reference_budget, "after {} cycles", done);
+            if let Some(detail) = difference(&reference, &chip8) {
+                panic!("after {} cycles: {}", done, detail);
+            }
+            if actual.is_err() {
+                break;
+            }
+            done += slice;
+        }
+        chip8
+    }
+
+    // Stores over an instruction further on in the block that is running.
+    const STORE_AHEAD: &[(usize, &[u16])] = &[(
+        0x200,
+        &[
+            0x6060, // LD V0, 0x60
+            0x612A, // LD V1, 0x2A
+            0xA20A, // LD I, 0x20A
+            0xF155, // LD [I], V1
+            0x6301, // LD V3, 1
+            0x6001, // LD V0, 0x01, stored over with LD V0, 0x2A
+            0x120C, // JP 0x20C
+        ],
+    )];
+
+    // Stores BCD digits over a skip further on in the block, so that it skips
+    // what would fault if the old code ran.
+    const BCD_AHEAD: &[(usize, &[u16])] = &[(
+        0x200,
+        &[
+            0x6001, // LD V0, 1
+            0x627B, // LD V2, 123
+            0xA20D, // LD I, 0x20D
+            0xF233, // LD B, V2
+            0x6301, // LD V3, 1
+            0x6401, // LD V4, 1
+            0x30FF, // SE V0, 0xFF, stored over with SE V0, 1
+            0x1234, // JP 0x234, stored over with SYS 0x203
+            0x6507, // LD V5, 7
+            0x1212, // JP 0x212
+        ],
+    )];
+
+    // Calls a block that starts on one page and ends on the next, then
+    // stores over its second half and calls it again.
+    const ACROSS_PAGES: &[(usize, &[u16])] = &[
+        (
+            0x200,
+            &[
+                0x223A, // CALL 0x23A
+                0x3507, // SE V5, 7
+                0x1208, // JP 0x208
+                0x1206, // JP 0x206
+                0x6065, // LD V0, 0x65
+                0x6107, // LD V1, 0x07
+                0xA244, // LD I, 0x244
+                0xF155, // LD [I], V1
+                0x1200, // JP 0x200
+            ],
+        ),
+        (
+            0x23A,
+            &[
+                0x6201, // LD V2, 1
+                0x6301, // LD V3, 1
+                0x6401, // LD V4, 1
+                0x6601, // LD V6, 1
+                0x6701, // LD V7, 1
+                0x6501, // LD V5, 1, stored over with LD V5, 7
+                0x00EE, // RET
+            ],
+        ),
+    ];
+
+    // Stores its counter into one of its own instructions every time round.
+    const REWRITING_LOOP: &[(usize, &[u16])] = &[(
+        0x200,
+        &[
+            0xA20B, // LD I, 0x20B
+            0x7001, // ADD V0, 1
+            0xF055, // LD [I], V0
+            0x6300, // LD V3, 0
+            0x6300, // LD V3, 0
+            0x6100, // LD V1, V0 by way of the store above
+            0x1200, // JP 0x200
+        ],
+    )];
+
+    // Falls off the end of memory.
+    const END_OF_MEMORY: &[(usize, &[u16])] = &[
+        (0x200, &[0x1FFA]), // JP 0xFFA
+        (
+            0xFFA,
+            &[
+                0x6007, // LD V0, 7
+                0x6108, // LD V1, 8
+                0x6209, // LD V2, 9
+            ],
+        ),
+    ];
+
+    // A long LD I whose operand would run past the end of memory.
+    const LONG_LOAD_AT_END: &[(usize, &[u16])] = &[
+        (0x200, &[0x1FFC]), // JP 0xFFC
+        (
+            0xFFC,
+            &[
+                0x6007, // LD V0, 7
+                0xF000, // LD I, with no room for the address
+            ],
+        ),
+    ];
+
+    // A drawing, key-polling counting loop with skips taken both ways.
+    const COUNTING_LOOP: &[(usize, &[u16])] = &[(
+        0x200,
+        &[
+            0x7001, // ADD V0, 1
+            0x8100, // LD V1, V0
+            0x8126, // SHR V1
+            0x4100, // SNE V1, 0
+            0x7201, // ADD V2, 1
+            0xF029, // LD F, V0
+            0xD125, // DRW V1, V2, 5
+            0xE09E, // SKP V0
+            0x6301, // LD V3, 1
+            0x1200, // JP 0x200
+        ],
+    )];
+
+    #[test]
+    fn store_into_the_running_block() {
+        let chip8 = run_both(STORE_AHEAD, &[100], 100);
+        assert_eq!(chip8.v[0], 0x2A);
+        assert_eq!(chip8.pc, 0x20C);
+    }
+
+    #[test]
+    fn bcd_into_the_running_block() {
+        let chip8 = run_both(BCD_AHEAD, &[100], 100);
+        assert_eq!(chip8.memory[0x20D..0x210], [1, 2, 3]);
+        assert_eq!(chip8.v[5], 7);
+        assert_eq!(chip8.pc, 0x212);
+    }
+
+    #[test]
+    fn store_across_a_page_boundary() {
+        assert_eq!(0x23A / PAGE_SIZE + 1, 0x244 / PAGE_SIZE);
+        let chip8 = run_both(ACROSS_PAGES, &[100], 100);
+        assert_eq!(chip8.v[5], 7);
+        assert_eq!(chip8.pc, 0x206);
+    }
+
+    #[test]
+    fn code_that_keeps_rewriting_itself() {
+        let chip8 = run_both(REWRITING_LOOP, &[1000], 1000);
+        assert_eq!(chip8.v[1], chip8.v[0]);
+        assert!(chip8.v[0] > MAX_RECOMPILES, "the loop ran past the recompile limit");
+    }
+
+    #[test]
+    fn block_ending_at_the_end_of_memory() {
+        let mut chip8 = machine(END_OF_MEMORY);
+        let result = BlockEngine::new().run(&mut chip8, &mut 100);
+        assert_eq!(result, Err(Chip8Error::PcOutOfRange { pc: 0x1000 }));
+        assert_eq!(chip8.v[..3], [7, 8, 9]);
+        run_both(END_OF_MEMORY, &[100], 100);
+        run_both(LONG_LOAD_AT_END, &[100], 100);
+    }
+
+    // The interpreter's copy of the machine has to draw the same numbers.
+    #[test]
+    fn checked_run_with_a_vip_code_page() {
+        let mut chip8 = machine(&[(
+            0x200,
+            &[
+                0xC0FF, // RND V0, 0xFF
+                0xC1FF, // RND V1, 0xFF
+                0x1204, // JP 0x204
+            ],
+        )]);
+        chip8.rng = Box::new(CosmacVip::with_code_page(0x1234, std::array::from_fn(|n| n as u8)));
+        Engine::new(EngineKind::Checked).run(&mut chip8, &mut 10).expect("no divergence");
+        assert_eq!(chip8.v[..2], [0x47, 0x7D]);
+    }
+
+    #[test]
+    fn budgets_ending_inside_blocks() {
+        let programs = [STORE_AHEAD, BCD_AHEAD, ACROSS_PAGES, REWRITING_LOOP, END_OF_MEMORY, COUNTING_LOOP];
+        for program in programs {
+            for budgets in [&[1][..], &[2], &[3], &[5, 7, 1, 64, 2], &[63], &[65]] {
+                run_both(program, budgets, 500);
+            }
+        }
+    }
+}
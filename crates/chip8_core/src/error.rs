@@ -0,0 +1,148 @@
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Chip8Error {
+    UnknownOpcode { opcode: u16, address: u16 },
+    StackOverflow { address: u16 },
+    StackUnderflow { address: u16 },
+    MemoryOutOfBounds { target: usize, address: u16 },
+    PcOutOfRange { pc: u16 },
+    // The fonts at `base` would end at `end`, past the start of the program.
+    FontOutOfBounds { base: u16, end: usize },
+}
+
+impl fmt::Display for Chip8Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Chip8Error::UnknownOpcode { opcode, address } => {
+                write!(f, "unknown opcode {:04X} at {:#05X}", opcode, address)
+            }
+            Chip8Error::StackOverflow { address } => {
+                write!(f, "stack overflow at {:#05X}", address)
+            }
+            Chip8Error::StackUnderflow { address } => {
+                write!(f, "stack underflow at {:#05X}", address)
+            }
+            Chip8Error::MemoryOutOfBounds { target, address } => {
+                write!(f, "out-of-bounds memory access to {:#X} at {:#05X}", target, address)
+            }
+            Chip8Error::PcOutOfRange { pc } => {
+                write!(f, "program counter out of range: {:#X}", pc)
+            }
+            Chip8Error::FontOutOfBounds { base, end } => {
+                write!(f, "fonts at {:#05X} would end at {:#X}, past the start of the program", base, end)
+            }
+        }
+    }
+}
+
+impl std::error::Error for Chip8Error {}
+
+// What to do when a fault is hit. `Wrap` behaves like `Ignore` for
+// unknown opcodes, which have nothing to wrap.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ErrorPolicy {
+    #[default]
+    Halt,
+    Ignore,
+    Wrap,
+}
+
+impl FromStr for ErrorPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "halt" => Ok(ErrorPolicy::Halt),
+            "ignore" => Ok(ErrorPolicy::Ignore),
+            "wrap" => Ok(ErrorPolicy::Wrap),
+            _ => Err(format!("unknown error policy '{}'", s)),
+        }
+    }
+}
+
+impl fmt::Display for ErrorPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            ErrorPolicy::Halt => "halt",
+            ErrorPolicy::Ignore => "ignore",
+            ErrorPolicy::Wrap => "wrap",
+        };
+        f.write_str(name)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ErrorPolicies {
+    pub unknown_opcode: ErrorPolicy,
+    pub stack: ErrorPolicy,
+    pub memory: ErrorPolicy,
+    pub pc: ErrorPolicy,
+}
+
+impl ErrorPolicies {
+    pub fn all(policy: ErrorPolicy) -> Self {
+        Self {
+            unknown_opcode: policy,
+            stack: policy,
+            memory: policy,
+            pc: policy,
+        }
+    }
+
+    // Parses a comma-separated list where each item is either a policy for
+    // every fault kind (`wrap`) or for a single kind (`stack=ignore`).
+    pub fn parse_list(&mut self, list: &str) -> Result<(), String> {
+        for item in list.split(',') {
+            match item.split_once('=') {
+                None => *self = Self::all(item.parse()?),
+                Some((kind, policy)) => {
+                    let policy = policy.parse()?;
+                    match kind.to_ascii_lowercase().as_str() {
+                        "opcode" => self.unknown_opcode = policy,
+                        "stack" => self.stack = policy,
+                        "memory" => self.memory = policy,
+                        "pc" => self.pc = policy,
+                        _ => return Err(format!("unknown fault kind '{}'", kind)),
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+// Formats the policies as a list `parse_list` accepts.
+impl fmt::Display for ErrorPolicies {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "opcode={},stack={},memory={},pc={}", self.unknown_opcode, self.stack, self.memory, self.pc)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_list() {
+        let mut policies = ErrorPolicies::default();
+        policies.parse_list("wrap,stack=ignore,PC=halt").expect("valid list");
+        assert_eq!(
+            policies,
+            ErrorPolicies {
+                unknown_opcode: ErrorPolicy::Wrap,
+                stack: ErrorPolicy::Ignore,
+                memory: ErrorPolicy::Wrap,
+                pc: ErrorPolicy::Halt,
+            }
+        );
+
+        let mut parsed = ErrorPolicies::default();
+        parsed.parse_list(&policies.to_string()).expect("Display output parses");
+        assert_eq!(parsed, policies);
+
+        assert!(policies.parse_list("stop").is_err());
+        assert!(policies.parse_list("disk=halt").is_err());
+    }
+}
@@ -0,0 +1,286 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::audio::AudioSink;
+use crate::chip8::Chip8;
+use crate::debugger::Condition;
+use crate::engine::{Divergence, Engine, EngineError, EngineKind};
+use crate::error::Chip8Error;
+use crate::movie::{Desync, Movie, Player, Recorder};
+use crate::scheduler::Scheduler;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyEvent {
+    pub frame: u64,
+    pub key: u8,
+    pub pressed: bool,
+}
+
+// How long a key given without `+` or `-` is held, in frames.
+pub const TAP_FRAMES: u64 = 5;
+
+// Parses a key script: events separated by commas or whitespace, each
+// `FRAME:+KEY` to press a key, `FRAME:-KEY` to release it, or `FRAME:KEY` to
+// tap it for `TAP_FRAMES` frames. Keys are hex digits, e.g. `30:5,90:+A,120:-A`.
+pub fn parse_key_script(script: &str) -> Result<Vec<KeyEvent>, String> {
+    let mut events = Vec::new();
+    for item in script.split(|c: char| c == ',' || c.is_whitespace()).filter(|item| !item.is_empty()) {
+        let (frame, key) = item.split_once(':').ok_or_else(|| format!("expected FRAME:KEY, got '{}'", item))?;
+        let frame: u64 = frame.parse().map_err(|_| format!("invalid frame '{}' in '{}'", frame, item))?;
+        let (pressed, digit) = match key.as_bytes().first() {
+            Some(b'+') => (Some(true), &key[1..]),
+            Some(b'-') => (Some(false), &key[1..]),
+            _ => (None, key),
+        };
+        let key = match u8::from_str_radix(digit, 16) {
+            Ok(key) if key < 16 => key,
+            _ => return Err(format!("invalid key '{}' in '{}'", digit, item)),
+        };
+        match pressed {
+            Some(pressed) => events.push(KeyEvent { frame, key, pressed }),
+            None => {
+                events.push(KeyEvent { frame, key, pressed: true });
+                events.push(KeyEvent { frame: frame + TAP_FRAMES, key, pressed: false });
+            }
+        }
+    }
+    events.sort_by_key(|event| event.frame);
+    Ok(events)
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct RunConfig {
+    pub instructions_per_second: u32,
+    // The run stops at whichever limit is reached first.
+    pub frames: Option<u64>,
+    pub cycles: Option<u64>,
+    // Checked after every instruction.
+    pub until: Option<Condition>,
+    pub keys: Vec<KeyEvent>,
+    // Records the run into `Report::movie`.
+    pub record: bool,
+    // Drives the run from a movie instead of `keys`, at the movie's speed,
+    // stopping at its end or on a desync.
+    pub replay: Option<Movie>,
+    pub engine: EngineKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    FrameLimit,
+    CycleLimit,
+    Condition,
+    Exited,
+    MovieEnd,
+    Desync(Desync),
+    Divergence(Divergence),
+    Error(Chip8Error),
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Outcome::FrameLimit => write!(f, "frame limit reached"),
+            Outcome::CycleLimit => write!(f, "cycle limit reached"),
+            Outcome::Condition => write!(f, "condition met"),
+            Outcome::Exited => write!(f, "program exited"),
+            Outcome::MovieEnd => write!(f, "end of movie reached"),
+            Outcome::Desync(desync) => write!(f, "{}", desync),
+            Outcome::Divergence(divergence) => write!(f, "{}", divergence),
+            Outcome::Error(e) => write!(f, "error: {}", e),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Report {
+    pub outcome: Outcome,
+    pub frames: u64,
+    pub cycles: u64,
+    pub movie: Option<Movie>,
+}
+
+// Runs a machine with no display or timing, frame by frame as the windowed
+// frontend would, until one of the limits in `config` is reached. Key events
+// are applied at the start of their frame. Errors from `audio` end the run,
+// as does a movie that can't be replayed on `chip8`.
+pub fn run(chip8: &mut Chip8, config: &RunConfig, audio: &mut dyn AudioSink) -> Result<Report, String> {
+    let mut player = match &config.replay {
+        Some(movie) => Some(Player::start(movie.clone(), chip8)?),
+        None => None,
+    };
+    let instructions_per_second = match &player {
+        Some(player) => player.movie().instructions_per_second,
+        None => config.instructions_per_second,
+    };
+    let mut recorder = config.record.then(|| Recorder::start(chip8, instructions_per_second));
+    let mut scheduler = Scheduler::new(instructions_per_second);
+    let mut engine = Engine::new(config.engine);
+    let mut keys = config.keys.iter().peekable();
+    let mut frames = 0;
+    let mut cycles = 0;
+
+    let outcome = 'running: loop {
+        if config.frames.is_some_and(|limit| frames >= limit) {
+            break Outcome::FrameLimit;
+        }
+        match &mut player {
+            Some(player) if player.finished() => break Outcome::MovieEnd,
+            Some(player) => player.begin_frame(chip8),
+            None => {
+                while let Some(event) = keys.next_if(|event| event.frame <= frames) {
+                    chip8.set_key(event.key as usize, event.pressed);
+                }
+            }
+        }
+
+        let mut budget = scheduler.cycles_for_frame();
+        while budget > 0 {
+            let mut run = budget;
+            if let Some(limit) = config.cycles {
+                if cycles >= limit {
+                    break 'running Outcome::CycleLimit;
+                }
+                run = run.min((limit - cycles).try_into().unwrap_or(u32::MAX));
+            }
+            // Conditions are checked after every instruction.
+            if config.until.is_some() {
+                run = 1;
+            }
+            let mut left = run;
+            let result = engine.run(chip8, &mut left);
+            cycles += (run - left) as u64;
+            budget -= run - left;
+            match result {
+                Err(EngineError::Fault(e)) => break 'running Outcome::Error(e),
+                Err(EngineError::Divergence(divergence)) => break 'running Outcome::Divergence(divergence),
+                Ok(()) => {}
+            }
+            if chip8.exited {
+                break 'running Outcome::Exited;
+            }
+            if config.until.is_some_and(|condition| condition.holds(chip8)) {
+                break 'running Outcome::Condition;
+            }
+        }
+        chip8.tick();
+        audio.frame(chip8)?;
+        frames += 1;
+        if let Some(recorder) = &mut recorder {
+            recorder.frame(chip8);
+        }
+        if let Some(Err(desync)) = player.as_mut().map(|player| player.end_frame(chip8)) {
+            break Outcome::Desync(desync);
+        }
+    };
+
+    let movie = recorder.map(Recorder::finish);
+    Ok(Report { outcome, frames, cycles, movie })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ScreenFormat {
+    #[default]
+    Ascii,
+    Pbm,
+    Png,
+    Hash,
+}
+
+impl FromStr for ScreenFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "ascii" | "text" => Ok(ScreenFormat::Ascii),
+            "pbm" => Ok(ScreenFormat::Pbm),
+            "png" => Ok(ScreenFormat::Png),
+            "hash" => Ok(ScreenFormat::Hash),
+            _ => Err(format!("unknown screen format '{}'", s)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::NullSink;
+    use crate::image;
+
+    fn machine(rom: &[u8]) -> Chip8 {
+        let mut chip8 = Chip8::new();
+        chip8.load_rom(rom).expect("ROM loads");
+        chip8
+    }
+
+    fn run_for(chip8: &mut Chip8, config: RunConfig) -> Report {
+        let config = RunConfig { instructions_per_second: 600, ..config };
+        run(chip8, &config, &mut NullSink).expect("runs")
+    }
+
+    // Draws the font's 0 in the corner, then counts V1 up forever.
+    const DRAW_ZERO: [u8; 8] = [
+        0xF0, 0x29, // LD F, V0
+        0xD0, 0x05, // DRW V0, V0, 5
+        0x71, 0x01, // ADD V1, 1
+        0x12, 0x04, // JP 0x204
+    ];
+
+    #[test]
+    fn runs_for_a_number_of_frames() {
+        let mut chip8 = machine(&DRAW_ZERO);
+        let report = run_for(&mut chip8, RunConfig { frames: Some(10), ..RunConfig::default() });
+        assert_eq!(report, Report { outcome: Outcome::FrameLimit, frames: 10, cycles: 100, movie: None });
+
+        let screen = image::ascii(&chip8);
+        let rows: Vec<&str> = screen.lines().collect();
+        assert_eq!(rows.len(), 32);
+        let zero = ["####", "#..#", "#..#", "#..#", "####"];
+        for (row, expected) in rows.iter().zip(zero) {
+            assert_eq!(&row[..4], expected);
+            assert!(row[4..].chars().all(|pixel| pixel == '.'));
+        }
+        assert!(rows[5..].iter().all(|row| row.chars().all(|pixel| pixel == '.')));
+    }
+
+    #[test]
+    fn stops_at_the_first_limit() {
+        let mut chip8 = machine(&DRAW_ZERO);
+        let report = run_for(&mut chip8, RunConfig { frames: Some(10), cycles: Some(25), ..RunConfig::default() });
+        assert_eq!((report.outcome, report.frames, report.cycles), (Outcome::CycleLimit, 2, 25));
+
+        let mut chip8 = machine(&DRAW_ZERO);
+        let until = "V1 == 0x20".parse().expect("condition parses");
+        let report = run_for(&mut chip8, RunConfig { frames: Some(10), until: Some(until), ..RunConfig::default() });
+        // Stopped straight after the ADD that made it true.
+        assert_eq!(report.outcome, Outcome::Condition);
+        assert_eq!((chip8.v[1], chip8.pc), (0x20, 0x206));
+    }
+
+    #[test]
+    fn reports_exits_and_errors() {
+        // Waits for key 7, then exits.
+        let mut chip8 = machine(&[0xF0, 0x0A, 0x00, 0xFD]);
+        let keys = parse_key_script("3:7").expect("valid key script");
+        let report = run_for(&mut chip8, RunConfig { frames: Some(10), keys, ..RunConfig::default() });
+        assert_eq!(report.outcome, Outcome::Exited);
+        assert_eq!(chip8.v[0], 7);
+
+        let mut chip8 = machine(&[0x00, 0xE0, 0xFF, 0xFF]);
+        let report = run_for(&mut chip8, RunConfig { frames: Some(10), ..RunConfig::default() });
+        assert_eq!(report.outcome, Outcome::Error(Chip8Error::UnknownOpcode { opcode: 0xFFFF, address: 0x202 }));
+        assert_eq!((report.frames, report.cycles), (0, 1));
+    }
+
+    #[test]
+    fn parses_key_scripts() {
+        let events = parse_key_script("30:5, 90:+A 120:-a").expect("valid key script");
+        let expected = [(30, 5, true), (35, 5, false), (90, 0xA, true), (120, 0xA, false)];
+        let events: Vec<(u64, u8, bool)> = events.iter().map(|event| (event.frame, event.key, event.pressed)).collect();
+        assert_eq!(events, expected);
+
+        assert_eq!(parse_key_script("30"), Err("expected FRAME:KEY, got '30'".to_string()));
+        assert_eq!(parse_key_script("x:5"), Err("invalid frame 'x' in 'x:5'".to_string()));
+        assert_eq!(parse_key_script("30:+G"), Err("invalid key 'G' in '30:+G'".to_string()));
+    }
+}
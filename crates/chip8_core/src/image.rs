@@ -0,0 +1,148 @@
+use std::io::{self, Write};
+
+use crate::chip8::Chip8;
+
+// Indexed by the bitplanes lit in a pixel: plane 1 alone is white, plane 2
+// alone is the first XO-CHIP accent colour, both together the second, and so on.
+pub const PALETTE: [(u8, u8, u8); 16] = [
+    (0x00, 0x00, 0x00),
+    (0xFF, 0xFF, 0xFF),
+    (0xAA, 0xAA, 0xAA),
+    (0x55, 0x55, 0x55),
+    (0xFF, 0x00, 0x00),
+    (0x00, 0xFF, 0x00),
+    (0x00, 0x00, 0xFF),
+    (0xFF, 0xFF, 0x00),
+    (0x88, 0x00, 0x00),
+    (0x00, 0x88, 0x00),
+    (0x00, 0x00, 0x88),
+    (0x88, 0x88, 0x00),
+    (0xFF, 0x00, 0xFF),
+    (0x00, 0xFF, 0xFF),
+    (0x88, 0x00, 0x88),
+    (0x00, 0x88, 0x88),
+];
+
+// One character per pixel: `.` when dark, `#` for the first plane alone and
+// the plane mask as a hex digit for other XO-CHIP colours.
+pub fn ascii(chip8: &Chip8) -> String {
+    let mut text = String::new();
+    for row in chip8.get_graphics().chunks(chip8.width()) {
+        text.extend(row.iter().map(|&pixel| match pixel {
+            0 => '.',
+            1 => '#',
+            _ => char::from_digit(pixel as u32 & 0xF, 16).unwrap_or('?'),
+        }));
+        text.push('\n');
+    }
+    text
+}
+
+// FNV-1a over the screen size and pixels; stable across runs and versions,
+// for comparing against a known-good value.
+pub fn hash(chip8: &Chip8) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    let size = [chip8.width() as u8, chip8.height() as u8];
+    for &byte in size.iter().chain(chip8.get_graphics()) {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+// Binary PBM with lit pixels black, each drawn as a `scale` x `scale` block.
+pub fn write_pbm<W: Write>(chip8: &Chip8, scale: usize, output: &mut W) -> io::Result<()> {
+    let (width, height) = (chip8.width() * scale, chip8.height() * scale);
+    write!(output, "P4\n{} {}\n", width, height)?;
+    for row in scaled_rows(chip8, scale) {
+        let mut packed = vec![0u8; width.div_ceil(8)];
+        for (x, &pixel) in row.iter().enumerate() {
+            if pixel != 0 {
+                packed[x / 8] |= 0x80 >> (x % 8);
+            }
+        }
+        output.write_all(&packed)?;
+    }
+    Ok(())
+}
+
+// Indexed-colour PNG using `PALETTE`. The image data is stored uncompressed,
+// which keeps the encoder small; CHIP-8 screens are tiny anyway.
+pub fn write_png<W: Write>(chip8: &Chip8, scale: usize, output: &mut W) -> io::Result<()> {
+    let (width, height) = (chip8.width() * scale, chip8.height() * scale);
+
+    let mut header = Vec::new();
+    header.extend_from_slice(&(width as u32).to_be_bytes());
+    header.extend_from_slice(&(height as u32).to_be_bytes());
+    // 8 bits per pixel, indexed colour, default compression, filter and interlacing.
+    header.extend_from_slice(&[8, 3, 0, 0, 0]);
+
+    let palette: Vec<u8> = PALETTE.iter().flat_map(|&(r, g, b)| [r, g, b]).collect();
+
+    let mut raw = Vec::with_capacity((width + 1) * height);
+    for row in scaled_rows(chip8, scale) {
+        // Filter type 0 (none) for every scanline.
+        raw.push(0);
+        raw.extend(row.iter().map(|&pixel| pixel & 0xF));
+    }
+
+    output.write_all(b"\x89PNG\r\n\x1a\n")?;
+    write_png_chunk(output, b"IHDR", &header)?;
+    write_png_chunk(output, b"PLTE", &palette)?;
+    write_png_chunk(output, b"IDAT", &zlib_stored(&raw))?;
+    write_png_chunk(output, b"IEND", &[])
+}
+
+fn scaled_rows(chip8: &Chip8, scale: usize) -> impl Iterator<Item = Vec<u8>> + '_ {
+    chip8.get_graphics().chunks(chip8.width()).flat_map(move |row| {
+        let scaled: Vec<u8> = row.iter().flat_map(|&pixel| std::iter::repeat_n(pixel, scale)).collect();
+        std::iter::repeat_n(scaled, scale)
+    })
+}
+
+fn write_png_chunk<W: Write>(output: &mut W, tag: &[u8; 4], data: &[u8]) -> io::Result<()> {
+    output.write_all(&(data.len() as u32).to_be_bytes())?;
+    output.write_all(tag)?;
+    output.write_all(data)?;
+    let crc = crc32(tag.iter().chain(data));
+    output.write_all(&crc.to_be_bytes())
+}
+
+// A zlib stream of uncompressed deflate blocks.
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    let mut output = vec![0x78, 0x01];
+    let mut blocks = data.chunks(0xFFFF).peekable();
+    if blocks.peek().is_none() {
+        output.extend_from_slice(&[1, 0, 0, 0xFF, 0xFF]);
+    }
+    while let Some(block) = blocks.next() {
+        let last = blocks.peek().is_none();
+        let len = block.len() as u16;
+        output.push(last as u8);
+        output.extend_from_slice(&len.to_le_bytes());
+        output.extend_from_slice(&(!len).to_le_bytes());
+        output.extend_from_slice(block);
+    }
+    output.extend_from_slice(&adler32(data).to_be_bytes());
+    output
+}
+
+fn crc32<'a>(bytes: impl Iterator<Item = &'a u8>) -> u32 {
+    let mut crc = 0xFFFFFFFFu32;
+    for &byte in bytes {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { 0xEDB88320 ^ (crc >> 1) } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for &byte in data {
+        a = (a + byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    b << 16 | a
+}
@@ -0,0 +1,18 @@
+pub mod asm;
+pub mod audio;
+pub mod chip8;
+pub mod debugger;
+pub mod disasm;
+pub mod engine;
+pub mod error;
+pub mod font;
+pub mod headless;
+pub mod image;
+pub mod movie;
+pub mod quirks;
+pub mod random;
+pub mod rewind;
+pub mod savestate;
+pub mod scheduler;
+
+pub use chip8::{decode, Chip8, Instruction, Op};
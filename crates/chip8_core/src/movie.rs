@@ -0,0 +1,529 @@
+use std::error::Error;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use crate::chip8::{Chip8, MEMORY_SIZE, PROGRAM_START, XO_CHIP_MEMORY_SIZE};
+use crate::error::ErrorPolicies;
+use crate::font::{FontStyle, BIG_FONT_SIZE, FONT_SIZE};
+use crate::quirks::Quirks;
+use crate::random::RandomKind;
+use crate::savestate;
+
+// A movie is a text file, so that it can be attached to a bug report or
+// edited by hand:
+//
+//   chip8-movie 1
+//   rom 5f1b9a3e27c0d684       FNV-1a hash of the ROM
+//   memory 4096
+//   quirks 2f                  save state quirk bits
+//   font 0 0x050               save state font style id, and font base
+//   errors opcode=halt,...     error policies, as given to --on-error
+//   rng xorshift 8c1f...       generator and its power-on state (the seed), hex
+//   ips 700
+//   frames 1800
+//   keys 0 0000                keypad from this frame on, bit N for key N
+//   reset 300                  the machine is reset before this frame
+//   hash 60 0123456789abcdef   state hash after this many frames
+//
+// Event lines follow the header in frame order. Blank lines and text after
+// `#` are ignored.
+const MAGIC: &str = "chip8-movie";
+pub const VERSION: u32 = 1;
+
+// Frames between state hashes.
+pub const HASH_INTERVAL: u64 = 60;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Event {
+    Keys(u16),
+    Reset,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Movie {
+    pub rom_hash: u64,
+    pub memory_size: usize,
+    pub quirks: Quirks,
+    pub font_style: FontStyle,
+    pub font_base: u16,
+    pub error_policies: ErrorPolicies,
+    pub rng: String,
+    pub rng_state: Vec<u8>,
+    pub instructions_per_second: u32,
+    pub frames: u64,
+    // Sorted by frame; a reset comes before a keypad change on the same frame.
+    pub events: Vec<(u64, Event)>,
+    pub hashes: Vec<(u64, u64)>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Desync {
+    pub frame: u64,
+    pub expected: u64,
+    pub actual: u64,
+}
+
+impl fmt::Display for Desync {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "replay desynced at frame {}: state hash {:016x}, expected {:016x}", self.frame, self.actual, self.expected)
+    }
+}
+
+impl Error for Desync {}
+
+fn fnv1a(data: &[u8]) -> u64 {
+    data.iter().fold(0xcbf29ce484222325, |hash, &byte| (hash ^ byte as u64).wrapping_mul(0x100000001b3))
+}
+
+// Hash of everything a save state holds, so any divergence shows up.
+pub fn state_hash(chip8: &Chip8) -> u64 {
+    fnv1a(&savestate::save(chip8))
+}
+
+fn keypad_bits(chip8: &Chip8) -> u16 {
+    chip8.keypad.iter().enumerate().fold(0, |bits, (key, &state)| bits | ((state != 0) as u16) << key)
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn parse_hex_bytes(s: &str) -> Option<Vec<u8>> {
+    if !s.len().is_multiple_of(2) || !s.is_ascii() {
+        return None;
+    }
+    (0..s.len()).step_by(2).map(|i| u8::from_str_radix(&s[i..i + 2], 16).ok()).collect()
+}
+
+impl Movie {
+    pub fn write<W: Write>(&self, output: &mut W) -> io::Result<()> {
+        writeln!(output, "{} {}", MAGIC, VERSION)?;
+        writeln!(output, "rom {:016x}", self.rom_hash)?;
+        writeln!(output, "memory {}", self.memory_size)?;
+        writeln!(output, "quirks {:x}", savestate::quirk_bits(&self.quirks))?;
+        writeln!(output, "font {} {:#05x}", savestate::font_style_id(self.font_style), self.font_base)?;
+        writeln!(output, "errors {}", self.error_policies)?;
+        writeln!(output, "rng {} {}", self.rng, hex(&self.rng_state))?;
+        writeln!(output, "ips {}", self.instructions_per_second)?;
+        writeln!(output, "frames {}", self.frames)?;
+
+        // Interleave events and hashes by frame. A hash after N frames goes
+        // before the events of frame N, which come after it.
+        let mut hashes = self.hashes.iter().peekable();
+        for (frame, event) in &self.events {
+            while let Some((after, hash)) = hashes.next_if(|(after, _)| after <= frame) {
+                writeln!(output, "hash {} {:016x}", after, hash)?;
+            }
+            match event {
+                Event::Keys(keys) => writeln!(output, "keys {} {:04x}", frame, keys)?,
+                Event::Reset => writeln!(output, "reset {}", frame)?,
+            }
+        }
+        for (after, hash) in hashes {
+            writeln!(output, "hash {} {:016x}", after, hash)?;
+        }
+        Ok(())
+    }
+
+    pub fn read_file<P: AsRef<Path>>(path: P) -> Result<Movie, String> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path)
+            .map_err(|e| format!("cannot read movie '{}': {}", path.display(), e))?;
+        Movie::parse(&text).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    pub fn write_file<P: AsRef<Path>>(&self, path: P) -> Result<(), String> {
+        let path = path.as_ref();
+        File::create(path)
+            .map(BufWriter::new)
+            .and_then(|mut file| {
+                self.write(&mut file)?;
+                file.flush()
+            })
+            .map_err(|e| format!("cannot write movie '{}': {}", path.display(), e))
+    }
+
+    pub fn parse(text: &str) -> Result<Movie, String> {
+        let mut lines = text
+            .lines()
+            .enumerate()
+            .map(|(index, line)| (index + 1, line.split('#').next().unwrap_or("").trim()))
+            .filter(|(_, line)| !line.is_empty());
+
+        match lines.next().map(|(_, line)| line.split_whitespace().collect::<Vec<_>>()) {
+            Some(fields) if fields.first() == Some(&MAGIC) => {
+                let version: u32 = fields.get(1).and_then(|v| v.parse().ok()).ok_or("missing movie version")?;
+                if version > VERSION {
+                    return Err(format!("movie version {} is newer than the supported version {}", version, VERSION));
+                }
+            }
+            _ => return Err("not a movie file".to_string()),
+        }
+
+        let mut rom_hash = None;
+        let mut memory_size = None;
+        let mut quirks = None;
+        let mut font = None;
+        let mut error_policies = None;
+        let mut rng = None;
+        let mut instructions_per_second = None;
+        let mut frames = None;
+        let mut events = Vec::new();
+        let mut hashes = Vec::new();
+        let mut last_frame = 0;
+
+        for (number, line) in lines {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let error = |message: &str| format!("line {}: {}", number, message);
+            let field = |index: usize| fields.get(index).copied().ok_or_else(|| error("missing field"));
+            let hex_field = |index: usize| field(index).and_then(|f| u64::from_str_radix(f, 16).map_err(|_| error("invalid hex number")));
+            let number_field = |index: usize| field(index).and_then(|f| f.parse::<u64>().map_err(|_| error("invalid number")));
+
+            let mut event_frame = |frame: u64| {
+                if frame < last_frame {
+                    return Err(error("events are out of order"));
+                }
+                last_frame = frame;
+                Ok(frame)
+            };
+
+            match fields[0] {
+                "rom" => rom_hash = Some(hex_field(1)?),
+                "memory" => {
+                    let size = number_field(1)? as usize;
+                    if !(MEMORY_SIZE..=XO_CHIP_MEMORY_SIZE).contains(&size) {
+                        return Err(error("memory size out of range"));
+                    }
+                    memory_size = Some(size);
+                }
+                "quirks" => quirks = Some(savestate::quirks_from_bits(hex_field(1)? as u32)),
+                "font" => {
+                    let style = savestate::font_style_from_id(number_field(1)? as u8).map_err(|e| error(&e.to_string()))?;
+                    let base = field(2)?;
+                    let base = u16::from_str_radix(base.trim_start_matches("0x"), 16).map_err(|_| error("invalid font base"))?;
+                    if base as usize + FONT_SIZE + BIG_FONT_SIZE > PROGRAM_START {
+                        return Err(error("font base out of range"));
+                    }
+                    font = Some((style, base));
+                }
+                "errors" => {
+                    let mut policies = ErrorPolicies::default();
+                    policies.parse_list(field(1)?).map_err(|e| error(&e))?;
+                    error_policies = Some(policies);
+                }
+                "rng" => {
+                    let state = parse_hex_bytes(field(2)?).ok_or_else(|| error("invalid generator state"))?;
+                    rng = Some((field(1)?.to_string(), state));
+                }
+                "ips" => instructions_per_second = Some(number_field(1)? as u32),
+                "frames" => frames = Some(number_field(1)?),
+                "keys" => {
+                    let frame = event_frame(number_field(1)?)?;
+                    events.push((frame, Event::Keys(hex_field(2)? as u16)));
+                }
+                "reset" => {
+                    let frame = event_frame(number_field(1)?)?;
+                    events.push((frame, Event::Reset));
+                }
+                "hash" => hashes.push((number_field(1)?, hex_field(2)?)),
+                other => return Err(error(&format!("unknown entry '{}'", other))),
+            }
+        }
+
+        let missing = |name: &str| format!("movie is missing its {} line", name);
+        let (font_style, font_base) = font.ok_or_else(|| missing("font"))?;
+        let (rng, rng_state) = rng.ok_or_else(|| missing("rng"))?;
+        Ok(Movie {
+            rom_hash: rom_hash.ok_or_else(|| missing("rom"))?,
+            memory_size: memory_size.ok_or_else(|| missing("memory"))?,
+            quirks: quirks.ok_or_else(|| missing("quirks"))?,
+            font_style,
+            font_base,
+            error_policies: error_policies.ok_or_else(|| missing("errors"))?,
+            rng,
+            rng_state,
+            instructions_per_second: instructions_per_second.ok_or_else(|| missing("ips"))?,
+            frames: frames.ok_or_else(|| missing("frames"))?,
+            events,
+            hashes,
+        })
+    }
+}
+
+// Records a session frame by frame. Frontends call `frame` after each whole
+// frame they emulate and `reset` when they reset the machine; anything else
+// that changes the machine behind the movie's back (loading a state,
+// rewinding, stepping in the debugger) should end the recording instead.
+pub struct Recorder {
+    movie: Movie,
+    keys: Option<u16>,
+}
+
+impl Recorder {
+    // Resets `chip8` so the movie starts from power-on.
+    pub fn start(chip8: &mut Chip8, instructions_per_second: u32) -> Recorder {
+        chip8.reset();
+        let movie = Movie {
+            rom_hash: fnv1a(&chip8.rom),
+            memory_size: chip8.memory.len(),
+            quirks: chip8.quirks,
+            font_style: chip8.font_style,
+            font_base: chip8.font_base,
+            error_policies: chip8.error_policies,
+            rng: chip8.rng.name().to_string(),
+            rng_state: chip8.rng.state(),
+            instructions_per_second,
+            frames: 0,
+            events: Vec::new(),
+            hashes: Vec::new(),
+        };
+        Recorder { movie, keys: None }
+    }
+
+    pub fn frames(&self) -> u64 {
+        self.movie.frames
+    }
+
+    pub fn reset(&mut self) {
+        self.movie.events.push((self.movie.frames, Event::Reset));
+        // The reset clears the keypad, so the next frame's keys are always
+        // recorded, even if they match the ones before it.
+        self.keys = None;
+    }
+
+    // The keypad is sampled after the frame; frontends only change it
+    // between frames, so it is what the frame ran with.
+    pub fn frame(&mut self, chip8: &Chip8) {
+        let keys = keypad_bits(chip8);
+        if self.keys != Some(keys) {
+            self.movie.events.push((self.movie.frames, Event::Keys(keys)));
+            self.keys = Some(keys);
+        }
+        self.movie.frames += 1;
+        if self.movie.frames.is_multiple_of(HASH_INTERVAL) {
+            self.movie.hashes.push((self.movie.frames, state_hash(chip8)));
+        }
+    }
+
+    pub fn finish(self) -> Movie {
+        self.movie
+    }
+}
+
+// Replays a movie. Frontends call `begin_frame` before each frame, run the
+// frame with the movie's instructions per second, and call `end_frame`.
+pub struct Player {
+    movie: Movie,
+    frame: u64,
+    next_event: usize,
+    next_hash: usize,
+}
+
+impl Player {
+    // Rebuilds `chip8` with the movie's configuration and the ROM it has
+    // loaded, which must be the one the movie was recorded with. The memory
+    // access log setting is kept; on error, `chip8` is left untouched.
+    pub fn start(movie: Movie, chip8: &mut Chip8) -> Result<Player, String> {
+        if fnv1a(&chip8.rom) != movie.rom_hash {
+            return Err("the movie was recorded with a different ROM".to_string());
+        }
+
+        let mut fresh = Chip8::with_memory_size(movie.memory_size);
+        fresh.set_font(movie.font_style, movie.font_base).map_err(|e| format!("cannot install the movie's font: {}", e))?;
+        fresh.quirks = movie.quirks;
+        fresh.error_policies = movie.error_policies;
+        fresh.log_memory_access = chip8.log_memory_access;
+        fresh.rng = match RandomKind::from_name(&movie.rng) {
+            _ if movie.rng == chip8.rng.name() => chip8.rng.clone_box(),
+            Some(kind) => kind.create(0),
+            None => return Err(format!("unknown random generator '{}'", movie.rng)),
+        };
+        fresh.rng.restore(&movie.rng_state)?;
+        fresh.load_rom(&chip8.rom).map_err(|e| e.to_string())?;
+
+        *chip8 = fresh;
+        Ok(Player { movie, frame: 0, next_event: 0, next_hash: 0 })
+    }
+
+    pub fn movie(&self) -> &Movie {
+        &self.movie
+    }
+
+    pub fn frame(&self) -> u64 {
+        self.frame
+    }
+
+    pub fn finished(&self) -> bool {
+        self.frame >= self.movie.frames
+    }
+
+    pub fn begin_frame(&mut self, chip8: &mut Chip8) {
+        while let Some((frame, event)) = self.movie.events.get(self.next_event) {
+            if *frame > self.frame {
+                break;
+            }
+            match event {
+                Event::Reset => chip8.reset(),
+                Event::Keys(keys) => {
+                    for key in 0..16 {
+                        chip8.set_key(key, keys & (1 << key) != 0);
+                    }
+                }
+            }
+            self.next_event += 1;
+        }
+    }
+
+    pub fn end_frame(&mut self, chip8: &Chip8) -> Result<(), Desync> {
+        self.frame += 1;
+        while let Some(&(frame, expected)) = self.movie.hashes.get(self.next_hash) {
+            if frame > self.frame {
+                break;
+            }
+            self.next_hash += 1;
+            let actual = state_hash(chip8);
+            if frame == self.frame && actual != expected {
+                return Err(Desync { frame, expected, actual });
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::AudioSink;
+    use crate::error::ErrorPolicy;
+    use crate::headless::{self, Outcome, RunConfig};
+
+    struct Silent;
+
+    impl AudioSink for Silent {
+        fn frame(&mut self, _chip8: &Chip8) -> Result<(), String> {
+            Ok(())
+        }
+
+        fn set_muted(&mut self, _muted: bool) {}
+    }
+
+    // Adds up random numbers, and counts cycles while key 5 is held.
+    const ROM: [u8; 14] = [
+        0xC0, 0xFF, // RND V0, 0xFF
+        0x81, 0x04, // ADD V1, V0
+        0x62, 0x05, // LD V2, 5
+        0xE2, 0x9E, // SKP V2
+        0x12, 0x00, // JP 0x200
+        0x73, 0x01, // ADD V3, 1
+        0x12, 0x00, // JP 0x200
+    ];
+
+    fn machine() -> Chip8 {
+        let mut chip8 = Chip8::new();
+        chip8.load_rom(&ROM).expect("ROM loads");
+        chip8
+    }
+
+    fn record() -> (Movie, u64) {
+        let mut chip8 = machine();
+        let config = RunConfig {
+            instructions_per_second: 600,
+            frames: Some(200),
+            keys: headless::parse_key_script("30:5,100:+5,150:-5").expect("valid key script"),
+            record: true,
+            ..RunConfig::default()
+        };
+        let report = headless::run(&mut chip8, &config, &mut Silent).expect("runs");
+        (report.movie.expect("recorded"), state_hash(&chip8))
+    }
+
+    fn replay(movie: &Movie) -> (Outcome, Chip8) {
+        // Seeded differently from the recording; the movie restores the seed.
+        let mut chip8 = machine();
+        let config = RunConfig { replay: Some(movie.clone()), ..RunConfig::default() };
+        let report = headless::run(&mut chip8, &config, &mut Silent).expect("the movie starts");
+        (report.outcome, chip8)
+    }
+
+    #[test]
+    fn text_round_trip() {
+        let error_policies = ErrorPolicies { memory: ErrorPolicy::Wrap, ..ErrorPolicies::default() };
+        let movie = Movie {
+            rom_hash: 0x5f1b_9a3e_27c0_d684,
+            memory_size: XO_CHIP_MEMORY_SIZE,
+            quirks: Quirks::SUPER_CHIP_1_0,
+            font_style: FontStyle::CosmacVip,
+            font_base: 0x000,
+            error_policies,
+            rng: "vip".to_string(),
+            rng_state: vec![0x34, 0x12],
+            instructions_per_second: 1000,
+            frames: 300,
+            events: vec![(0, Event::Keys(0)), (60, Event::Keys(0x8001)), (60, Event::Reset), (200, Event::Keys(0))],
+            hashes: vec![(60, 1), (120, u64::MAX), (180, 0xABC)],
+        };
+        let mut text = Vec::new();
+        movie.write(&mut text).expect("writes to memory");
+        let text = String::from_utf8(text).expect("movies are text");
+        assert_eq!(Movie::parse(&text), Ok(movie.clone()));
+
+        // Comments and blank lines are ignored.
+        let annotated = text.replace("ips 1000", "\n# faster than usual\nips 1000   # instructions per second\n");
+        assert_eq!(Movie::parse(&annotated), Ok(movie));
+    }
+
+    #[test]
+    fn rejects_malformed_movies() {
+        let (movie, _) = record();
+        let mut text = Vec::new();
+        movie.write(&mut text).expect("writes to memory");
+        let text = String::from_utf8(text).expect("movies are text");
+
+        assert_eq!(Movie::parse("C8ST"), Err("not a movie file".to_string()));
+        let newer = text.replacen("chip8-movie 1", "chip8-movie 2", 1);
+        assert_eq!(Movie::parse(&newer), Err("movie version 2 is newer than the supported version 1".to_string()));
+        let without_rng: String = text.lines().filter(|line| !line.starts_with("rng")).map(|line| format!("{}\n", line)).collect();
+        assert_eq!(Movie::parse(&without_rng), Err("movie is missing its rng line".to_string()));
+        let out_of_order = format!("{}keys 10 0000\n", text);
+        assert!(Movie::parse(&out_of_order).is_err_and(|e| e.ends_with("events are out of order")));
+        let unknown = text.replacen("frames", "frame", 1);
+        assert_eq!(Movie::parse(&unknown), Err("line 9: unknown entry 'frame'".to_string()));
+    }
+
+    #[test]
+    fn replay_reproduces_the_recording() {
+        let (movie, recorded) = record();
+        assert_eq!(movie.frames, 200);
+        assert_eq!(movie.hashes.iter().map(|&(frame, _)| frame).collect::<Vec<_>>(), [60, 120, 180]);
+
+        let (outcome, chip8) = replay(&movie);
+        assert_eq!(outcome, Outcome::MovieEnd);
+        assert_eq!(state_hash(&chip8), recorded);
+        assert_ne!(chip8.v[3], 0, "the held key was replayed");
+    }
+
+    #[test]
+    fn replay_detects_a_desync() {
+        let (mut movie, _) = record();
+        let (frame, hash) = &mut movie.hashes[1];
+        *hash ^= 1;
+        let expected = *hash;
+        let frame = *frame;
+        let (outcome, _) = replay(&movie);
+        assert!(matches!(outcome, Outcome::Desync(desync) if desync.frame == frame && desync.expected == expected));
+
+        // Input that differs from the recording shows up at the next hash.
+        let (mut movie, _) = record();
+        let (frame, event) = movie.events.iter_mut().find(|(frame, _)| *frame == 100).expect("the key press");
+        *event = Event::Keys(0);
+        assert_eq!(*frame, 100);
+        let (outcome, _) = replay(&movie);
+        assert!(matches!(outcome, Outcome::Desync(desync) if desync.frame == 120), "{:?}", outcome);
+
+        let mut other = Chip8::new();
+        other.load_rom(&[0x12, 0x00]).expect("ROM loads");
+        assert!(Player::start(movie, &mut other).is_err(), "a movie only plays on its own ROM");
+    }
+}
@@ -0,0 +1,192 @@
+use std::fmt;
+use std::str::FromStr;
+
+// Behaviours that differ between the interpreters CHIP-8 programs were
+// written for. Each flag selects one interpretation of an instruction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Quirks {
+    // `8XY6`/`8XYE` shift VY into VX instead of shifting VX in place.
+    pub shift_uses_vy: bool,
+    // How far `FX55`/`FX65` move I.
+    pub load_store_increment: LoadStoreIncrement,
+    // `8XY1`/`8XY2`/`8XY3` clear VF.
+    pub logic_resets_vf: bool,
+    // `BNNN` jumps to NNN + VX (X being the top nibble of NNN) instead of NNN + V0.
+    pub jump_uses_vx: bool,
+    // Sprites are clipped at the screen edges instead of wrapping around.
+    pub clip_sprites: bool,
+    // `DXYN` waits for the next 60 Hz tick before execution continues.
+    pub display_wait: bool,
+    // In high resolution, `DXYN` sets VF to the number of sprite rows that
+    // collided or were clipped at the bottom edge rather than to 1.
+    pub collision_counts_rows: bool,
+    // In low resolution, `DXY0` draws an 8x16 sprite, one byte per row,
+    // instead of a 16x16 one.
+    pub lores_dxy0_is_8x16: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LoadStoreIncrement {
+    // I is left where it was.
+    Unchanged,
+    // I ends on the last register transferred, a CHIP-48 bug SUPER-CHIP 1.0
+    // kept.
+    ByX,
+    // I ends past the last register transferred.
+    ByXPlusOne,
+}
+
+impl LoadStoreIncrement {
+    // The amount added to I after transferring V0 to VX.
+    pub fn amount(&self, x: u8) -> u16 {
+        match self {
+            LoadStoreIncrement::Unchanged => 0,
+            LoadStoreIncrement::ByX => x as u16,
+            LoadStoreIncrement::ByXPlusOne => x as u16 + 1,
+        }
+    }
+}
+
+impl Quirks {
+    pub const COSMAC_VIP: Quirks = Quirks {
+        shift_uses_vy: true,
+        load_store_increment: LoadStoreIncrement::ByXPlusOne,
+        logic_resets_vf: true,
+        jump_uses_vx: false,
+        clip_sprites: true,
+        display_wait: true,
+        collision_counts_rows: false,
+        lores_dxy0_is_8x16: false,
+    };
+
+    pub const CHIP_48: Quirks = Quirks {
+        shift_uses_vy: false,
+        load_store_increment: LoadStoreIncrement::ByX,
+        logic_resets_vf: false,
+        jump_uses_vx: true,
+        clip_sprites: true,
+        display_wait: false,
+        collision_counts_rows: false,
+        lores_dxy0_is_8x16: false,
+    };
+
+    pub const SUPER_CHIP_1_0: Quirks = Quirks {
+        shift_uses_vy: false,
+        load_store_increment: LoadStoreIncrement::ByX,
+        logic_resets_vf: false,
+        jump_uses_vx: true,
+        clip_sprites: true,
+        display_wait: false,
+        collision_counts_rows: true,
+        lores_dxy0_is_8x16: true,
+    };
+
+    pub const SUPER_CHIP_1_1: Quirks = Quirks {
+        shift_uses_vy: false,
+        load_store_increment: LoadStoreIncrement::Unchanged,
+        logic_resets_vf: false,
+        jump_uses_vx: true,
+        clip_sprites: true,
+        display_wait: false,
+        collision_counts_rows: true,
+        lores_dxy0_is_8x16: false,
+    };
+
+    pub const XO_CHIP: Quirks = Quirks {
+        shift_uses_vy: true,
+        load_store_increment: LoadStoreIncrement::ByXPlusOne,
+        logic_resets_vf: false,
+        jump_uses_vx: false,
+        clip_sprites: false,
+        display_wait: false,
+        collision_counts_rows: false,
+        lores_dxy0_is_8x16: false,
+    };
+}
+
+impl Default for Quirks {
+    fn default() -> Self {
+        Quirks::COSMAC_VIP
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum QuirkProfile {
+    #[default]
+    CosmacVip,
+    Chip48,
+    SuperChip10,
+    SuperChip11,
+    XoChip,
+}
+
+impl QuirkProfile {
+    pub fn quirks(&self) -> Quirks {
+        match self {
+            QuirkProfile::CosmacVip => Quirks::COSMAC_VIP,
+            QuirkProfile::Chip48 => Quirks::CHIP_48,
+            QuirkProfile::SuperChip10 => Quirks::SUPER_CHIP_1_0,
+            QuirkProfile::SuperChip11 => Quirks::SUPER_CHIP_1_1,
+            QuirkProfile::XoChip => Quirks::XO_CHIP,
+        }
+    }
+}
+
+impl FromStr for QuirkProfile {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "vip" | "cosmac-vip" | "chip8" => Ok(QuirkProfile::CosmacVip),
+            "chip48" | "chip-48" => Ok(QuirkProfile::Chip48),
+            "schip10" | "schip-1.0" => Ok(QuirkProfile::SuperChip10),
+            "schip11" | "schip-1.1" | "schip" => Ok(QuirkProfile::SuperChip11),
+            "xochip" | "xo-chip" => Ok(QuirkProfile::XoChip),
+            _ => Err(format!("unknown quirk profile '{}'", s)),
+        }
+    }
+}
+
+impl fmt::Display for QuirkProfile {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            QuirkProfile::CosmacVip => "COSMAC VIP",
+            QuirkProfile::Chip48 => "CHIP-48",
+            QuirkProfile::SuperChip10 => "SUPER-CHIP 1.0",
+            QuirkProfile::SuperChip11 => "SUPER-CHIP 1.1",
+            QuirkProfile::XoChip => "XO-CHIP",
+        };
+        f.write_str(name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::savestate;
+
+    const PROFILES: [QuirkProfile; 5] = [
+        QuirkProfile::CosmacVip,
+        QuirkProfile::Chip48,
+        QuirkProfile::SuperChip10,
+        QuirkProfile::SuperChip11,
+        QuirkProfile::XoChip,
+    ];
+
+    #[test]
+    fn profiles_differ() {
+        for (n, a) in PROFILES.iter().enumerate() {
+            for b in &PROFILES[n + 1..] {
+                assert_ne!(a.quirks(), b.quirks(), "{} and {}", a, b);
+            }
+        }
+    }
+
+    #[test]
+    fn quirk_bits_round_trip() {
+        for profile in PROFILES {
+            let quirks = profile.quirks();
+            assert_eq!(savestate::quirks_from_bits(savestate::quirk_bits(&quirks)), quirks, "{}", profile);
+        }
+    }
+}
@@ -0,0 +1,288 @@
+use std::fmt;
+use std::str::FromStr;
+
+// The generator behind `CXNN`. It is part of the machine state, so a fixed
+// seed makes runs reproducible and save states restore it along with
+// everything else.
+pub trait RandomSource {
+    // `memory` is the machine's memory, for generators that read from it
+    // the way the COSMAC VIP's does.
+    fn next_byte(&mut self, memory: &[u8]) -> u8;
+
+    // Called on every 60 Hz tick.
+    fn tick(&mut self) {}
+
+    // Identifies the generator in save states.
+    fn name(&self) -> &'static str;
+
+    fn state(&self) -> Vec<u8>;
+
+    fn restore(&mut self, state: &[u8]) -> Result<(), String>;
+
+    fn clone_box(&self) -> Box<dyn RandomSource>;
+}
+
+// A seed for runs that don't ask for a particular one.
+pub fn entropy_seed() -> u64 {
+    rand::random()
+}
+
+// xorshift64*, the default generator.
+#[derive(Debug, Clone)]
+pub struct Xorshift {
+    state: u64,
+}
+
+impl Xorshift {
+    pub fn new(seed: u64) -> Self {
+        // Run the seed through SplitMix64 so that small seeds, and zero in
+        // particular, still give a well-mixed non-zero state.
+        let mut z = seed.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^= z >> 31;
+        Self { state: if z == 0 { 1 } else { z } }
+    }
+}
+
+impl RandomSource for Xorshift {
+    fn next_byte(&mut self, _memory: &[u8]) -> u8 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        (self.state.wrapping_mul(0x2545_F491_4F6C_DD1D) >> 56) as u8
+    }
+
+    fn name(&self) -> &'static str {
+        "xorshift"
+    }
+
+    fn state(&self) -> Vec<u8> {
+        self.state.to_le_bytes().to_vec()
+    }
+
+    fn restore(&mut self, state: &[u8]) -> Result<(), String> {
+        let state = u64::from_le_bytes(state.try_into().map_err(|_| "xorshift state must be 8 bytes")?);
+        if state == 0 {
+            return Err("xorshift state must not be zero".to_string());
+        }
+        self.state = state;
+        Ok(())
+    }
+
+    fn clone_box(&self) -> Box<dyn RandomSource> {
+        Box::new(self.clone())
+    }
+}
+
+// The COSMAC VIP interpreter's routine. Register R9 counts up once per
+// display interrupt and once per `CXNN`; its low byte picks a byte from the
+// interpreter's own code page at 0x100, which is added to its high byte to
+// give the result and the new high byte. No copy of the interpreter ships
+// with the emulator, so `new` reads the page from emulated memory, which
+// holds the font rather than the interpreter; the sequence matches a real VIP
+// only with the page from a dump of its ROM passed to `with_code_page`, or
+// when a ROM puts the interpreter image back in place.
+#[derive(Debug, Clone)]
+pub struct CosmacVip {
+    r9: u16,
+    code_page: Option<Box<[u8; 256]>>,
+}
+
+impl CosmacVip {
+    const CODE_PAGE: usize = 0x100;
+
+    pub fn new(seed: u64) -> Self {
+        Self { r9: seed as u16, code_page: None }
+    }
+
+    // `page` is the interpreter's code from 0x100 to 0x1FF. It is saved with
+    // the state, so copies of the machine and its save states keep it.
+    pub fn with_code_page(seed: u64, page: [u8; 256]) -> Self {
+        Self { r9: seed as u16, code_page: Some(Box::new(page)) }
+    }
+}
+
+impl RandomSource for CosmacVip {
+    fn next_byte(&mut self, memory: &[u8]) -> u8 {
+        self.r9 = self.r9.wrapping_add(1);
+        let [high, low] = self.r9.to_be_bytes();
+        let byte = match &self.code_page {
+            Some(page) => page[low as usize],
+            None => memory.get(Self::CODE_PAGE + low as usize).copied().unwrap_or(0),
+        };
+        let high = high.wrapping_add(byte);
+        self.r9 = u16::from_be_bytes([high, low]);
+        high
+    }
+
+    fn tick(&mut self) {
+        self.r9 = self.r9.wrapping_add(1);
+    }
+
+    fn name(&self) -> &'static str {
+        "vip"
+    }
+
+    // R9, followed by the code page if there is one.
+    fn state(&self) -> Vec<u8> {
+        let mut state = self.r9.to_le_bytes().to_vec();
+        if let Some(page) = &self.code_page {
+            state.extend_from_slice(&page[..]);
+        }
+        state
+    }
+
+    fn restore(&mut self, state: &[u8]) -> Result<(), String> {
+        let (r9, page) = match state.len() {
+            2 => (state, None),
+            258 => (&state[..2], Some(Box::new(state[2..].try_into().expect("256 bytes")))),
+            _ => return Err("VIP random state must be 2 or 258 bytes".to_string()),
+        };
+        self.r9 = u16::from_le_bytes(r9.try_into().expect("2 bytes"));
+        self.code_page = page;
+        Ok(())
+    }
+
+    fn clone_box(&self) -> Box<dyn RandomSource> {
+        Box::new(self.clone())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RandomKind {
+    #[default]
+    Xorshift,
+    CosmacVip,
+}
+
+impl RandomKind {
+    pub fn create(&self, seed: u64) -> Box<dyn RandomSource> {
+        match self {
+            RandomKind::Xorshift => Box::new(Xorshift::new(seed)),
+            RandomKind::CosmacVip => Box::new(CosmacVip::new(seed)),
+        }
+    }
+
+    // Looks up a built-in generator by `RandomSource::name`.
+    pub fn from_name(name: &str) -> Option<RandomKind> {
+        match name {
+            "xorshift" => Some(RandomKind::Xorshift),
+            "vip" => Some(RandomKind::CosmacVip),
+            _ => None,
+        }
+    }
+}
+
+impl FromStr for RandomKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "xorshift" => Ok(RandomKind::Xorshift),
+            "vip" | "cosmac-vip" => Ok(RandomKind::CosmacVip),
+            _ => Err(format!("unknown random generator '{}'", s)),
+        }
+    }
+}
+
+impl fmt::Display for RandomKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            RandomKind::Xorshift => "xorshift",
+            RandomKind::CosmacVip => "COSMAC VIP",
+        };
+        f.write_str(name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chip8::Chip8;
+    use crate::savestate;
+
+    const KINDS: [RandomKind; 2] = [RandomKind::Xorshift, RandomKind::CosmacVip];
+
+    fn bytes(rng: &mut dyn RandomSource, memory: &[u8], count: usize) -> Vec<u8> {
+        (0..count).map(|_| rng.next_byte(memory)).collect()
+    }
+
+    #[test]
+    fn same_seed_same_sequence() {
+        let memory: Vec<u8> = (0..=255).cycle().take(0x1000).collect();
+        for kind in KINDS {
+            for seed in [0, 1, 0xDEAD_BEEF] {
+                let (mut a, mut b) = (kind.create(seed), kind.create(seed));
+                assert_eq!(bytes(&mut *a, &memory, 64), bytes(&mut *b, &memory, 64), "{} seeded with {}", kind, seed);
+            }
+            let (mut a, mut b) = (kind.create(1), kind.create(2));
+            assert_ne!(bytes(&mut *a, &memory, 64), bytes(&mut *b, &memory, 64), "{} with different seeds", kind);
+        }
+    }
+
+    #[test]
+    fn state_round_trip() {
+        let memory: Vec<u8> = (0..=255).cycle().take(0x1000).collect();
+        for kind in KINDS {
+            let mut rng = kind.create(7);
+            bytes(&mut *rng, &memory, 10);
+            rng.tick();
+            let mut copy = kind.create(99);
+            copy.restore(&rng.state()).expect("own state restores");
+            assert_eq!(bytes(&mut *copy, &memory, 64), bytes(&mut *rng, &memory, 64), "{}", kind);
+        }
+        assert!(Xorshift::new(0).restore(&[0; 8]).is_err());
+        assert!(Xorshift::new(0).restore(&[1; 4]).is_err());
+        assert!(CosmacVip::new(0).restore(&[1; 3]).is_err());
+        assert!(CosmacVip::new(0).restore(&[1; 257]).is_err());
+    }
+
+    // Worked by hand from the routine, with each page byte equal to its
+    // offset so that every step adds the low byte to the high one.
+    #[test]
+    fn vip_sequence() {
+        let page: [u8; 256] = std::array::from_fn(|n| n as u8);
+        let zeros = [0; 0x1000];
+        let mut rng = CosmacVip::with_code_page(0x1234, page);
+        assert_eq!(bytes(&mut rng, &zeros, 5), [0x47, 0x7D, 0xB4, 0xEC, 0x25]);
+        rng.tick();
+        assert_eq!(bytes(&mut rng, &zeros, 1), [0x60]);
+
+        // Without a page of its own it reads the page from memory.
+        let mut memory = [0; 0x1000];
+        memory[0x100..0x200].copy_from_slice(&page);
+        assert_eq!(bytes(&mut CosmacVip::new(0x1234), &memory, 5), [0x47, 0x7D, 0xB4, 0xEC, 0x25]);
+    }
+
+    // The page goes with the state, into a machine with another generator too.
+    #[test]
+    fn vip_code_page_survives_a_save_state() {
+        let page: [u8; 256] = std::array::from_fn(|n| n as u8);
+        let mut chip8 = Chip8::new();
+        chip8.rng = Box::new(CosmacVip::with_code_page(0x1234, page));
+        let mut loaded = Chip8::new();
+        savestate::load(&mut loaded, &savestate::save(&chip8)).expect("state loads");
+        let zeros = [0; 0x1000];
+        assert_eq!(bytes(&mut *loaded.rng, &zeros, 5), [0x47, 0x7D, 0xB4, 0xEC, 0x25]);
+    }
+
+    // A state saved mid-run carries the generator, so the load continues the
+    // same sequence even into a machine with another generator.
+    #[test]
+    fn savestate_round_trip() {
+        for kind in KINDS {
+            let mut chip8 = Chip8::new();
+            chip8.rng = kind.create(42);
+            bytes(&mut *chip8.rng, &chip8.memory.clone(), 5);
+            let state = savestate::save(&chip8);
+
+            let mut loaded = Chip8::new();
+            loaded.rng = RandomKind::Xorshift.create(1);
+            savestate::load(&mut loaded, &state).expect("state loads");
+            assert_eq!(loaded.rng.name(), kind.create(0).name());
+            let memory = chip8.memory.clone();
+            assert_eq!(bytes(&mut *loaded.rng, &memory, 64), bytes(&mut *chip8.rng, &memory, 64), "{}", kind);
+        }
+    }
+}
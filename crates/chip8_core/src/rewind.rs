@@ -0,0 +1,284 @@
+use std::collections::VecDeque;
+
+use crate::chip8::Chip8;
+use crate::savestate;
+
+// Keeps recent history as save-state snapshots. Only the newest snapshot is
+// stored whole; each older frame is the XOR of two consecutive snapshots with
+// its runs of zeroes squeezed out. XOR undoes itself, so stepping back a frame
+// is applying the newest delta to the newest snapshot. Where the snapshot
+// changed size, as when a state from a larger machine is loaded, the older
+// one is kept whole instead.
+pub struct Rewind {
+    budget: usize,
+    current: Option<Vec<u8>>,
+    deltas: VecDeque<Frame>,
+    used: usize,
+}
+
+enum Frame {
+    Delta(Vec<u8>),
+    Whole(Vec<u8>),
+}
+
+impl Frame {
+    fn len(&self) -> usize {
+        match self {
+            Frame::Delta(bytes) | Frame::Whole(bytes) => bytes.len(),
+        }
+    }
+}
+
+impl Rewind {
+    // `budget` caps the bytes held in deltas; the oldest frames are dropped
+    // to stay under it.
+    pub fn new(budget: usize) -> Self {
+        Self {
+            budget,
+            current: None,
+            deltas: VecDeque::new(),
+            used: 0,
+        }
+    }
+
+    // Call once per emulated frame.
+    pub fn record(&mut self, chip8: &Chip8) {
+        if self.budget == 0 {
+            return;
+        }
+        let snapshot = savestate::save(chip8);
+        if let Some(previous) = self.current.take() {
+            let frame = if previous.len() == snapshot.len() {
+                Frame::Delta(compress(&previous, &snapshot))
+            } else {
+                Frame::Whole(previous)
+            };
+            self.used += frame.len();
+            self.deltas.push_back(frame);
+            while self.used > self.budget {
+                match self.deltas.pop_front() {
+                    Some(oldest) => self.used -= oldest.len(),
+                    None => break,
+                }
+            }
+        }
+        self.current = Some(snapshot);
+    }
+
+    // Restores the state from `frames` recorded frames ago, or as far back
+    // as the history goes, and returns how many frames were stepped back.
+    // Rewinding 0 frames returns to the last recorded frame.
+    pub fn rewind(&mut self, chip8: &mut Chip8, frames: usize) -> usize {
+        let Some(current) = self.current.as_mut() else {
+            return 0;
+        };
+        let mut rewound = 0;
+        while rewound < frames {
+            let Some(frame) = self.deltas.pop_back() else {
+                break;
+            };
+            self.used -= frame.len();
+            match frame {
+                Frame::Delta(delta) => apply(current, &delta),
+                Frame::Whole(snapshot) => *current = snapshot,
+            }
+            rewound += 1;
+        }
+        // The snapshot was produced by `savestate::save`, so it always loads.
+        let _ = savestate::load(chip8, current);
+        rewound
+    }
+
+    // Frames that can be stepped back.
+    pub fn len(&self) -> usize {
+        self.deltas.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.deltas.is_empty()
+    }
+
+    pub fn memory_used(&self) -> usize {
+        self.used + self.current.as_ref().map_or(0, Vec::len)
+    }
+
+    pub fn clear(&mut self) {
+        self.current = None;
+        self.deltas.clear();
+        self.used = 0;
+    }
+}
+
+// Encodes `old ^ new` as pairs of (zero run, literal run) lengths, each a
+// LEB128 varint, with the literal bytes following their length.
+fn compress(old: &[u8], new: &[u8]) -> Vec<u8> {
+    let mut output = Vec::new();
+    let mut pos = 0;
+    while pos < old.len() {
+        let zeroes = old[pos..].iter().zip(&new[pos..]).take_while(|(a, b)| a == b).count();
+        pos += zeroes;
+        let literals = old[pos..].iter().zip(&new[pos..]).take_while(|(a, b)| a != b).count();
+        write_varint(&mut output, zeroes);
+        write_varint(&mut output, literals);
+        output.extend(old[pos..pos + literals].iter().zip(&new[pos..pos + literals]).map(|(a, b)| a ^ b));
+        pos += literals;
+    }
+    output
+}
+
+fn apply(state: &mut [u8], delta: &[u8]) {
+    let mut input = delta;
+    let mut pos = 0;
+    while !input.is_empty() {
+        pos += read_varint(&mut input);
+        let literals = read_varint(&mut input);
+        for (byte, xor) in state[pos..pos + literals].iter_mut().zip(&input[..literals]) {
+            *byte ^= xor;
+        }
+        input = &input[literals..];
+        pos += literals;
+    }
+}
+
+fn write_varint(output: &mut Vec<u8>, mut value: usize) {
+    while value >= 0x80 {
+        output.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    output.push(value as u8);
+}
+
+fn read_varint(input: &mut &[u8]) -> usize {
+    let mut value = 0;
+    let mut shift = 0;
+    while let Some((&byte, rest)) = input.split_first() {
+        *input = rest;
+        value |= ((byte & 0x7F) as usize) << shift;
+        if byte & 0x80 == 0 {
+            break;
+        }
+        shift += 7;
+    }
+    value
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chip8::{KeyWait, XO_CHIP_MEMORY_SIZE};
+
+    // A machine with `program` at 0x200.
+    fn machine(program: &[u16]) -> Chip8 {
+        let rom: Vec<u8> = program.iter().flat_map(|opcode| opcode.to_be_bytes()).collect();
+        let mut chip8 = Chip8::new();
+        chip8.load_rom(&rom).expect("program loads");
+        chip8
+    }
+
+    // Counts V0 up once a frame.
+    const COUNTER: &[u16] = &[
+        0x7001, // ADD V0, 1
+        0x1200, // JP 0x200
+    ];
+
+    fn frame(chip8: &mut Chip8, rewind: &mut Rewind) {
+        chip8.emulate_cycle().expect("runs");
+        chip8.emulate_cycle().expect("runs");
+        rewind.record(chip8);
+    }
+
+    #[test]
+    fn rewinds_to_earlier_frames() {
+        let mut chip8 = machine(COUNTER);
+        let mut rewind = Rewind::new(1 << 20);
+        rewind.record(&chip8);
+        for _ in 0..10 {
+            frame(&mut chip8, &mut rewind);
+        }
+        assert_eq!(rewind.len(), 10);
+        assert_eq!(chip8.v[0], 10);
+
+        assert_eq!(rewind.rewind(&mut chip8, 3), 3);
+        assert_eq!(chip8.v[0], 7);
+        assert_eq!(rewind.len(), 7);
+        // Zero frames goes back to the newest frame left.
+        chip8.v[0] = 0xFF;
+        assert_eq!(rewind.rewind(&mut chip8, 0), 0);
+        assert_eq!(chip8.v[0], 7);
+        // Further than the history only goes as far as it reaches.
+        assert_eq!(rewind.rewind(&mut chip8, 100), 7);
+        assert_eq!(chip8.v[0], 0);
+        assert!(rewind.is_empty());
+    }
+
+    #[test]
+    fn budget_drops_the_oldest_frames() {
+        let mut chip8 = machine(COUNTER);
+        let mut rewind = Rewind::new(1 << 20);
+        rewind.record(&chip8);
+        frame(&mut chip8, &mut rewind);
+        let delta = rewind.memory_used() - savestate::save(&chip8).len();
+
+        let mut rewind = Rewind::new(4 * delta);
+        rewind.record(&chip8);
+        for _ in 0..10 {
+            frame(&mut chip8, &mut rewind);
+        }
+        assert_eq!(rewind.len(), 4);
+        assert_eq!(rewind.rewind(&mut chip8, 100), 4);
+        assert_eq!(chip8.v[0], 7);
+
+        let mut off = Rewind::new(0);
+        off.record(&chip8);
+        assert_eq!(off.rewind(&mut chip8, 1), 0);
+    }
+
+    // FX0A waits, then a key press and its release, all keep the history.
+    #[test]
+    fn history_survives_a_key_wait() {
+        let mut chip8 = machine(&[
+            0x7001, // ADD V0, 1
+            0xF10A, // LD V1, K
+            0x7001, // ADD V0, 1
+            0x1204, // JP 0x204
+        ]);
+        let mut rewind = Rewind::new(1 << 20);
+        rewind.record(&chip8);
+        frame(&mut chip8, &mut rewind);
+        assert_eq!(chip8.key_wait, Some(KeyWait::Press { x: 1 }));
+        chip8.keypad[5] = 1;
+        frame(&mut chip8, &mut rewind);
+        assert_eq!(chip8.key_wait, Some(KeyWait::Release { x: 1, key: 5 }));
+        chip8.keypad[5] = 0;
+        frame(&mut chip8, &mut rewind);
+        assert_eq!(chip8.key_wait, None);
+        assert_eq!(rewind.len(), 3);
+
+        assert_eq!(rewind.rewind(&mut chip8, 1), 1);
+        assert_eq!(chip8.key_wait, Some(KeyWait::Release { x: 1, key: 5 }));
+        assert_eq!(rewind.rewind(&mut chip8, 2), 2);
+        assert_eq!(chip8.key_wait, None);
+        assert_eq!(chip8.pc, 0x200);
+    }
+
+    #[test]
+    fn history_survives_a_size_change() {
+        let mut chip8 = machine(COUNTER);
+        let mut rewind = Rewind::new(1 << 20);
+        rewind.record(&chip8);
+        frame(&mut chip8, &mut rewind);
+
+        let mut larger = Chip8::with_memory_size(XO_CHIP_MEMORY_SIZE);
+        larger.load_rom(&[0x70, 0x05, 0x12, 0x00]).expect("program loads");
+        larger.emulate_cycle().expect("runs");
+        savestate::load(&mut chip8, &savestate::save(&larger)).expect("state loads");
+        rewind.record(&chip8);
+        assert_eq!(rewind.len(), 2);
+
+        assert_eq!(rewind.rewind(&mut chip8, 1), 1);
+        assert_eq!(chip8.memory.len(), 0x1000);
+        assert_eq!(chip8.v[0], 1);
+        assert_eq!(rewind.rewind(&mut chip8, 1), 1);
+        assert_eq!(chip8.v[0], 0);
+    }
+}
@@ -0,0 +1,495 @@
+use std::error::Error;
+use std::fmt;
+
+use crate::chip8::{Chip8, KeyWait, MEMORY_SIZE, PROGRAM_START, SCREEN_HEIGHT, SCREEN_WIDTH, XO_CHIP_MEMORY_SIZE};
+use crate::font::{FontStyle, BIG_FONT_SIZE, FONT_SIZE};
+use crate::quirks::{LoadStoreIncrement, Quirks};
+use crate::random::RandomKind;
+
+// A state file is the magic, a little-endian u16 format version and a list of
+// chunks, each a four-byte tag, a u32 length and the payload. Unknown chunks
+// are skipped and missing ones keep their power-on values, so states saved by
+// older versions keep loading as chunks are added.
+//
+// Version history:
+//   1: CPU, memory, display, keypad, quirk and XO-CHIP registers, and the ROM.
+//   2: the random number generator. Version 1 states keep the current one.
+//   3: the state of an `FX0A` waiting for a key.
+const MAGIC: &[u8; 4] = b"C8ST";
+pub const VERSION: u16 = 3;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum StateError {
+    BadMagic,
+    // Saved by a newer version of the emulator.
+    UnsupportedVersion(u16),
+    Truncated,
+    Invalid(String),
+}
+
+impl fmt::Display for StateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StateError::BadMagic => write!(f, "not a save state"),
+            StateError::UnsupportedVersion(version) => {
+                write!(f, "save state version {} is newer than the supported version {}", version, VERSION)
+            }
+            StateError::Truncated => write!(f, "save state is truncated"),
+            StateError::Invalid(message) => write!(f, "invalid save state: {}", message),
+        }
+    }
+}
+
+impl Error for StateError {}
+
+struct Writer {
+    data: Vec<u8>,
+}
+
+impl Writer {
+    fn u8(&mut self, value: u8) {
+        self.data.push(value);
+    }
+
+    fn u16(&mut self, value: u16) {
+        self.data.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn bytes(&mut self, bytes: &[u8]) {
+        self.data.extend_from_slice(bytes);
+    }
+
+    fn chunk(&mut self, tag: &[u8; 4], write: impl FnOnce(&mut Writer)) {
+        let mut chunk = Writer { data: Vec::new() };
+        write(&mut chunk);
+        self.bytes(tag);
+        self.bytes(&(chunk.data.len() as u32).to_le_bytes());
+        self.bytes(&chunk.data);
+    }
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8], StateError> {
+        if self.data.len() < len {
+            return Err(StateError::Truncated);
+        }
+        let (bytes, rest) = self.data.split_at(len);
+        self.data = rest;
+        Ok(bytes)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], StateError> {
+        let mut array = [0; N];
+        array.copy_from_slice(self.bytes(N)?);
+        Ok(array)
+    }
+
+    fn u8(&mut self) -> Result<u8, StateError> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, StateError> {
+        Ok(u16::from_le_bytes(self.array()?))
+    }
+
+    fn u32(&mut self) -> Result<u32, StateError> {
+        Ok(u32::from_le_bytes(self.array()?))
+    }
+
+    fn bool(&mut self) -> Result<bool, StateError> {
+        Ok(self.u8()? != 0)
+    }
+}
+
+// Bit order of the quirk flags; new quirks go on the end so that older
+// states read them as unset. Bit 1 says whether loads and stores move I and
+// bit 7 that they stop short by one.
+pub(crate) fn quirk_bits(quirks: &Quirks) -> u32 {
+    [
+        quirks.shift_uses_vy,
+        quirks.load_store_increment != LoadStoreIncrement::Unchanged,
+        quirks.logic_resets_vf,
+        quirks.jump_uses_vx,
+        quirks.clip_sprites,
+        quirks.display_wait,
+        quirks.collision_counts_rows,
+        quirks.load_store_increment == LoadStoreIncrement::ByX,
+        quirks.lores_dxy0_is_8x16,
+    ]
+    .iter()
+    .enumerate()
+    .fold(0, |bits, (bit, &set)| bits | (set as u32) << bit)
+}
+
+pub(crate) fn quirks_from_bits(bits: u32) -> Quirks {
+    let bit = |n: u32| bits & (1 << n) != 0;
+    Quirks {
+        shift_uses_vy: bit(0),
+        load_store_increment: match (bit(1), bit(7)) {
+            (false, _) => LoadStoreIncrement::Unchanged,
+            (true, false) => LoadStoreIncrement::ByXPlusOne,
+            (true, true) => LoadStoreIncrement::ByX,
+        },
+        logic_resets_vf: bit(2),
+        jump_uses_vx: bit(3),
+        clip_sprites: bit(4),
+        display_wait: bit(5),
+        collision_counts_rows: bit(6),
+        lores_dxy0_is_8x16: bit(8),
+    }
+}
+
+pub(crate) fn font_style_id(style: FontStyle) -> u8 {
+    match style {
+        FontStyle::Standard => 0,
+        FontStyle::CosmacVip => 1,
+        FontStyle::Dream6800 => 2,
+        FontStyle::Eti660 => 3,
+    }
+}
+
+pub(crate) fn font_style_from_id(id: u8) -> Result<FontStyle, StateError> {
+    match id {
+        0 => Ok(FontStyle::Standard),
+        1 => Ok(FontStyle::CosmacVip),
+        2 => Ok(FontStyle::Dream6800),
+        3 => Ok(FontStyle::Eti660),
+        _ => Err(StateError::Invalid(format!("unknown font style {}", id))),
+    }
+}
+
+pub fn save(chip8: &Chip8) -> Vec<u8> {
+    let mut writer = Writer { data: Vec::new() };
+    writer.bytes(MAGIC);
+    writer.u16(VERSION);
+
+    writer.chunk(b"CPU ", |w| {
+        w.bytes(&chip8.v);
+        w.u16(chip8.i);
+        w.u16(chip8.pc);
+        w.u8(chip8.sp as u8);
+        for &address in &chip8.stack {
+            w.u16(address);
+        }
+        w.u8(chip8.delay_timer);
+        w.u8(chip8.sound_timer);
+        w.u8(chip8.waiting_for_vblank as u8);
+        w.u8(chip8.exited as u8);
+    });
+    writer.chunk(b"MEM ", |w| w.bytes(&chip8.memory));
+    writer.chunk(b"GFX ", |w| {
+        w.u8(chip8.hires as u8);
+        w.u8(chip8.plane_mask);
+        w.bytes(&chip8.gfx);
+    });
+    writer.chunk(b"KEYS", |w| w.bytes(&chip8.keypad));
+    // Always three bytes, so that states stay the same size and rewind can
+    // store the difference between them.
+    writer.chunk(b"WAIT", |w| match chip8.key_wait {
+        None => w.bytes(&[0, 0, 0]),
+        Some(KeyWait::Press { x }) => w.bytes(&[1, x, 0]),
+        Some(KeyWait::Release { x, key }) => w.bytes(&[2, x, key]),
+    });
+    writer.chunk(b"CONF", |w| {
+        w.bytes(&quirk_bits(&chip8.quirks).to_le_bytes());
+        w.u8(font_style_id(chip8.font_style));
+        w.u16(chip8.font_base);
+    });
+    writer.chunk(b"XO  ", |w| {
+        w.bytes(&chip8.rpl);
+        w.bytes(&chip8.audio_pattern);
+        w.u8(chip8.pitch);
+    });
+    writer.chunk(b"RNG ", |w| {
+        let name = chip8.rng.name();
+        w.u8(name.len() as u8);
+        w.bytes(name.as_bytes());
+        w.bytes(&chip8.rng.state());
+    });
+    writer.chunk(b"ROM ", |w| w.bytes(&chip8.rom));
+
+    writer.data
+}
+
+// Replaces the machine state with a saved one. Error policies and the
+// memory access log are frontend settings and are kept as they are. On
+// error, `chip8` is left untouched.
+pub fn load(chip8: &mut Chip8, data: &[u8]) -> Result<(), StateError> {
+    let mut reader = Reader { data };
+    if reader.bytes(MAGIC.len()).map_err(|_| StateError::BadMagic)? != MAGIC {
+        return Err(StateError::BadMagic);
+    }
+    let version = reader.u16()?;
+    if version > VERSION {
+        return Err(StateError::UnsupportedVersion(version));
+    }
+
+    let mut state = Chip8::with_memory_size(chip8.memory.len());
+    state.error_policies = chip8.error_policies;
+    state.log_memory_access = chip8.log_memory_access;
+    state.rng = chip8.rng.clone_box();
+
+    while !reader.data.is_empty() {
+        let tag = reader.array::<4>()?;
+        let len = reader.u32()? as usize;
+        let mut chunk = Reader { data: reader.bytes(len)? };
+
+        match &tag {
+            b"CPU " => {
+                state.v = chunk.array()?;
+                state.i = chunk.u16()?;
+                state.pc = chunk.u16()?;
+                state.sp = chunk.u8()? as usize;
+                for address in &mut state.stack {
+                    *address = chunk.u16()?;
+                }
+                state.delay_timer = chunk.u8()?;
+                state.sound_timer = chunk.u8()?;
+                state.waiting_for_vblank = chunk.bool()?;
+                state.exited = chunk.bool()?;
+                if state.sp > state.stack.len() {
+                    return Err(StateError::Invalid(format!("stack pointer {} out of range", state.sp)));
+                }
+            }
+            b"MEM " => {
+                if !(MEMORY_SIZE..=XO_CHIP_MEMORY_SIZE).contains(&chunk.data.len()) {
+                    return Err(StateError::Invalid(format!("memory size {} out of range", chunk.data.len())));
+                }
+                state.memory = chunk.data.to_vec();
+            }
+            b"GFX " => {
+                state.hires = chunk.bool()?;
+                state.plane_mask = chunk.u8()?;
+                state.gfx = chunk.array::<{ SCREEN_WIDTH * SCREEN_HEIGHT }>()?;
+            }
+            b"KEYS" => state.keypad = chunk.array()?,
+            b"WAIT" => {
+                state.key_wait = match chunk.u8()? {
+                    0 => None,
+                    1 => Some(KeyWait::Press { x: chunk.u8()? }),
+                    2 => Some(KeyWait::Release { x: chunk.u8()?, key: chunk.u8()? }),
+                    phase => return Err(StateError::Invalid(format!("unknown key wait phase {}", phase))),
+                };
+                let (x, key) = match state.key_wait {
+                    Some(KeyWait::Press { x }) => (x, 0),
+                    Some(KeyWait::Release { x, key }) => (x, key),
+                    None => (0, 0),
+                };
+                if x > 0xF || key > 0xF {
+                    return Err(StateError::Invalid("key wait register or key out of range".to_string()));
+                }
+            }
+            b"CONF" => {
+                state.quirks = quirks_from_bits(chunk.u32()?);
+                state.font_style = font_style_from_id(chunk.u8()?)?;
+                state.font_base = chunk.u16()?;
+            }
+            b"XO  " => {
+                state.rpl = chunk.array()?;
+                state.audio_pattern = chunk.array()?;
+                state.pitch = chunk.u8()?;
+            }
+            b"RNG " => {
+                let len = chunk.u8()? as usize;
+                let name = String::from_utf8_lossy(chunk.bytes(len)?).into_owned();
+                // A custom generator can only restore its own state.
+                if name != state.rng.name() {
+                    state.rng = RandomKind::from_name(&name)
+                        .ok_or_else(|| StateError::Invalid(format!("unknown random generator '{}'", name)))?
+                        .create(0);
+                }
+                state.rng.restore(chunk.data).map_err(StateError::Invalid)?;
+            }
+            b"ROM " => state.rom = chunk.data.to_vec(),
+            _ => {}
+        }
+    }
+
+    if state.pc as usize >= state.memory.len() {
+        return Err(StateError::Invalid(format!("program counter {:#X} outside memory", state.pc)));
+    }
+    if state.font_base as usize + FONT_SIZE + BIG_FONT_SIZE > PROGRAM_START {
+        return Err(StateError::Invalid(format!("font base {:#X} out of range", state.font_base)));
+    }
+    if PROGRAM_START + state.rom.len() > state.memory.len() {
+        return Err(StateError::Invalid(format!("ROM of {} bytes does not fit in memory", state.rom.len())));
+    }
+    *chip8 = state;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::random::{CosmacVip, RandomSource};
+
+    // A machine with something other than its power-on value in every chunk.
+    fn busy_machine() -> Chip8 {
+        let mut chip8 = Chip8::with_memory_size(XO_CHIP_MEMORY_SIZE);
+        chip8.set_font(FontStyle::Dream6800, 0x000).expect("font fits");
+        chip8.load_rom(&[0x12, 0x00, 0xAB, 0xCD]).expect("ROM loads");
+        chip8.v = std::array::from_fn(|x| x as u8 * 3);
+        chip8.i = 0x345;
+        chip8.pc = 0x202;
+        chip8.sp = 2;
+        chip8.stack[..2].copy_from_slice(&[0x204, 0x206]);
+        chip8.delay_timer = 7;
+        chip8.sound_timer = 9;
+        chip8.waiting_for_vblank = true;
+        chip8.exited = true;
+        chip8.memory[0xFFFF] = 0x5A;
+        chip8.hires = true;
+        chip8.plane_mask = 3;
+        chip8.gfx[SCREEN_WIDTH * SCREEN_HEIGHT - 1] = 2;
+        chip8.keypad[0xC] = 1;
+        chip8.key_wait = Some(KeyWait::Release { x: 4, key: 0xC });
+        chip8.quirks = Quirks::SUPER_CHIP_1_0;
+        chip8.rpl[15] = 0x77;
+        chip8.audio_pattern[0] = 0xF0;
+        chip8.pitch = 0x40;
+        chip8.rng = Box::new(CosmacVip::new(0x1234));
+        chip8
+    }
+
+    // Offsets that start a chunk, and the end of the state.
+    fn chunk_starts(state: &[u8]) -> Vec<usize> {
+        let mut starts = vec![MAGIC.len() + 2];
+        while let Some(&start) = starts.last().filter(|&&start| start < state.len()) {
+            let len = u32::from_le_bytes(state[start + 4..start + 8].try_into().expect("4 bytes")) as usize;
+            starts.push(start + 8 + len);
+        }
+        starts
+    }
+
+    #[test]
+    fn every_chunk_round_trips() {
+        let chip8 = busy_machine();
+        let state = save(&chip8);
+        let mut loaded = Chip8::new();
+        load(&mut loaded, &state).expect("state loads");
+        assert_eq!(save(&loaded), state);
+
+        assert_eq!((loaded.i, loaded.pc, loaded.sp, loaded.stack[1]), (0x345, 0x202, 2, 0x206));
+        assert_eq!((loaded.delay_timer, loaded.sound_timer), (7, 9));
+        assert!(loaded.waiting_for_vblank && loaded.exited);
+        assert_eq!((loaded.memory.len(), loaded.memory[0xFFFF]), (XO_CHIP_MEMORY_SIZE, 0x5A));
+        assert!(loaded.hires);
+        assert_eq!(loaded.gfx, chip8.gfx);
+        assert_eq!(loaded.keypad[0xC], 1);
+        assert_eq!(loaded.key_wait, Some(KeyWait::Release { x: 4, key: 0xC }));
+        assert_eq!(loaded.quirks, Quirks::SUPER_CHIP_1_0);
+        assert_eq!((loaded.font_style, loaded.font_base), (FontStyle::Dream6800, 0x000));
+        assert_eq!((loaded.rpl[15], loaded.audio_pattern[0], loaded.pitch), (0x77, 0xF0, 0x40));
+        assert_eq!(loaded.rng.name(), "vip");
+        assert_eq!(loaded.rom, [0x12, 0x00, 0xAB, 0xCD]);
+
+        for key_wait in [None, Some(KeyWait::Press { x: 0xF })] {
+            let mut chip8 = busy_machine();
+            chip8.key_wait = key_wait;
+            load(&mut loaded, &save(&chip8)).expect("state loads");
+            assert_eq!(loaded.key_wait, key_wait);
+        }
+    }
+
+    #[test]
+    fn rejects_other_files_and_newer_versions() {
+        let mut chip8 = busy_machine();
+        let before = save(&chip8);
+        assert_eq!(load(&mut chip8, b"C8"), Err(StateError::BadMagic));
+        assert_eq!(load(&mut chip8, b"PK\x03\x04\x03\x00"), Err(StateError::BadMagic));
+
+        let mut newer = before.clone();
+        newer[4..6].copy_from_slice(&(VERSION + 1).to_le_bytes());
+        assert_eq!(load(&mut chip8, &newer), Err(StateError::UnsupportedVersion(VERSION + 1)));
+
+        let mut bad_stack = before.clone();
+        let sp = chunk_starts(&before)[0] + 8 + 16 + 4;
+        bad_stack[sp] = 17;
+        assert!(matches!(load(&mut chip8, &bad_stack), Err(StateError::Invalid(_))));
+        assert_eq!(save(&chip8), before, "a failed load leaves the machine as it was");
+    }
+
+    // A newer version may add chunks; an older one skips them.
+    #[test]
+    fn skips_unknown_chunks() {
+        let chip8 = busy_machine();
+        let state = save(&chip8);
+        let mut extended = state[..6].to_vec();
+        extended.extend_from_slice(b"NEW!");
+        extended.extend_from_slice(&3u32.to_le_bytes());
+        extended.extend_from_slice(&[1, 2, 3]);
+        extended.extend_from_slice(&state[6..]);
+
+        let mut loaded = Chip8::new();
+        load(&mut loaded, &extended).expect("state loads");
+        assert_eq!(save(&loaded), state);
+    }
+
+    // Cut anywhere, a state fails to load rather than panicking, except
+    // between chunks, where the chunks left out keep their power-on values.
+    #[test]
+    fn truncated_states_are_errors() {
+        let mut chip8 = Chip8::new();
+        chip8.load_rom(&[0x12, 0x00]).expect("ROM loads");
+        chip8.v[3] = 0x33;
+        let state = save(&chip8);
+        let starts = chunk_starts(&state);
+        for len in 0..state.len() {
+            let mut loaded = Chip8::new();
+            let result = load(&mut loaded, &state[..len]);
+            match len {
+                0..=3 => assert_eq!(result, Err(StateError::BadMagic)),
+                _ if starts.contains(&len) => assert_eq!(result, Ok(()), "cut at {}", len),
+                _ => assert_eq!(result, Err(StateError::Truncated), "cut at {}", len),
+            }
+        }
+
+        let mut loaded = Chip8::new();
+        load(&mut loaded, &state[..starts[1]]).expect("the CPU chunk alone loads");
+        assert_eq!(loaded.v[3], 0x33);
+    }
+
+    // Generators that aren't built in can still restore their own state.
+    #[test]
+    fn keeps_a_custom_generator() {
+        #[derive(Clone)]
+        struct Fixed(u8);
+
+        impl RandomSource for Fixed {
+            fn next_byte(&mut self, _memory: &[u8]) -> u8 {
+                self.0
+            }
+
+            fn name(&self) -> &'static str {
+                "fixed"
+            }
+
+            fn state(&self) -> Vec<u8> {
+                vec![self.0]
+            }
+
+            fn restore(&mut self, state: &[u8]) -> Result<(), String> {
+                self.0 = *state.first().ok_or("empty state")?;
+                Ok(())
+            }
+
+            fn clone_box(&self) -> Box<dyn RandomSource> {
+                Box::new(self.clone())
+            }
+        }
+
+        let mut chip8 = Chip8::new();
+        chip8.rng = Box::new(Fixed(0x42));
+        let state = save(&chip8);
+        chip8.rng = Box::new(Fixed(0));
+        load(&mut chip8, &state).expect("state loads");
+        assert_eq!(chip8.rng.next_byte(&[]), 0x42);
+
+        let mut other = Chip8::new();
+        assert_eq!(load(&mut other, &state), Err(StateError::Invalid("unknown random generator 'fixed'".to_string())));
+    }
+}
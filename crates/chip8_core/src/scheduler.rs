@@ -0,0 +1,83 @@
+use std::thread;
+use std::time::{Duration, Instant};
+
+pub const FRAME_RATE: f64 = 60.0;
+
+// Converts elapsed wall-clock time into 60 Hz emulated frames and spreads the
+// configured instructions-per-second across them, carrying the fractional
+// part so that e.g. 700 IPS alternates between 11 and 12 cycles per frame.
+pub struct Scheduler {
+    pub instructions_per_second: u32,
+    // Multiplier on emulated time: above 1.0 fast-forwards, below 1.0 slows down.
+    pub speed: f64,
+    last: Instant,
+    pending_frames: f64,
+    pending_cycles: f64,
+}
+
+impl Scheduler {
+    pub fn new(instructions_per_second: u32) -> Self {
+        Self {
+            instructions_per_second,
+            speed: 1.0,
+            last: Instant::now(),
+            pending_frames: 0.0,
+            pending_cycles: 0.0,
+        }
+    }
+
+    // Forgets the time elapsed since the last call, e.g. after a pause.
+    pub fn reset_clock(&mut self) {
+        self.last = Instant::now();
+        self.pending_frames = 0.0;
+    }
+
+    pub fn frames_due(&mut self) -> u32 {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.last).as_secs_f64();
+        self.last = now;
+
+        self.pending_frames += elapsed * self.speed * FRAME_RATE;
+        let due = self.pending_frames.floor();
+        self.pending_frames -= due;
+
+        // Drop whatever a stall (window drag, debugger stop) left behind
+        // rather than running it all at once.
+        let limit = (self.speed * 4.0).ceil().max(4.0);
+        due.min(limit) as u32
+    }
+
+    pub fn cycles_for_frame(&mut self) -> u32 {
+        self.pending_cycles += self.instructions_per_second as f64 / FRAME_RATE;
+        let cycles = self.pending_cycles.floor();
+        self.pending_cycles -= cycles;
+        cycles as u32
+    }
+}
+
+// Sleeps until fixed deadlines rather than for fixed durations, so time spent
+// emulating and rendering comes out of the frame budget instead of adding to it.
+pub struct FramePacer {
+    frame: Duration,
+    next: Instant,
+}
+
+impl FramePacer {
+    pub fn new(frame_rate: f64) -> Self {
+        Self {
+            frame: Duration::from_secs_f64(1.0 / frame_rate),
+            next: Instant::now(),
+        }
+    }
+
+    pub fn wait(&mut self) {
+        self.next += self.frame;
+        let now = Instant::now();
+        if self.next > now {
+            thread::sleep(self.next - now);
+        } else if now - self.next > self.frame {
+            // Too far behind to catch up; start pacing again from here.
+            self.next = now;
+        }
+    }
+}
@@ -0,0 +1,222 @@
+// Runs the test programs in tests/roms headlessly under every quirk profile
+// and compares the final screen with the one each program documents: a tick
+// for every check that passes, the quirk digits each profile should show, or
+// a picture drawn by hand in tests/golden. The programs are written for this
+// repository in its own assembler, modelled on the community test ROMs but
+// not those ROMs, so they check the emulator against its own reading of each
+// instruction and quirk rather than against other implementations.
+//
+//   cargo test -p chip8_core --test conformance
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use chip8_core::asm;
+use chip8_core::audio::AudioSink;
+use chip8_core::chip8::{Chip8, XO_CHIP_MEMORY_SIZE};
+use chip8_core::engine::EngineKind;
+use chip8_core::font::FontStyle;
+use chip8_core::headless::{self, Outcome, RunConfig};
+use chip8_core::image;
+use chip8_core::quirks::QuirkProfile;
+use chip8_core::random::RandomKind;
+
+// Names as `QuirkProfile` parses them, used in golden file names.
+const PROFILES: [(QuirkProfile, &str); 5] = [
+    (QuirkProfile::CosmacVip, "vip"),
+    (QuirkProfile::Chip48, "chip48"),
+    (QuirkProfile::SuperChip10, "schip10"),
+    (QuirkProfile::SuperChip11, "schip11"),
+    (QuirkProfile::XoChip, "xochip"),
+];
+
+// Fast enough that the programs finish well within their frames.
+const INSTRUCTIONS_PER_SECOND: u32 = 6000;
+
+#[test]
+fn logo() {
+    check("logo", 60, "", |_| golden("logo"));
+}
+
+#[test]
+fn opcodes() {
+    check("opcodes", 120, "", |_| ticks(34));
+}
+
+#[test]
+fn flags() {
+    check("flags", 120, "", |_| ticks(13));
+}
+
+// The digits quirks.asm shows under each profile, as its header describes.
+const QUIRK_DIGITS: [(&str, &str); 5] = [
+    ("vip", "112201110"),
+    ("chip48", "001111010"),
+    ("schip10", "001111001"),
+    ("schip11", "000011011"),
+    ("xochip", "012200010"),
+];
+
+#[test]
+fn quirks() {
+    check("quirks", 120, "", |profile| {
+        let (_, digits) = QUIRK_DIGITS.iter().find(|(name, _)| *name == profile).expect("digits for every profile");
+        let mut screen = Screen::new();
+        for (n, digit) in digits.chars().enumerate() {
+            let digit = digit.to_digit(16).expect("hex digit") as usize;
+            screen.draw(4 + 6 * n, 2, &FontStyle::Standard.sprites()[5 * digit..][..5]);
+        }
+        screen.ascii()
+    });
+}
+
+#[test]
+fn keypad() {
+    check("keypad", 90, "10:5,40:+A,60:-A", |_| ticks(3));
+}
+
+// The program sets the sound timer to 30, which sounds for the 29 frames
+// after the one that set it, as the timer is ticked before each frame is
+// played.
+#[test]
+fn beep() {
+    for (profile, name) in PROFILES {
+        let mut sink = Counter::default();
+        run("beep", profile, 60, "", &mut sink);
+        assert_eq!(sink.sounding, 29, "sounding frames under {}", name);
+    }
+    // The note is erased once the buzzer stops.
+    check("beep", 60, "", |_| Screen::new().ascii());
+}
+
+#[derive(Default)]
+struct Counter {
+    sounding: u32,
+}
+
+impl AudioSink for Counter {
+    fn frame(&mut self, chip8: &Chip8) -> Result<(), String> {
+        if chip8.sound_active() {
+            self.sounding += 1;
+        }
+        Ok(())
+    }
+
+    fn set_muted(&mut self, _muted: bool) {}
+}
+
+struct Silent;
+
+impl AudioSink for Silent {
+    fn frame(&mut self, _chip8: &Chip8) -> Result<(), String> {
+        Ok(())
+    }
+
+    fn set_muted(&mut self, _muted: bool) {}
+}
+
+// Runs `name` for `frames` frames under every profile and compares the
+// screens with `expected` for each.
+fn check(name: &str, frames: u64, keys: &str, expected: impl Fn(&str) -> String) {
+    let mut failures = Vec::new();
+    for (profile, profile_name) in PROFILES {
+        let actual = run(name, profile, frames, keys, &mut Silent);
+        let expected = expected(profile_name);
+        if actual != expected {
+            failures.push(format!("{} under {}:\n{}", name, profile_name, diff(&expected, &actual)));
+        }
+    }
+    assert!(failures.is_empty(), "screens differ from the expected ones\n\n{}", failures.join("\n"));
+}
+
+fn run(name: &str, profile: QuirkProfile, frames: u64, keys: &str, audio: &mut dyn AudioSink) -> String {
+    let mut chip8 = if profile == QuirkProfile::XoChip {
+        Chip8::with_memory_size(XO_CHIP_MEMORY_SIZE)
+    } else {
+        Chip8::new()
+    };
+    chip8.quirks = profile.quirks();
+    chip8.rng = RandomKind::default().create(1);
+    chip8.load_rom(&rom(name)).unwrap_or_else(|e| panic!("cannot load {}: {}", name, e));
+
+    // Checked, so every run also holds the block engine to the interpreter.
+    let config = RunConfig {
+        instructions_per_second: INSTRUCTIONS_PER_SECOND,
+        frames: Some(frames),
+        keys: headless::parse_key_script(keys).expect("valid key script"),
+        engine: EngineKind::Checked,
+        ..RunConfig::default()
+    };
+    let report = headless::run(&mut chip8, &config, audio).expect("the audio sink never fails");
+    assert_eq!(report.outcome, Outcome::FrameLimit, "{} under {}", name, profile);
+    image::ascii(&chip8)
+}
+
+fn rom(name: &str) -> Vec<u8> {
+    let source = directory("roms").join(format!("{}.asm", name));
+    asm::assemble_file(&source, 0x200).unwrap_or_else(|e| panic!("{}", e))
+}
+
+fn golden(name: &str) -> String {
+    let path = directory("golden").join(format!("{}.txt", name));
+    fs::read_to_string(&path).unwrap_or_else(|e| panic!("cannot read {}: {}", path.display(), e))
+}
+
+// What report.asm draws for `count` checks that all pass.
+fn ticks(count: usize) -> String {
+    let tick = [0x08, 0x10, 0xA0, 0x40, 0x00];
+    let mut screen = Screen::new();
+    for n in 0..count {
+        screen.draw(6 * (n % 10), 6 * (n / 10), &tick);
+    }
+    screen.ascii()
+}
+
+// A lores screen, drawn the way DXYN draws, for building expected screens.
+struct Screen {
+    pixels: [[bool; 64]; 32],
+}
+
+impl Screen {
+    fn new() -> Self {
+        Self { pixels: [[false; 64]; 32] }
+    }
+
+    fn draw(&mut self, x: usize, y: usize, sprite: &[u8]) {
+        for (row, bits) in sprite.iter().enumerate() {
+            for column in 0..8 {
+                if bits & (0x80 >> column) != 0 {
+                    self.pixels[y + row][x + column] ^= true;
+                }
+            }
+        }
+    }
+
+    // In the form `image::ascii` gives.
+    fn ascii(&self) -> String {
+        let mut text = String::new();
+        for row in &self.pixels {
+            text.extend(row.iter().map(|&pixel| if pixel { '#' } else { '.' }));
+            text.push('\n');
+        }
+        text
+    }
+}
+
+fn directory(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join(name)
+}
+
+// The rows that differ, expected above actual.
+fn diff(expected: &str, actual: &str) -> String {
+    let mut out = String::new();
+    let rows = expected.lines().count().max(actual.lines().count());
+    for row in 0..rows {
+        let want = expected.lines().nth(row).unwrap_or("");
+        let got = actual.lines().nth(row).unwrap_or("");
+        if want != got {
+            out.push_str(&format!("  row {:2} expected {}\n         actual   {}\n", row, want, got));
+        }
+    }
+    out
+}
@@ -0,0 +1,21 @@
+; Sounds the buzzer for half a second and draws a note while it does.
+
+    CLS
+    LD I, note
+    LD V0, 0x1C
+    LD V1, 0xC
+    DRW V0, V1, 0x8
+    LD V2, 0x1E
+    LD ST, V2
+    LD DT, V2
+wait:
+    LD V2, DT
+    SE V2, 0x0
+    JMP to wait
+    DRW V0, V1, 0x8
+
+end:
+    JMP to end
+
+note:
+    db 0x0C, 0x0E, 0x0B, 0x09, 0x08, 0x78, 0xF8, 0x70
@@ -0,0 +1,87 @@
+; Checks the carry and borrow flags, including with VF as an operand or as
+; the target, where the flag overwrites the result. One mark each.
+
+    CLS
+    LD VE, 0x0
+    LD VD, 0x0
+
+    ; 8XY4: a sum of exactly 0x100 carries
+    LD VA, 0x80
+    LD V1, 0x80
+    ADD VA, V1
+    LD VC, VF
+    LD VB, 0x0
+    CALL report
+    LD VA, VC
+    LD VB, 0x1
+    CALL report
+
+    ; 8XY5 and 8XY7 with equal operands don't borrow
+    LD VA, 0x05
+    LD V1, 0x05
+    SUB VA, V1
+    LD VA, VF
+    LD VB, 0x1
+    CALL report
+    LD VA, 0x05
+    SUBN VA, V1
+    LD VA, VF
+    CALL report
+
+    ; VF as an operand is read before the flag is set
+    LD V1, 0x10
+    LD VF, 0x20
+    ADD V1, VF
+    LD VA, V1
+    LD VB, 0x30
+    CALL report
+    LD V1, 0x30
+    LD VF, 0x10
+    SUB V1, VF
+    LD VA, V1
+    LD VB, 0x20
+    CALL report
+
+    ; VF as the target ends up holding the flag
+    LD VF, 0xFF
+    LD V1, 0x01
+    ADD VF, V1
+    LD VA, VF
+    LD VB, 0x1
+    CALL report
+    LD VF, 0x01
+    ADD VF, V1
+    LD VA, VF
+    LD VB, 0x0
+    CALL report
+    LD VF, 0x05
+    SUB VF, V1
+    LD VA, VF
+    LD VB, 0x1
+    CALL report
+    LD VF, 0x00
+    SUB VF, V1
+    LD VA, VF
+    LD VB, 0x0
+    CALL report
+    LD VF, 0x01
+    LD V1, 0x05
+    SUBN VF, V1
+    LD VA, VF
+    LD VB, 0x1
+    CALL report
+    LD VF, 0x02
+    SHR VF, VF
+    LD VA, VF
+    LD VB, 0x0
+    CALL report
+    LD VF, 0x81
+    SHL VF, VF
+    LD VA, VF
+    LD VB, 0x1
+    CALL report
+
+end:
+    JMP to end
+
+include "report.asm"
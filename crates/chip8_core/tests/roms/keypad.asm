@@ -0,0 +1,28 @@
+; Driven by the key script in the conformance test: 5 tapped, then A held and
+; released. Marks FX0A returning 5, EX9E seeing A held and EXA1 seeing it go.
+
+    CLS
+    LD VE, 0x0
+    LD VD, 0x0
+
+    LD VA, K
+    LD VB, 0x5
+    CALL report
+
+    LD V1, 0xA
+held:
+    SKP V1
+    JMP to held
+    LD VA, 0x1
+    LD VB, 0x1
+    CALL report
+
+released:
+    SKNP V1
+    JMP to released
+    CALL report
+
+end:
+    JMP to end
+
+include "report.asm"
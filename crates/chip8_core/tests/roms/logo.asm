@@ -0,0 +1,53 @@
+; Like the IBM logo test: clears the screen and draws a framed logo and the
+; sixteen font digits with nothing but 00E0, 6XNN, 7XNN, ANNN, FX29 and DXYN.
+
+    CLS
+    LD I, bar
+    LD V0, 0x0
+    LD V1, 0x0
+    LD V2, 0x1F
+top:
+    DRW V0, V1, 0x1
+    DRW V0, V2, 0x1
+    ADD V0, 0x8
+    SE V0, 0x40
+    JMP to top
+
+    LD I, chip
+    LD V0, 0x14
+    LD V1, 0x3
+    DRW V0, V1, 0x5
+    LD I, chip8
+    ADD V0, 0x8
+    DRW V0, V1, 0x5
+    LD I, dash8
+    ADD V0, 0x8
+    DRW V0, V1, 0x5
+
+    LD V0, 0x0
+    LD V1, 0x4
+    LD V2, 0xB
+digits:
+    LD F, V0
+    DRW V1, V2, 0x5
+    ADD V0, 0x1
+    ADD V1, 0x7
+    SE V0, 0x8
+    JMP to next
+    LD V1, 0x4
+    LD V2, 0x13
+next:
+    SE V0, 0x10
+    JMP to digits
+
+end:
+    JMP to end
+
+bar:
+    db 0xFF
+chip:
+    db 0xEA, 0x8A, 0x8E, 0x8A, 0xEA
+chip8:
+    db 0xEE, 0x4A, 0x4E, 0x48, 0xE8
+dash8:
+    db 0x0E, 0x0A, 0xEE, 0x0A, 0x0E
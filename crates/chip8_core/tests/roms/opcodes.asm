@@ -0,0 +1,222 @@
+; Checks the instructions whose behaviour every profile agrees on, one mark
+; each: skips, arithmetic and its flags, BCD, loads and stores, calls, jumps,
+; timers and the font.
+
+    CLS
+    LD VE, 0x0
+    LD VD, 0x0
+
+    ; 3XNN, taken and not taken
+    LD V0, 0x5
+    LD VB, 0x1
+    LD VA, 0x1
+    SE V0, 0x5
+    LD VA, 0x0
+    CALL report
+    LD VA, 0x0
+    SE V0, 0x6
+    LD VA, 0x1
+    CALL report
+
+    ; 4XNN, taken and not taken
+    LD VA, 0x1
+    SNE V0, 0x6
+    LD VA, 0x0
+    CALL report
+    LD VA, 0x0
+    SNE V0, 0x5
+    LD VA, 0x1
+    CALL report
+
+    ; 5XY0 and 9XY0
+    LD V1, 0x5
+    LD VA, 0x1
+    SE V0, V1
+    LD VA, 0x0
+    CALL report
+    LD V1, 0x6
+    LD VA, 0x1
+    SNE V0, V1
+    LD VA, 0x0
+    CALL report
+
+    ; 7XNN wraps without touching VF
+    LD VA, 0xFF
+    ADD VA, 0x2
+    LD VB, 0x1
+    CALL report
+
+    ; 8XY0 to 8XY3
+    LD V1, 0x42
+    LD VA, V1
+    LD VB, 0x42
+    CALL report
+    LD VA, 0x0F
+    LD V1, 0xF0
+    OR VA, V1
+    LD VB, 0xFF
+    CALL report
+    LD VA, 0x3C
+    LD V1, 0x0F
+    AND VA, V1
+    LD VB, 0x0C
+    CALL report
+    LD VA, 0x3C
+    XOR VA, V1
+    LD VB, 0x33
+    CALL report
+
+    ; 8XY4 with and without a carry
+    LD VA, 0xF0
+    LD V1, 0x20
+    ADD VA, V1
+    LD VC, VF
+    LD VB, 0x10
+    CALL report
+    LD VA, VC
+    LD VB, 0x1
+    CALL report
+    LD VA, 0x10
+    ADD VA, V1
+    LD VA, VF
+    LD VB, 0x0
+    CALL report
+
+    ; 8XY5 with and without a borrow
+    LD VA, 0x30
+    LD V1, 0x10
+    SUB VA, V1
+    LD VC, VF
+    LD VB, 0x20
+    CALL report
+    LD VA, VC
+    LD VB, 0x1
+    CALL report
+    LD VA, 0x10
+    LD V1, 0x30
+    SUB VA, V1
+    LD VC, VF
+    LD VB, 0xE0
+    CALL report
+    LD VA, VC
+    LD VB, 0x0
+    CALL report
+
+    ; 8XY7
+    LD VA, 0x10
+    SUBN VA, V1
+    LD VC, VF
+    LD VB, 0x20
+    CALL report
+    LD VA, VC
+    LD VB, 0x1
+    CALL report
+
+    ; 8XY6 and 8XYE, with VX as VY so the shift quirk doesn't matter
+    LD VA, 0x05
+    SHR VA, VA
+    LD VC, VF
+    LD VB, 0x02
+    CALL report
+    LD VA, VC
+    LD VB, 0x1
+    CALL report
+    LD VA, 0x81
+    SHL VA, VA
+    LD VC, VF
+    LD VB, 0x02
+    CALL report
+    LD VA, VC
+    LD VB, 0x1
+    CALL report
+
+    ; FX33
+    LD V3, 0xEA
+    LD I, scratch
+    LD B, V3
+    LD I, scratch
+    LD V2, [I]
+    LD VA, V0
+    LD VB, 0x2
+    CALL report
+    LD VA, V1
+    LD VB, 0x3
+    CALL report
+    LD VA, V2
+    LD VB, 0x4
+    CALL report
+
+    ; FX55 and FX65
+    LD V0, 0x11
+    LD V1, 0x22
+    LD V2, 0x33
+    LD I, scratch
+    LD [I], V2
+    LD V0, 0x0
+    LD V1, 0x0
+    LD V2, 0x0
+    LD I, scratch
+    LD V2, [I]
+    LD VA, V1
+    LD VB, 0x22
+    CALL report
+
+    ; FX1E
+    LD I, pattern
+    LD V0, 0x1
+    ADD I, V0
+    LD V0, [I]
+    LD VA, V0
+    LD VB, 0xA5
+    CALL report
+
+    ; 2NNN and 00EE
+    LD VA, 0x0
+    CALL set_va
+    LD VB, 0x77
+    CALL report
+
+    ; 1NNN
+    LD VA, 0x1
+    JMP to jumped
+    LD VA, 0x0
+jumped:
+    LD VB, 0x1
+    CALL report
+
+    ; FX15 and FX07
+    LD V0, 0x10
+    LD DT, V0
+    LD V1, DT
+    LD VA, 0x0
+    SE V1, 0x0
+    LD VA, 0x1
+    LD VB, 0x1
+    CALL report
+
+    ; CXNN with an empty mask
+    RND VA, 0x00
+    LD VB, 0x0
+    CALL report
+
+    ; FX29 points at the standard font's 0
+    LD V0, 0x0
+    LD F, V0
+    LD V0, [I]
+    LD VA, V0
+    LD VB, 0xF0
+    CALL report
+
+end:
+    JMP to end
+
+set_va:
+    LD VA, 0x77
+    RET
+
+pattern:
+    db 0x5A, 0xA5
+scratch:
+    db 0, 0, 0
+
+include "report.asm"
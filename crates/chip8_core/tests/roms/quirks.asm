@@ -0,0 +1,138 @@
+; Shows which quirks are on, one digit each, 1 for on and 0 for off. In order:
+; logic resets VF, shifts read VY, how far FX55 and FX65 with X = 1 move I
+; (0, 1 or 2, one digit each), BNNN adds VX, sprites clip, drawing waits for
+; the display, DXY0 draws 16 wide in lores, and hires collisions count rows.
+
+    ; Hires collisions first, since switching resolution may clear the screen.
+    HIGH
+    LD I, two_rows
+    LD V0, 0x0
+    DRW V0, V0, 0x2
+    DRW V0, V0, 0x2
+    LD V7, 0x0
+    SNE VF, 0x2
+    LD V7, 0x1
+    LOW
+    CLS
+    LD VE, 0x4
+    LD VD, 0x2
+
+    ; 8XY1
+    LD VF, 0x5
+    LD V0, 0x1
+    LD V1, 0x2
+    OR V0, V1
+    LD V9, 0x0
+    SNE VF, 0x0
+    LD V9, 0x1
+    CALL digit
+
+    ; 8XY6 with VX and VY apart
+    LD V0, 0x10
+    LD V1, 0x04
+    SHR V0, V1
+    LD V9, 0x0
+    SNE V0, 0x2
+    LD V9, 0x1
+    CALL digit
+
+    ; FX55 over the first two bytes of 0xFF 0xFF 0x02, then FX65 reads
+    ; where I ended up
+    LD V0, 0x0
+    LD V1, 0x1
+    LD I, stored
+    LD [I], V1
+    LD V0, [I]
+    LD V9, V0
+    CALL digit
+
+    ; FX65 from 0x00 0x01 0x02, then again from where I ended up
+    LD I, loaded
+    LD V1, [I]
+    LD V0, [I]
+    LD V9, V0
+    CALL digit
+
+    ; BNNN: the table is in 0x2NN, so with the quirk it adds V2
+    LD V0, 0x0
+    LD V2, 0x2
+    JMP V0, jumps
+jumps:
+    JMP to jump_off
+    JMP to jump_on
+jump_off:
+    LD V9, 0x0
+    JMP to jump_done
+jump_on:
+    LD V9, 0x1
+jump_done:
+    CALL digit
+
+    ; A line at x=60 only reaches x=0 if it wraps
+    LD I, line
+    LD V0, 0x3C
+    LD V1, 0x1E
+    LD V3, 0x0
+    DRW V0, V1, 0x1
+    DRW V3, V1, 0x1
+    LD V9, 0x1
+    SE VF, 0x0
+    LD V9, 0x0
+    DRW V0, V1, 0x1
+    DRW V3, V1, 0x1
+    CALL digit
+
+    ; Count the draws that fit in three frames; waiting allows only a few
+    LD V0, 0x3
+    LD DT, V0
+    LD V3, 0x0
+    LD V1, 0x1F
+    LD I, line
+wait_loop:
+    DRW V3, V1, 0x1
+    DRW V3, V1, 0x1
+    ADD V3, 0x1
+    LD V6, DT
+    SE V6, 0x0
+    JMP to wait_loop
+    LD V6, 0xA
+    SUB V6, V3
+    LD V9, VF
+    LD V3, 0x0
+    CALL digit
+
+    ; A pixel at x=12 only hits a DXY0 sprite at x=0 if it is 16 wide
+    LD I, square
+    LD V0, 0x0
+    LD V1, 0x10
+    LD V2, 0xC
+    DRW V0, V1, 0x0
+    LD I, two_rows
+    DRW V2, V1, 0x1
+    LD V9, VF
+    DRW V2, V1, 0x1
+    LD I, square
+    DRW V0, V1, 0x0
+    CALL digit
+
+    LD V9, V7
+    CALL digit
+
+end:
+    JMP to end
+
+two_rows:
+    db 0x80, 0x80
+line:
+    db 0xFF
+stored:
+    db 0xFF, 0xFF, 0x02
+loaded:
+    db 0x00, 0x01, 0x02
+square:
+    db 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF
+    db 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF
+    db 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF
+    db 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF
+
+include "report.asm"
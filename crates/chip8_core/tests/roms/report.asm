@@ -0,0 +1,26 @@
+; Shared by the test programs. `report` draws a tick at VE, VD if VA equals
+; VB and a cross otherwise, then moves the cursor along, ten marks to a row.
+; Both clobber VF. `digit` draws V9 as a hex digit and moves the cursor along.
+
+report:
+    LD I, tick
+    SE VA, VB
+    LD I, cross
+    DRW VE, VD, 0x5
+    ADD VE, 0x6
+    SE VE, 0x3C
+    RET
+    LD VE, 0x0
+    ADD VD, 0x6
+    RET
+
+digit:
+    LD F, V9
+    DRW VE, VD, 0x5
+    ADD VE, 0x6
+    RET
+
+tick:
+    db 0x08, 0x10, 0xA0, 0x40, 0x00
+cross:
+    db 0x88, 0x50, 0x20, 0x50, 0x88
@@ -0,0 +1,40 @@
+// Option parsing helpers shared by the binaries.
+
+use std::str::FromStr;
+
+use chip8_core::random::{self, RandomKind, RandomSource};
+
+// The value following `option`.
+pub fn value(option: &str, value: Option<String>) -> Result<String, String> {
+    value.ok_or_else(|| format!("{} requires a value", option))
+}
+
+pub fn parse_number<T: FromStr>(option: &str, raw: Option<String>) -> Result<T, String> {
+    let raw = value(option, raw)?;
+    raw.parse()
+        .map_err(|_| format!("invalid value '{}' for {}", raw, option))
+}
+
+// Decimal, or hex with a `0x` prefix.
+pub fn parse_address(option: &str, raw: Option<String>) -> Result<u16, String> {
+    let raw = value(option, raw)?;
+    let parsed = match raw.strip_prefix("0x").or_else(|| raw.strip_prefix("0X")) {
+        Some(hex) => u16::from_str_radix(hex, 16),
+        None => raw.parse(),
+    };
+    parsed.map_err(|_| format!("invalid address '{}' for {}", raw, option))
+}
+
+// The `--rng` and `--seed` options.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RngOptions {
+    pub kind: RandomKind,
+    pub seed: Option<u64>,
+}
+
+impl RngOptions {
+    // Seeded from entropy unless `--seed` was given.
+    pub fn create(&self) -> Box<dyn RandomSource> {
+        self.kind.create(self.seed.unwrap_or_else(random::entropy_seed))
+    }
+}
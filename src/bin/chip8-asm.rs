@@ -0,0 +1,75 @@
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
+
+use chip8_core::asm;
+use chip8_core::chip8::PROGRAM_START;
+use chip8_rust::args::{parse_address, value};
+
+const USAGE: &str = "\
+Usage: chip8-asm [OPTIONS] <SOURCE>
+
+Options:
+  -o, --output <PATH>    Where to write the ROM (default: SOURCE with a .ch8 extension)
+      --origin <ADDR>    Address the ROM is loaded at (default: 0x200)
+  -h, --help             Print this help";
+
+struct Options {
+    source_path: PathBuf,
+    output_path: Option<PathBuf>,
+    origin: u16,
+}
+
+fn main() {
+    let options = match parse(env::args().skip(1)) {
+        Ok(Some(options)) => options,
+        Ok(None) => {
+            println!("{}", USAGE);
+            return;
+        }
+        Err(e) => {
+            eprintln!("error: {}\n\n{}", e, USAGE);
+            process::exit(2);
+        }
+    };
+
+    if let Err(e) = run(&options) {
+        eprintln!("error: {}", e);
+        process::exit(1);
+    }
+}
+
+fn parse<I: IntoIterator<Item = String>>(args: I) -> Result<Option<Options>, String> {
+    let mut source_path = None;
+    let mut output_path = None;
+    let mut origin = PROGRAM_START as u16;
+
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-o" | "--output" => output_path = Some(PathBuf::from(value(&arg, args.next())?)),
+            "--origin" => origin = parse_address(&arg, args.next())?,
+            "-h" | "--help" => return Ok(None),
+            _ if arg.starts_with('-') && arg.len() > 1 => return Err(format!("unknown option '{}'", arg)),
+            _ if source_path.is_none() => source_path = Some(PathBuf::from(arg)),
+            _ => return Err(format!("unexpected argument '{}'", arg)),
+        }
+    }
+
+    let source_path = source_path.ok_or("no source file given")?;
+    Ok(Some(Options { source_path, output_path, origin }))
+}
+
+fn run(options: &Options) -> Result<(), String> {
+    let rom = asm::assemble_file(&options.source_path, options.origin).map_err(|e| e.to_string())?;
+    let output_path = match &options.output_path {
+        Some(path) => path.clone(),
+        None => options.source_path.with_extension("ch8"),
+    };
+    write_rom(&output_path, &rom)
+}
+
+fn write_rom(path: &Path, rom: &[u8]) -> Result<(), String> {
+    fs::write(path, rom).map_err(|e| format!("cannot write '{}': {}", path.display(), e))
+}
@@ -0,0 +1,83 @@
+use std::env;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::process;
+
+use chip8_core::chip8::PROGRAM_START;
+use chip8_core::disasm::{self, Format};
+use chip8_rust::args::{parse_address, value};
+
+const USAGE: &str = "\
+Usage: chip8-disasm [OPTIONS] <ROM>
+
+Options:
+  -f, --format <FORMAT>  Listing syntax: text, octo, json (default: text)
+  -o, --output <PATH>    Write the listing to a file instead of stdout
+      --origin <ADDR>    Address the ROM is loaded at (default: 0x200)
+  -h, --help             Print this help";
+
+struct Options {
+    rom_path: String,
+    format: Format,
+    output_path: Option<String>,
+    origin: u16,
+}
+
+fn main() {
+    let options = match parse(env::args().skip(1)) {
+        Ok(Some(options)) => options,
+        Ok(None) => {
+            println!("{}", USAGE);
+            return;
+        }
+        Err(e) => {
+            eprintln!("error: {}\n\n{}", e, USAGE);
+            process::exit(2);
+        }
+    };
+
+    if let Err(e) = run(&options) {
+        eprintln!("error: {}", e);
+        process::exit(1);
+    }
+}
+
+fn parse<I: IntoIterator<Item = String>>(args: I) -> Result<Option<Options>, String> {
+    let mut rom_path = None;
+    let mut format = Format::default();
+    let mut output_path = None;
+    let mut origin = PROGRAM_START as u16;
+
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-f" | "--format" => format = value(&arg, args.next())?.parse()?,
+            "-o" | "--output" => output_path = Some(value(&arg, args.next())?),
+            "--origin" => origin = parse_address(&arg, args.next())?,
+            "-h" | "--help" => return Ok(None),
+            _ if arg.starts_with('-') && arg.len() > 1 => return Err(format!("unknown option '{}'", arg)),
+            _ if rom_path.is_none() => rom_path = Some(arg),
+            _ => return Err(format!("unexpected argument '{}'", arg)),
+        }
+    }
+
+    let rom_path = rom_path.ok_or("no ROM file given")?;
+    Ok(Some(Options { rom_path, format, output_path, origin }))
+}
+
+fn run(options: &Options) -> Result<(), String> {
+    let rom = fs::read(&options.rom_path)
+        .map_err(|e| format!("cannot read ROM '{}': {}", options.rom_path, e))?;
+    let listing = disasm::disassemble(&rom, options.origin);
+
+    let result = match &options.output_path {
+        Some(path) => File::create(path)
+            .map(BufWriter::new)
+            .and_then(|mut file| {
+                listing.write(options.format, &mut file)?;
+                file.flush()
+            }),
+        None => listing.write(options.format, &mut io::stdout().lock()),
+    };
+    result.map_err(|e| format!("cannot write listing: {}", e))
+}
@@ -0,0 +1,213 @@
+use std::env;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::process;
+
+use chip8_core::audio::{AudioSink, NullSink, ToneSettings, WavSink};
+use chip8_core::chip8::{Chip8, XO_CHIP_MEMORY_SIZE};
+use chip8_core::error::ErrorPolicies;
+use chip8_core::font::{FontStyle, DEFAULT_FONT_BASE};
+use chip8_core::headless::{self, Outcome, RunConfig, ScreenFormat};
+use chip8_core::image;
+use chip8_core::movie::Movie;
+use chip8_core::quirks::QuirkProfile;
+use chip8_rust::args::{parse_number, value, RngOptions};
+
+const USAGE: &str = "\
+Usage: chip8-headless [OPTIONS] <ROM>
+
+Runs a ROM without a window and prints the final screen.
+
+Options:
+      --frames <N>          Stop after N frames, 60 per emulated second
+      --cycles <N>          Stop after N instructions
+      --until <COND>        Stop once a condition holds, e.g. 'PC == 0x2A0' or 'V3 >= 10'
+  -k, --keys <SCRIPT>       Scripted input: FRAME:KEY taps a key, FRAME:+KEY presses it and
+                            FRAME:-KEY releases it, e.g. '30:5,60:+A,90:-A'; @PATH reads
+                            the script from a file
+  -i, --ips <N>             Instructions executed per second (default: 700)
+      --engine <ENGINE>     interpreter, blocks (decodes basic blocks once, for bulk runs) or
+                            check (blocks, cross-checked against the interpreter as they run)
+                            (default: interpreter)
+  -q, --quirks <PROFILE>    Quirk profile: vip, chip48, schip10, schip11, xochip (default: vip)
+  -f, --font <STYLE>        Built-in font: standard, vip, dream6800, eti660 (default: standard)
+      --rng <KIND>          Random number generator: xorshift, vip (default: xorshift)
+      --seed <N>            Seed the random number generator for reproducible runs
+  -e, --on-error <POLICY>   Fault handling: halt, ignore or wrap, optionally per kind (default: halt)
+      --record <PATH>       Record the run as a movie file
+      --replay <PATH>       Replay a movie file instead of a key script, with the machine
+                            configuration it was recorded with, checking for desyncs
+      --wav <PATH>          Write the buzzer output to a WAV file
+      --format <FORMAT>     Screen output: ascii, pbm, png, hash (default: ascii)
+  -s, --scale <N>           Pixel size for pbm and png output (default: 1)
+  -o, --output <PATH>       Write the screen to a file instead of stdout
+  -h, --help                Print this help
+
+At least one of --frames, --cycles, --until and --replay is required. The exit
+status is 1 if the program faulted, the replay desynced or the block engine
+diverged from the interpreter.";
+
+struct Options {
+    rom_path: String,
+    config: RunConfig,
+    quirks: QuirkProfile,
+    font: FontStyle,
+    rng: RngOptions,
+    error_policies: ErrorPolicies,
+    record_path: Option<String>,
+    wav_path: Option<String>,
+    format: ScreenFormat,
+    scale: usize,
+    output_path: Option<String>,
+}
+
+fn main() {
+    let options = match parse(env::args().skip(1)) {
+        Ok(Some(options)) => options,
+        Ok(None) => {
+            println!("{}", USAGE);
+            return;
+        }
+        Err(e) => {
+            eprintln!("error: {}\n\n{}", e, USAGE);
+            process::exit(2);
+        }
+    };
+
+    match run(&options) {
+        Ok(Outcome::Error(_) | Outcome::Desync(_) | Outcome::Divergence(_)) => process::exit(1),
+        Ok(_) => {}
+        Err(e) => {
+            eprintln!("error: {}", e);
+            process::exit(1);
+        }
+    }
+}
+
+fn parse<I: IntoIterator<Item = String>>(args: I) -> Result<Option<Options>, String> {
+    let mut rom_path = None;
+    let mut config = RunConfig { instructions_per_second: 700, ..RunConfig::default() };
+    let mut quirks = QuirkProfile::default();
+    let mut font = FontStyle::default();
+    let mut rng = RngOptions::default();
+    let mut error_policies = ErrorPolicies::default();
+    let mut record_path = None;
+    let mut wav_path = None;
+    let mut format = ScreenFormat::default();
+    let mut scale = 1;
+    let mut output_path = None;
+
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(None),
+            "--frames" => config.frames = Some(parse_number(&arg, args.next())?),
+            "--cycles" => config.cycles = Some(parse_number(&arg, args.next())?),
+            "--until" => config.until = Some(value(&arg, args.next())?.parse()?),
+            "-k" | "--keys" => {
+                let script = value(&arg, args.next())?;
+                let script = match script.strip_prefix('@') {
+                    Some(path) => fs::read_to_string(path)
+                        .map_err(|e| format!("cannot read key script '{}': {}", path, e))?,
+                    None => script,
+                };
+                config.keys = headless::parse_key_script(&script)?;
+            }
+            "-i" | "--ips" => config.instructions_per_second = parse_number(&arg, args.next())?,
+            "--engine" => config.engine = value(&arg, args.next())?.parse()?,
+            "-q" | "--quirks" => quirks = value(&arg, args.next())?.parse()?,
+            "-f" | "--font" => font = value(&arg, args.next())?.parse()?,
+            "--rng" => rng.kind = value(&arg, args.next())?.parse()?,
+            "--seed" => rng.seed = Some(parse_number(&arg, args.next())?),
+            "-e" | "--on-error" => error_policies.parse_list(&value(&arg, args.next())?)?,
+            "--record" => record_path = Some(value(&arg, args.next())?),
+            "--replay" => config.replay = Some(Movie::read_file(value(&arg, args.next())?)?),
+            "--wav" => wav_path = Some(value(&arg, args.next())?),
+            "--format" => format = value(&arg, args.next())?.parse()?,
+            "-s" | "--scale" => scale = parse_number(&arg, args.next())?,
+            "-o" | "--output" => output_path = Some(value(&arg, args.next())?),
+            _ if arg.starts_with('-') && arg.len() > 1 => return Err(format!("unknown option '{}'", arg)),
+            _ if rom_path.is_none() => rom_path = Some(arg),
+            _ => return Err(format!("unexpected argument '{}'", arg)),
+        }
+    }
+
+    let rom_path = rom_path.ok_or("missing ROM path")?;
+    if config.frames.is_none() && config.cycles.is_none() && config.until.is_none() && config.replay.is_none() {
+        return Err("one of --frames, --cycles, --until or --replay is required".to_string());
+    }
+    if config.replay.is_some() && !config.keys.is_empty() {
+        return Err("--keys cannot be combined with --replay".to_string());
+    }
+    config.record = record_path.is_some();
+    if config.instructions_per_second == 0 {
+        return Err("--ips must be at least 1".to_string());
+    }
+    if scale == 0 {
+        return Err("--scale must be at least 1".to_string());
+    }
+
+    Ok(Some(Options {
+        rom_path,
+        config,
+        quirks,
+        font,
+        rng,
+        error_policies,
+        record_path,
+        wav_path,
+        format,
+        scale,
+        output_path,
+    }))
+}
+
+fn run(options: &Options) -> Result<Outcome, String> {
+    let rom_data = fs::read(&options.rom_path)
+        .map_err(|e| format!("cannot read ROM '{}': {}", options.rom_path, e))?;
+
+    let mut chip8 = if options.quirks == QuirkProfile::XoChip {
+        Chip8::with_memory_size(XO_CHIP_MEMORY_SIZE)
+    } else {
+        Chip8::new()
+    };
+    chip8.set_font(options.font, DEFAULT_FONT_BASE).map_err(|e| format!("cannot install the font: {}", e))?;
+    chip8.error_policies = options.error_policies;
+    chip8.quirks = options.quirks.quirks();
+    chip8.rng = options.rng.create();
+    chip8.load_rom(&rom_data)
+        .map_err(|e| format!("cannot load ROM '{}': {}", options.rom_path, e))?;
+
+    let mut audio: Box<dyn AudioSink> = match &options.wav_path {
+        Some(path) => Box::new(WavSink::create(path, ToneSettings::default())
+            .map_err(|e| format!("cannot create WAV file '{}': {}", path, e))?),
+        None => Box::new(NullSink),
+    };
+    let report = headless::run(&mut chip8, &options.config, audio.as_mut())?;
+    audio.finish()?;
+    eprintln!("{} after {} frames and {} instructions", report.outcome, report.frames, report.cycles);
+    if let (Some(movie), Some(path)) = (&report.movie, &options.record_path) {
+        movie.write_file(path)?;
+    }
+
+    let result = match &options.output_path {
+        Some(path) => File::create(path)
+            .map(BufWriter::new)
+            .and_then(|mut file| {
+                write_screen(&chip8, options, &mut file)?;
+                file.flush()
+            }),
+        None => write_screen(&chip8, options, &mut io::stdout().lock()),
+    };
+    result.map_err(|e| format!("cannot write screen: {}", e))?;
+    Ok(report.outcome)
+}
+
+fn write_screen<W: Write>(chip8: &Chip8, options: &Options, output: &mut W) -> io::Result<()> {
+    match options.format {
+        ScreenFormat::Ascii => output.write_all(image::ascii(chip8).as_bytes()),
+        ScreenFormat::Pbm => image::write_pbm(chip8, options.scale, output),
+        ScreenFormat::Png => image::write_png(chip8, options.scale, output),
+        ScreenFormat::Hash => writeln!(output, "{:016x}", image::hash(chip8)),
+    }
+}
@@ -0,0 +1,421 @@
+use std::env;
+use std::fmt::Write as _;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::process::{self, Command, Stdio};
+use std::str::FromStr;
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use chip8_core::chip8::{Chip8, XO_CHIP_MEMORY_SIZE};
+use chip8_core::{decode, Instruction};
+use chip8_core::error::ErrorPolicies;
+use chip8_core::font::{FontStyle, DEFAULT_FONT_BASE};
+use chip8_core::quirks::QuirkProfile;
+use chip8_core::scheduler::{FramePacer, Scheduler, FRAME_RATE};
+use chip8_rust::args::{parse_number, value, RngOptions};
+
+const USAGE: &str = "\
+Usage: chip8-term [OPTIONS] <ROM>
+
+Runs a ROM in the terminal, for machines without a display.
+
+Options:
+  -b, --blocks <MODE>       Characters to draw with: half (2 pixels per cell) or
+                            braille (8 pixels per cell) (default: half)
+      --hold <MS>           How long a key counts as held after the terminal last
+                            sent it (default: 150)
+  -i, --ips <N>             Instructions executed per second (default: 700)
+  -q, --quirks <PROFILE>    Quirk profile: vip, chip48, schip10, schip11, xochip (default: vip)
+  -f, --font <STYLE>        Built-in font: standard, vip, dream6800, eti660 (default: standard)
+      --rng <KIND>          Random number generator: xorshift, vip (default: xorshift)
+      --seed <N>            Seed the random number generator for reproducible runs
+  -e, --on-error <POLICY>   Fault handling: halt, ignore or wrap, optionally per kind (default: halt)
+  -m, --mute                Don't ring the terminal bell for the buzzer
+  -h, --help                Print this help
+
+Keys:
+  The keypad is on 1234/QWER/ASDF/ZXCV. Ctrl+C quits, P pauses and Ctrl+R
+  resets the machine. Terminals only report key presses, so a key is released
+  once the terminal stops repeating it; keys held longer than the terminal's
+  repeat delay need a --hold above that delay.
+  Needs a Unix terminal with `stty`.";
+
+// Indexed by hex key, as in the window's default layout.
+const KEYS: [u8; 16] = *b"x123qweasdzc4rfv";
+
+const CTRL_C: u8 = 0x03;
+const CTRL_R: u8 = 0x12;
+const ESCAPE: u8 = 0x1B;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+enum Blocks {
+    // Upper and lower half blocks: one column and two rows of pixels per cell.
+    #[default]
+    Half,
+    // Braille patterns: two columns and four rows of pixels per cell.
+    Braille,
+}
+
+impl FromStr for Blocks {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "half" => Ok(Blocks::Half),
+            "braille" => Ok(Blocks::Braille),
+            _ => Err(format!("unknown block mode '{}'", s)),
+        }
+    }
+}
+
+struct Options {
+    rom_path: String,
+    blocks: Blocks,
+    hold: Duration,
+    instructions_per_second: u32,
+    quirks: QuirkProfile,
+    font: FontStyle,
+    rng: RngOptions,
+    error_policies: ErrorPolicies,
+    mute: bool,
+}
+
+fn main() {
+    let options = match parse(env::args().skip(1)) {
+        Ok(Some(options)) => options,
+        Ok(None) => {
+            println!("{}", USAGE);
+            return;
+        }
+        Err(e) => {
+            eprintln!("error: {}\n\n{}", e, USAGE);
+            process::exit(2);
+        }
+    };
+
+    if let Err(e) = run(&options) {
+        eprintln!("error: {}", e);
+        process::exit(1);
+    }
+}
+
+fn parse<I: IntoIterator<Item = String>>(args: I) -> Result<Option<Options>, String> {
+    let mut rom_path = None;
+    let mut blocks = Blocks::default();
+    let mut hold_ms: u64 = 150;
+    let mut instructions_per_second = 700;
+    let mut quirks = QuirkProfile::default();
+    let mut font = FontStyle::default();
+    let mut rng = RngOptions::default();
+    let mut error_policies = ErrorPolicies::default();
+    let mut mute = false;
+
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(None),
+            "-b" | "--blocks" => blocks = value(&arg, args.next())?.parse()?,
+            "--hold" => hold_ms = parse_number(&arg, args.next())?,
+            "-i" | "--ips" => instructions_per_second = parse_number(&arg, args.next())?,
+            "-q" | "--quirks" => quirks = value(&arg, args.next())?.parse()?,
+            "-f" | "--font" => font = value(&arg, args.next())?.parse()?,
+            "--rng" => rng.kind = value(&arg, args.next())?.parse()?,
+            "--seed" => rng.seed = Some(parse_number(&arg, args.next())?),
+            "-e" | "--on-error" => error_policies.parse_list(&value(&arg, args.next())?)?,
+            "-m" | "--mute" => mute = true,
+            _ if arg.starts_with('-') && arg.len() > 1 => return Err(format!("unknown option '{}'", arg)),
+            _ if rom_path.is_none() => rom_path = Some(arg),
+            _ => return Err(format!("unexpected argument '{}'", arg)),
+        }
+    }
+
+    let rom_path = rom_path.ok_or("missing ROM path")?;
+    if instructions_per_second == 0 {
+        return Err("--ips must be at least 1".to_string());
+    }
+    if hold_ms == 0 {
+        return Err("--hold must be at least 1".to_string());
+    }
+
+    Ok(Some(Options {
+        rom_path,
+        blocks,
+        hold: Duration::from_millis(hold_ms),
+        instructions_per_second,
+        quirks,
+        font,
+        rng,
+        error_policies,
+        mute,
+    }))
+}
+
+fn run(options: &Options) -> Result<(), String> {
+    let rom_data = fs::read(&options.rom_path)
+        .map_err(|e| format!("cannot read ROM '{}': {}", options.rom_path, e))?;
+
+    let mut chip8 = if options.quirks == QuirkProfile::XoChip {
+        Chip8::with_memory_size(XO_CHIP_MEMORY_SIZE)
+    } else {
+        Chip8::new()
+    };
+    chip8.set_font(options.font, DEFAULT_FONT_BASE).map_err(|e| format!("cannot install the font: {}", e))?;
+    chip8.error_policies = options.error_policies;
+    chip8.quirks = options.quirks.quirks();
+    chip8.rng = options.rng.create();
+    chip8.load_rom(&rom_data)
+        .map_err(|e| format!("cannot load ROM '{}': {}", options.rom_path, e))?;
+
+    let _terminal = RawTerminal::enter()?;
+    let input = spawn_input();
+    let mut stdout = io::stdout().lock();
+
+    let mut scheduler = Scheduler::new(options.instructions_per_second);
+    let mut pacer = FramePacer::new(FRAME_RATE);
+    // When each key is released again, if it is held.
+    let mut held: [Option<Instant>; 16] = [None; 16];
+    let mut paused = false;
+    let mut sounding = false;
+    let mut status = String::new();
+    let mut last_frame = String::new();
+
+    'running: loop {
+        loop {
+            let bytes = match input.try_recv() {
+                Ok(bytes) => bytes,
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => break 'running,
+            };
+            // A lone read is usually one keystroke; anything starting with
+            // Escape is an arrow or function key sequence and is skipped.
+            for &byte in bytes.iter().take_while(|&&byte| byte != ESCAPE) {
+                match byte.to_ascii_lowercase() {
+                    CTRL_C => break 'running,
+                    CTRL_R => {
+                        chip8.reset();
+                        held = [None; 16];
+                        paused = false;
+                        status.clear();
+                    }
+                    b'p' => paused = !paused,
+                    byte => {
+                        if let Some(key) = KEYS.iter().position(|&k| k == byte) {
+                            chip8.set_key(key, true);
+                            held[key] = Some(Instant::now() + options.hold);
+                        }
+                    }
+                }
+            }
+        }
+
+        let now = Instant::now();
+        for (key, release) in held.iter_mut().enumerate() {
+            if release.is_some_and(|release| release <= now) {
+                chip8.set_key(key, false);
+                *release = None;
+            }
+        }
+
+        if paused {
+            scheduler.reset_clock();
+        } else {
+            for _ in 0..scheduler.frames_due() {
+                let cycles = scheduler.cycles_for_frame();
+                if let Err(e) = chip8.run_frame(cycles) {
+                    status = format!("error: {} (paused; Ctrl+R resets)", e);
+                    paused = true;
+                    break;
+                }
+                if chip8.exited {
+                    status = "program exited (paused; Ctrl+R resets)".to_string();
+                    paused = true;
+                    break;
+                }
+            }
+        }
+
+        if chip8.sound_active() && !sounding && !options.mute && !paused {
+            stdout.write_all(b"\x07").map_err(|e| e.to_string())?;
+        }
+        sounding = chip8.sound_active();
+
+        let state = if !status.is_empty() {
+            status.as_str()
+        } else if paused {
+            "paused"
+        } else if chip8.awaiting_key() {
+            "waiting for a key"
+        } else {
+            "running"
+        };
+        let frame = draw(&chip8, options.blocks, &held, state);
+        if frame != last_frame {
+            stdout.write_all(frame.as_bytes())
+                .and_then(|_| stdout.flush())
+                .map_err(|e| format!("cannot write to the terminal: {}", e))?;
+            last_frame = frame;
+        }
+
+        pacer.wait();
+    }
+    Ok(())
+}
+
+// Puts the terminal in raw mode on the alternate screen, and restores it when
+// dropped.
+struct RawTerminal {
+    saved: String,
+}
+
+impl RawTerminal {
+    fn enter() -> Result<RawTerminal, String> {
+        let saved = stty(&["-g"])?;
+        stty(&["raw", "-echo"])?;
+        print!("\x1b[?1049h\x1b[?25l\x1b[2J");
+        Ok(RawTerminal { saved: saved.trim().to_string() })
+    }
+}
+
+impl Drop for RawTerminal {
+    fn drop(&mut self) {
+        print!("\x1b[?25h\x1b[?1049l");
+        let _ = io::stdout().flush();
+        let _ = stty(&[&self.saved]);
+    }
+}
+
+fn stty(args: &[&str]) -> Result<String, String> {
+    let output = Command::new("stty")
+        .args(args)
+        .stdin(Stdio::inherit())
+        .output()
+        .map_err(|e| format!("cannot run stty: {}", e))?;
+    if !output.status.success() {
+        return Err(format!("stty failed, is stdin a terminal? {}", String::from_utf8_lossy(&output.stderr).trim()));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+// Stdin blocks, so it is read on its own thread; each read is sent whole.
+fn spawn_input() -> Receiver<Vec<u8>> {
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        let mut stdin = io::stdin();
+        let mut buffer = [0; 64];
+        loop {
+            match stdin.read(&mut buffer) {
+                Ok(0) | Err(_) => break,
+                Ok(n) => {
+                    if sender.send(buffer[..n].to_vec()).is_err() {
+                        break;
+                    }
+                }
+            }
+        }
+    });
+    receiver
+}
+
+// The whole screen as one write: the display in a box with the register
+// panel beside it. Any lit bitplane counts as a lit pixel.
+fn draw(chip8: &Chip8, blocks: Blocks, held: &[Option<Instant>; 16], state: &str) -> String {
+    let screen = match blocks {
+        Blocks::Half => half_blocks(chip8),
+        Blocks::Braille => braille(chip8),
+    };
+    let columns = screen.first().map_or(0, |row| row.chars().count());
+    let panel = panel(chip8, held, state);
+
+    let mut text = String::from("\x1b[H");
+    let border = "─".repeat(columns);
+    let mut lines = vec![format!("┌{}┐", border)];
+    lines.extend(screen.iter().map(|row| format!("│{}│", row)));
+    lines.push(format!("└{}┘", border));
+    let blank = " ".repeat(columns + 2);
+    for index in 0..lines.len().max(panel.len()) {
+        let left = lines.get(index).map_or(blank.as_str(), String::as_str);
+        let right = panel.get(index).map_or("", String::as_str);
+        let _ = write!(text, "{}  {}\x1b[K\r\n", left, right);
+    }
+    text.push_str("\x1b[J");
+    text
+}
+
+fn lit(chip8: &Chip8, x: usize, y: usize) -> bool {
+    chip8.gfx[y * chip8.width() + x] != 0
+}
+
+fn half_blocks(chip8: &Chip8) -> Vec<String> {
+    (0..chip8.height())
+        .step_by(2)
+        .map(|y| {
+            (0..chip8.width())
+                .map(|x| match (lit(chip8, x, y), lit(chip8, x, y + 1)) {
+                    (true, true) => '█',
+                    (true, false) => '▀',
+                    (false, true) => '▄',
+                    (false, false) => ' ',
+                })
+                .collect()
+        })
+        .collect()
+}
+
+// Dots are numbered down the left column, then down the right one, with the
+// bottom row added later as dots 7 and 8.
+const BRAILLE_DOTS: [[u32; 2]; 4] = [[0x01, 0x08], [0x02, 0x10], [0x04, 0x20], [0x40, 0x80]];
+
+fn braille(chip8: &Chip8) -> Vec<String> {
+    (0..chip8.height())
+        .step_by(4)
+        .map(|y| {
+            (0..chip8.width())
+                .step_by(2)
+                .map(|x| {
+                    let mut dots = 0;
+                    for (row, bits) in BRAILLE_DOTS.iter().enumerate() {
+                        for (column, bit) in bits.iter().enumerate() {
+                            if lit(chip8, x + column, y + row) {
+                                dots |= bit;
+                            }
+                        }
+                    }
+                    char::from_u32(0x2800 + dots).unwrap_or(' ')
+                })
+                .collect()
+        })
+        .collect()
+}
+
+fn panel(chip8: &Chip8, held: &[Option<Instant>; 16], state: &str) -> Vec<String> {
+    let byte = |offset: u16| chip8.memory.get(chip8.pc.wrapping_add(offset) as usize).copied().unwrap_or(0) as u16;
+    let opcode = byte(0) << 8 | byte(1);
+    let operand = byte(2) << 8 | byte(3);
+
+    let mut lines = vec![
+        format!("PC {:04X}  I {:04X}  SP {:X}", chip8.pc, chip8.i, chip8.sp),
+        format!("DT {:02X}    ST {:02X}", chip8.delay_timer, chip8.sound_timer),
+        String::new(),
+    ];
+    for (row, registers) in chip8.v.chunks(4).enumerate() {
+        let mut line = String::new();
+        for (column, value) in registers.iter().enumerate() {
+            let _ = write!(line, "V{:X} {:02X}  ", row * 4 + column, value);
+        }
+        lines.push(line.trim_end().to_string());
+    }
+    lines.push(String::new());
+    lines.push(format!("{:04X}  {}", opcode, decode(opcode, operand).display()));
+    let keys: String = held
+        .iter()
+        .enumerate()
+        .map(|(key, release)| if release.is_some() { format!("{:X}", key) } else { ".".to_string() })
+        .collect();
+    lines.push(format!("keys {}", keys));
+    lines.push(String::new());
+    lines.push(state.to_string());
+    lines.push("Ctrl+C quits, P pauses".to_string());
+    lines
+}
@@ -0,0 +1,254 @@
+use chip8_core::audio::ToneSettings;
+use chip8_core::chip8::PROGRAM_START;
+use chip8_core::error::ErrorPolicies;
+use chip8_core::font::{FontStyle, BIG_FONT_SIZE, DEFAULT_FONT_BASE, FONT_SIZE};
+use chip8_core::image::PALETTE;
+use chip8_core::quirks::QuirkProfile;
+use chip8_rust::args::{parse_address, parse_number, value, RngOptions};
+
+use crate::sdl_video::{parse_color, Palette, Scaling};
+
+pub const USAGE: &str = "\
+Usage: chip8-rust [OPTIONS] <ROM>
+
+Options:
+  -s, --scale <N>             Initial window scale factor (default: 10)
+      --scaling <MODE>        Fit the screen to the window: integer, aspect (default: aspect)
+      --fullscreen            Start in fullscreen (toggle with F11)
+      --fg <COLOR>            Foreground colour as RRGGBB (default: FFFFFF)
+      --bg <COLOR>            Background colour as RRGGBB (default: 000000)
+      --palette <COLORS>      Comma-separated colours for pixel values 0 to 15, for
+                              XO-CHIP's bit planes; --fg and --bg override 1 and 0
+      --ghosting <PERCENT>    Brightness a pixel keeps per frame after turning off,
+                              from 0 to 99, to hide XOR flicker (default: 0)
+  -i, --ips <N>               Instructions executed per second (default: 700)
+  -c, --cycles-per-frame <N>  Instructions per 60 Hz frame, an alternative to --ips
+      --turbo <X>             Speed multiplier while Tab is held (default: 4)
+      --slow <X>              Speed multiplier for slow motion, toggled with L (default: 0.25)
+  -q, --quirks <PROFILE>      Quirk profile: vip, chip48, schip10, schip11, xochip (default: vip)
+  -f, --font <STYLE>          Built-in font: standard, vip, dream6800, eti660 (default: standard)
+      --font-base <ADDR>      Address the font is loaded at (default: 0x050)
+      --rng <KIND>            Random number generator: xorshift, vip (default: xorshift)
+      --seed <N>              Seed the random number generator for reproducible runs
+  -e, --on-error <POLICY>     Fault handling: halt, ignore or wrap, optionally per kind
+                              (opcode, stack, memory, pc), e.g. halt,stack=wrap (default: halt)
+      --tone <HZ>             Buzzer frequency (default: 440)
+      --volume <PERCENT>      Buzzer volume from 0 to 100 (default: 25)
+      --waveform <SHAPE>      Buzzer waveform: square, sine, triangle, sawtooth (default: square)
+  -m, --mute                  Start with sound muted (toggle with M)
+      --wav <PATH>            Write the buzzer output to a WAV file instead of playing it
+      --record <PATH>         Record the session as a movie file
+      --replay <PATH>         Replay a movie file with the configuration it was recorded with
+      --keymap <PATH>         Key bindings file (default: ~/.config/chip8-rust/keymap.toml)
+      --rewind-budget <MB>    Memory kept for rewinding, 0 to disable (default: 32)
+      --rewind-speed <N>      Frames stepped back per frame while rewinding (default: 2)
+  -d, --debug                 Start in the debugger console (F12 breaks in at any time)
+  -p, --paused                Start with emulation paused (toggle with P)
+      --headless              Run without opening a window and print the final screen
+      --frames <N>            Stop after N frames (required with --headless unless replaying)
+  -h, --help                  Print this help
+
+Keys:
+  Escape quits, P pauses, M mutes, Tab fast-forwards, L toggles slow motion,
+  Backspace rewinds while held, F12 opens the debugger console on stdin,
+  Ctrl+R resets the machine, F10 opens the key rebinding screen, F11 toggles
+  fullscreen.
+  Shift+F1 to Shift+F9 save the machine state to slots 1 to 9 (ROM.state1 and
+  so on, next to the ROM) and F1 to F9 load them again.
+  Loading a state, rewinding and breaking into the debugger end a recording or
+  replay, since a movie can't capture them; the keypad is ignored while replaying.
+  The keypad defaults to 1234/QWER/ASDF/ZXCV and a game controller's d-pad
+  drives 5, 7, 8 and 9. See the README for the keymap file format.";
+
+#[derive(Debug)]
+pub struct Options {
+    pub rom_path: String,
+    pub scale: u32,
+    pub scaling: Scaling,
+    pub fullscreen: bool,
+    pub palette: Palette,
+    // Fraction of brightness kept per frame, from 0 to 0.99.
+    pub ghosting: f32,
+    pub instructions_per_second: u32,
+    pub turbo_speed: f64,
+    pub slow_speed: f64,
+    pub quirks: QuirkProfile,
+    pub font: FontStyle,
+    pub font_base: u16,
+    pub rng: RngOptions,
+    pub error_policies: ErrorPolicies,
+    pub tone: ToneSettings,
+    pub wav_path: Option<String>,
+    pub record_path: Option<String>,
+    pub replay_path: Option<String>,
+    pub keymap_path: Option<String>,
+    // In bytes.
+    pub rewind_budget: usize,
+    pub rewind_speed: usize,
+    pub debug: bool,
+    pub start_paused: bool,
+    pub headless: bool,
+    pub frames: Option<u64>,
+}
+
+pub enum Command {
+    Run(Box<Options>),
+    Help,
+}
+
+pub fn parse<I: IntoIterator<Item = String>>(args: I) -> Result<Command, String> {
+    let mut rom_path = None;
+    let mut scale = 10;
+    let mut scaling = Scaling::default();
+    let mut fullscreen = false;
+    let mut palette = PALETTE;
+    let mut foreground = None;
+    let mut background = None;
+    let mut ghosting: f32 = 0.0;
+    let mut instructions_per_second = 700;
+    // Whichever of --ips and --cycles-per-frame came last, for errors.
+    let mut speed_option = "--ips";
+    let mut turbo_speed: f64 = 4.0;
+    let mut slow_speed: f64 = 0.25;
+    let mut quirks = QuirkProfile::default();
+    let mut font = FontStyle::default();
+    let mut font_base = DEFAULT_FONT_BASE;
+    let mut rng = RngOptions::default();
+    let mut error_policies = ErrorPolicies::default();
+    let mut tone = ToneSettings::default();
+    let mut wav_path = None;
+    let mut record_path = None;
+    let mut replay_path = None;
+    let mut keymap_path = None;
+    let mut rewind_budget: usize = 32;
+    let mut rewind_speed = 2;
+    let mut debug = false;
+    let mut start_paused = false;
+    let mut headless = false;
+    let mut frames = None;
+
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(Command::Help),
+            "-s" | "--scale" => scale = parse_number(&arg, args.next())?,
+            "--scaling" => scaling = value(&arg, args.next())?.parse()?,
+            "--fullscreen" => fullscreen = true,
+            "--fg" => foreground = Some(parse_color(&value(&arg, args.next())?)?),
+            "--bg" => background = Some(parse_color(&value(&arg, args.next())?)?),
+            "--palette" => {
+                let colors = value(&arg, args.next())?;
+                let colors: Vec<&str> = colors.split(',').map(str::trim).collect();
+                if colors.len() > palette.len() {
+                    return Err(format!("--palette takes at most {} colours", palette.len()));
+                }
+                for (entry, color) in palette.iter_mut().zip(colors) {
+                    *entry = parse_color(color)?;
+                }
+            }
+            "--ghosting" => ghosting = parse_number(&arg, args.next())?,
+            "-i" | "--ips" => {
+                instructions_per_second = parse_number(&arg, args.next())?;
+                speed_option = "--ips";
+            }
+            "-c" | "--cycles-per-frame" => {
+                instructions_per_second = parse_number::<u32>(&arg, args.next())?.saturating_mul(60);
+                speed_option = "--cycles-per-frame";
+            }
+            "--turbo" => turbo_speed = parse_number(&arg, args.next())?,
+            "--slow" => slow_speed = parse_number(&arg, args.next())?,
+            "-q" | "--quirks" => quirks = value(&arg, args.next())?.parse()?,
+            "-f" | "--font" => font = value(&arg, args.next())?.parse()?,
+            "--font-base" => font_base = parse_address(&arg, args.next())?,
+            "--rng" => rng.kind = value(&arg, args.next())?.parse()?,
+            "--seed" => rng.seed = Some(parse_number(&arg, args.next())?),
+            "-e" | "--on-error" => error_policies.parse_list(&value(&arg, args.next())?)?,
+            "--tone" => tone.frequency = parse_number(&arg, args.next())?,
+            "--volume" => tone.volume = parse_number::<f32>(&arg, args.next())? / 100.0,
+            "--waveform" => tone.waveform = value(&arg, args.next())?.parse()?,
+            "-m" | "--mute" => tone.muted = true,
+            "--wav" => wav_path = Some(value(&arg, args.next())?),
+            "--record" => record_path = Some(value(&arg, args.next())?),
+            "--replay" => replay_path = Some(value(&arg, args.next())?),
+            "--keymap" => keymap_path = Some(value(&arg, args.next())?),
+            "--rewind-budget" => rewind_budget = parse_number(&arg, args.next())?,
+            "--rewind-speed" => rewind_speed = parse_number(&arg, args.next())?,
+            "-d" | "--debug" => debug = true,
+            "-p" | "--paused" => start_paused = true,
+            "--headless" => headless = true,
+            "--frames" => frames = Some(parse_number(&arg, args.next())?),
+            _ if arg.starts_with('-') && arg.len() > 1 => {
+                return Err(format!("unknown option '{}'", arg));
+            }
+            _ => {
+                if rom_path.is_some() {
+                    return Err(format!("unexpected argument '{}'", arg));
+                }
+                rom_path = Some(arg);
+            }
+        }
+    }
+
+    let rom_path = rom_path.ok_or("missing ROM path")?;
+    if scale == 0 {
+        return Err("--scale must be at least 1".to_string());
+    }
+    if !(0.0..100.0).contains(&ghosting) {
+        return Err("--ghosting must be between 0 and 99".to_string());
+    }
+    if let Some(color) = background {
+        palette[0] = color;
+    }
+    if let Some(color) = foreground {
+        palette[1] = color;
+    }
+    if instructions_per_second == 0 {
+        return Err(format!("{} must be at least 1", speed_option));
+    }
+    if !turbo_speed.is_finite() || turbo_speed <= 0.0 || !slow_speed.is_finite() || slow_speed <= 0.0 {
+        return Err("--turbo and --slow must be positive".to_string());
+    }
+    if font_base as usize + FONT_SIZE + BIG_FONT_SIZE > PROGRAM_START {
+        return Err(format!("--font-base must leave room for the fonts below {:#X}", PROGRAM_START));
+    }
+    if !tone.frequency.is_finite() || tone.frequency <= 0.0 {
+        return Err("--tone must be a positive frequency".to_string());
+    }
+    if !(0.0..=1.0).contains(&tone.volume) {
+        return Err("--volume must be between 0 and 100".to_string());
+    }
+    if rewind_speed == 0 {
+        return Err("--rewind-speed must be at least 1".to_string());
+    }
+    if headless && frames.is_none() && replay_path.is_none() {
+        return Err("--headless requires --frames or --replay".to_string());
+    }
+
+    Ok(Command::Run(Box::new(Options {
+        rom_path,
+        scale,
+        scaling,
+        fullscreen,
+        palette,
+        ghosting: ghosting / 100.0,
+        instructions_per_second,
+        turbo_speed,
+        slow_speed,
+        quirks,
+        font,
+        font_base,
+        rng,
+        error_policies,
+        tone,
+        wav_path,
+        record_path,
+        replay_path,
+        keymap_path,
+        rewind_budget: rewind_budget.saturating_mul(1024 * 1024),
+        rewind_speed,
+        debug,
+        start_paused,
+        headless,
+        frames,
+    })))
+}
@@ -0,0 +1,468 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// Hex keys in the order they sit on the keypad, which is also the order the
+// rebinding screen asks for them in.
+pub const KEYPAD_ORDER: [usize; 16] = [0x1, 0x2, 0x3, 0xC, 0x4, 0x5, 0x6, 0xD, 0x7, 0x8, 0x9, 0xE, 0xA, 0x0, 0xB, 0xF];
+
+// Indexed by hex key. The keypad's 4x4 grid on the left of a QWERTY keyboard.
+const DEFAULT_KEYBOARD: [&str; 16] = ["X", "1", "2", "3", "Q", "W", "E", "A", "S", "D", "Z", "C", "4", "R", "F", "V"];
+
+// The d-pad drives 5, 7, 8 and 9, the keys most games put under W, A, S and D.
+const DEFAULT_CONTROLLER: [&[&str]; 16] = [
+    &["x"], &[], &[], &[], &["b"], &["dpup"], &["a"], &["dpleft"],
+    &["dpdown"], &["dpright"], &["y"], &[], &[], &[], &[], &["start"],
+];
+
+// Keys the window handles itself, by SDL key name; they can't be bound to the
+// keypad. Ctrl+R only resets with Ctrl held, so R stays free.
+const HOTKEYS: [&str; 18] = [
+    "Escape", "P", "M", "L", "Tab", "Backspace",
+    "F1", "F2", "F3", "F4", "F5", "F6", "F7", "F8", "F9", "F10", "F11", "F12",
+];
+
+pub fn is_hotkey(name: &str) -> bool {
+    HOTKEYS.iter().any(|hotkey| hotkey.eq_ignore_ascii_case(name))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Device {
+    Keyboard,
+    Controller,
+}
+
+impl Device {
+    fn section(&self) -> &'static str {
+        match self {
+            Device::Keyboard => "keyboard",
+            Device::Controller => "controller",
+        }
+    }
+}
+
+// Key names per hex key; `None` leaves a key to the section below it.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Bindings {
+    pub keyboard: [Option<Vec<String>>; 16],
+    pub controller: [Option<Vec<String>>; 16],
+}
+
+impl Bindings {
+    fn keys(&self, device: Device) -> &[Option<Vec<String>>; 16] {
+        match device {
+            Device::Keyboard => &self.keyboard,
+            Device::Controller => &self.controller,
+        }
+    }
+
+    fn device(&mut self, device: Device) -> &mut [Option<Vec<String>>; 16] {
+        match device {
+            Device::Keyboard => &mut self.keyboard,
+            Device::Controller => &mut self.controller,
+        }
+    }
+}
+
+// The bindings in effect for one ROM.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Layout {
+    pub keyboard: [Vec<String>; 16],
+    pub controller: [Vec<String>; 16],
+}
+
+// A key binding file:
+//
+//   [keyboard]
+//   1 = "1"
+//   C = ["4", "Keypad 4"]
+//
+//   [controller]
+//   5 = "dpup"
+//
+//   [rom."pong.ch8".keyboard]
+//   1 = "W"
+//
+// Keys are the hex keys 0 to F; values are SDL key names or game controller
+// button names. `rom` tables override single keys for ROMs with that file
+// name. Keys not bound anywhere fall back to the built-in layout.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Keymap {
+    pub global: Bindings,
+    pub roms: BTreeMap<String, Bindings>,
+}
+
+// `$XDG_CONFIG_HOME/chip8-rust/keymap.toml`, falling back to `~/.config`.
+pub fn default_path() -> Option<PathBuf> {
+    let config = env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".config")))?;
+    Some(config.join("chip8-rust").join("keymap.toml"))
+}
+
+impl Keymap {
+    // A missing file is an empty keymap, i.e. the built-in layout.
+    pub fn load(path: &Path) -> Result<Keymap, String> {
+        match fs::read_to_string(path) {
+            Ok(text) => Keymap::parse(&text).map_err(|e| format!("{}: {}", path.display(), e)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Keymap::default()),
+            Err(e) => Err(format!("cannot read keymap '{}': {}", path.display(), e)),
+        }
+    }
+
+    // Comments in the file are not kept.
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).map_err(|e| format!("cannot create '{}': {}", dir.display(), e))?;
+        }
+        fs::write(path, self.to_toml()).map_err(|e| format!("cannot write keymap '{}': {}", path.display(), e))
+    }
+
+    pub fn parse(text: &str) -> Result<Keymap, String> {
+        let mut keymap = Keymap::default();
+        let mut section: Option<(Option<String>, Device)> = None;
+
+        for (index, line) in text.lines().enumerate() {
+            let error = |message: String| format!("line {}: {}", index + 1, message);
+            let mut cursor = Cursor { rest: line };
+            cursor.skip_space();
+            if cursor.at_end() {
+                continue;
+            }
+
+            if cursor.eat('[') {
+                let mut parts = vec![cursor.key().map_err(error)?];
+                while cursor.eat('.') {
+                    parts.push(cursor.key().map_err(error)?);
+                }
+                if !cursor.eat(']') || !cursor.at_end() {
+                    return Err(error("expected ']' after the table name".to_string()));
+                }
+                let device = |name: &str| match name {
+                    "keyboard" => Some(Device::Keyboard),
+                    "controller" => Some(Device::Controller),
+                    _ => None,
+                };
+                section = match parts.as_slice() {
+                    [name] => device(name).map(|device| (None, device)),
+                    [rom, file, name] if rom == "rom" => device(name).map(|device| (Some(file.clone()), device)),
+                    _ => None,
+                };
+                if section.is_none() {
+                    return Err(error(format!("unknown table '{}'", parts.join("."))));
+                }
+                continue;
+            }
+
+            let key = cursor.key().map_err(error)?;
+            let hex = match usize::from_str_radix(&key, 16) {
+                Ok(hex) if hex < 16 && key.len() == 1 => hex,
+                _ => return Err(error(format!("'{}' is not a hex key from 0 to F", key))),
+            };
+            if !cursor.eat('=') {
+                return Err(error("expected '=' after the key".to_string()));
+            }
+            let names = cursor.value().map_err(error)?;
+            if !cursor.at_end() {
+                return Err(error("unexpected text after the value".to_string()));
+            }
+
+            let Some((rom, device)) = &section else {
+                return Err(error("bindings must be inside a [keyboard] or [controller] table".to_string()));
+            };
+            if *device == Device::Keyboard {
+                if let Some(hotkey) = names.iter().find(|name| is_hotkey(name)) {
+                    return Err(error(format!("'{}' is a hotkey and can't be bound to key {:X}", hotkey, hex)));
+                }
+            }
+            let bindings = match rom {
+                Some(rom) => keymap.roms.entry(rom.clone()).or_default(),
+                None => &mut keymap.global,
+            };
+            let keys = bindings.device(*device);
+            if keys[hex].is_some() {
+                return Err(error(format!("key {:X} is bound twice in this table", hex)));
+            }
+            for (other, bound) in keys.iter().enumerate() {
+                let shared = bound.iter().flatten().find(|bound| names.iter().any(|name| name.eq_ignore_ascii_case(bound)));
+                if let Some(name) = shared {
+                    return Err(error(format!("'{}' is already bound to key {:X}", name, other)));
+                }
+            }
+            keys[hex] = Some(names);
+        }
+        Ok(keymap)
+    }
+
+    pub fn to_toml(&self) -> String {
+        let mut text = String::new();
+        write_section(&mut text, "keyboard", &self.global.keyboard);
+        write_section(&mut text, "controller", &self.global.controller);
+        for (rom, bindings) in &self.roms {
+            for device in [Device::Keyboard, Device::Controller] {
+                let name = format!("rom.{}.{}", quote(rom), device.section());
+                write_section(&mut text, &name, bindings.keys(device));
+            }
+        }
+        text
+    }
+
+    // The ROM's overrides, then the global tables, then the built-in layout.
+    // A name bound at a more specific level is taken off keys bound at a
+    // less specific one, so overriding one key can't leave a name on two.
+    pub fn resolve(&self, rom: &str) -> Layout {
+        let levels = [self.roms.get(rom), Some(&self.global)];
+        let device_layout = |device: Device| -> [Vec<String>; 16] {
+            let picks: [(usize, Vec<String>); 16] = std::array::from_fn(|key| {
+                levels
+                    .iter()
+                    .enumerate()
+                    .find_map(|(level, bindings)| {
+                        let names = (*bindings)?.keys(device)[key].clone()?;
+                        Some((level, names))
+                    })
+                    .unwrap_or_else(|| {
+                        let names = match device {
+                            Device::Keyboard => vec![DEFAULT_KEYBOARD[key].to_string()],
+                            Device::Controller => DEFAULT_CONTROLLER[key].iter().map(|name| name.to_string()).collect(),
+                        };
+                        (levels.len(), names)
+                    })
+            });
+            std::array::from_fn(|key| {
+                let (level, names) = &picks[key];
+                names
+                    .iter()
+                    .filter(|name| {
+                        !picks.iter().any(|(other, others)| {
+                            other < level && others.iter().any(|bound| bound.eq_ignore_ascii_case(name))
+                        })
+                    })
+                    .cloned()
+                    .collect()
+            })
+        };
+        Layout {
+            keyboard: device_layout(Device::Keyboard),
+            controller: device_layout(Device::Controller),
+        }
+    }
+
+    // Binds `name` to `key` alone, taking it off any other key. The change
+    // goes into the ROM's table if it has one, otherwise the global one.
+    pub fn bind(&mut self, rom: &str, device: Device, key: usize, name: &str) {
+        let layout = self.resolve(rom);
+        let resolved = match device {
+            Device::Keyboard => layout.keyboard,
+            Device::Controller => layout.controller,
+        };
+        let bindings = match self.roms.get_mut(rom) {
+            Some(bindings) => bindings,
+            None => &mut self.global,
+        };
+        let bindings = bindings.device(device);
+        for (other, names) in resolved.iter().enumerate() {
+            if other != key && names.iter().any(|bound| bound.eq_ignore_ascii_case(name)) {
+                bindings[other] = Some(names.iter().filter(|bound| !bound.eq_ignore_ascii_case(name)).cloned().collect());
+            }
+        }
+        bindings[key] = Some(vec![name.to_string()]);
+    }
+}
+
+fn write_section(text: &mut String, name: &str, keys: &[Option<Vec<String>>; 16]) {
+    if keys.iter().all(Option::is_none) {
+        return;
+    }
+    if !text.is_empty() {
+        text.push('\n');
+    }
+    text.push_str(&format!("[{}]\n", name));
+    for (key, names) in keys.iter().enumerate() {
+        let Some(names) = names else { continue };
+        let value = match names.as_slice() {
+            [name] => quote(name),
+            names => format!("[{}]", names.iter().map(|name| quote(name)).collect::<Vec<_>>().join(", ")),
+        };
+        text.push_str(&format!("{:X} = {}\n", key, value));
+    }
+}
+
+fn quote(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+// Reads the subset of TOML a keymap needs: bare and quoted keys, strings,
+// arrays of strings and `#` comments.
+struct Cursor<'a> {
+    rest: &'a str,
+}
+
+impl Cursor<'_> {
+    fn skip_space(&mut self) {
+        self.rest = self.rest.trim_start();
+        if self.rest.starts_with('#') {
+            self.rest = "";
+        }
+    }
+
+    fn at_end(&mut self) -> bool {
+        self.skip_space();
+        self.rest.is_empty()
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        self.skip_space();
+        match self.rest.strip_prefix(c) {
+            Some(rest) => {
+                self.rest = rest;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn key(&mut self) -> Result<String, String> {
+        self.skip_space();
+        if self.rest.starts_with('"') {
+            return self.string();
+        }
+        let len = self.rest
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_' || c == '-'))
+            .unwrap_or(self.rest.len());
+        if len == 0 {
+            return Err("expected a key".to_string());
+        }
+        let (key, rest) = self.rest.split_at(len);
+        self.rest = rest;
+        Ok(key.to_string())
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        self.skip_space();
+        let mut chars = self.rest.strip_prefix('"').ok_or("expected a string")?.char_indices();
+        let mut value = String::new();
+        while let Some((index, c)) = chars.next() {
+            match c {
+                '"' => {
+                    self.rest = &self.rest[index + 2..];
+                    return Ok(value);
+                }
+                '\\' => match chars.next() {
+                    Some((_, '"')) => value.push('"'),
+                    Some((_, '\\')) => value.push('\\'),
+                    _ => return Err("unsupported escape in string".to_string()),
+                },
+                c => value.push(c),
+            }
+        }
+        Err("unterminated string".to_string())
+    }
+
+    // A string or an array of strings.
+    fn value(&mut self) -> Result<Vec<String>, String> {
+        if !self.eat('[') {
+            return Ok(vec![self.string()?]);
+        }
+        let mut values = Vec::new();
+        while !self.eat(']') {
+            values.push(self.string()?);
+            if !self.eat(',') && !self.rest.trim_start().starts_with(']') {
+                return Err("expected ',' or ']' in array".to_string());
+            }
+        }
+        Ok(values)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEYMAP: &str = r#"
+# Arrows for the usual movement keys.
+[keyboard]
+5 = "Up"
+8 = ["Down", "Keypad 2"]   # either works
+a = "Space"
+
+[controller]
+5 = "dpup"
+
+[rom."pong.ch8".keyboard]
+1 = "W"
+"4" = "S"
+"#;
+
+    fn names(names: &[&str]) -> Option<Vec<String>> {
+        Some(names.iter().map(|name| name.to_string()).collect())
+    }
+
+    fn error(text: &str) -> String {
+        Keymap::parse(text).expect_err("should not parse")
+    }
+
+    #[test]
+    fn parses_a_keymap() {
+        let keymap = Keymap::parse(KEYMAP).expect("parses");
+        assert_eq!(keymap.global.keyboard[0x5], names(&["Up"]));
+        assert_eq!(keymap.global.keyboard[0x8], names(&["Down", "Keypad 2"]));
+        assert_eq!(keymap.global.keyboard[0xA], names(&["Space"]));
+        assert_eq!(keymap.global.controller[0x5], names(&["dpup"]));
+        let pong = &keymap.roms["pong.ch8"];
+        assert_eq!((pong.keyboard[0x1].clone(), pong.keyboard[0x4].clone()), (names(&["W"]), names(&["S"])));
+
+        assert_eq!(Keymap::parse(&keymap.to_toml()), Ok(keymap.clone()));
+        assert_eq!(Keymap::parse("# nothing bound\n\n"), Ok(Keymap::default()));
+    }
+
+    #[test]
+    fn resolves_rom_then_global_then_built_in() {
+        let keymap = Keymap::parse(KEYMAP).expect("parses");
+        let pong = keymap.resolve("pong.ch8");
+        assert_eq!(pong.keyboard[0x1], ["W"]);
+        assert_eq!(pong.keyboard[0x5], ["Up"]);
+        // W was the built-in 5, so only the ROM's binding keeps it.
+        assert_eq!(keymap.resolve("pong.ch8").keyboard.iter().filter(|names| names.contains(&"W".to_string())).count(), 1);
+        assert_eq!(keymap.resolve("other.ch8").keyboard[0x1], ["1"]);
+        assert_eq!(keymap.resolve("other.ch8").keyboard[0x0], ["X"]);
+    }
+
+    #[test]
+    fn rejects_unknown_keys_and_tables() {
+        assert_eq!(error("[keyboard]\nG = \"A\""), "line 2: 'G' is not a hex key from 0 to F");
+        assert_eq!(error("[keyboard]\n10 = \"A\""), "line 2: '10' is not a hex key from 0 to F");
+        assert_eq!(error("[mouse]"), "line 1: unknown table 'mouse'");
+        assert_eq!(error("[rom.\"a.ch8\"]"), "line 1: unknown table 'rom.a.ch8'");
+    }
+
+    #[test]
+    fn rejects_duplicate_bindings() {
+        assert_eq!(error("[keyboard]\n1 = \"A\"\n1 = \"B\""), "line 3: key 1 is bound twice in this table");
+        assert_eq!(error("[keyboard]\n1 = \"A\"\n2 = [\"B\", \"a\"]"), "line 3: 'A' is already bound to key 1");
+        // Across tables, the more specific one wins instead.
+        let keymap = Keymap::parse("[keyboard]\n1 = \"A\"\n[rom.\"x.ch8\".keyboard]\n2 = \"A\"").expect("parses");
+        assert_eq!(keymap.resolve("x.ch8").keyboard[0x1], Vec::<String>::new());
+    }
+
+    #[test]
+    fn rejects_hotkeys() {
+        assert_eq!(error("[keyboard]\n5 = \"P\""), "line 2: 'P' is a hotkey and can't be bound to key 5");
+        assert_eq!(error("[rom.\"x.ch8\".keyboard]\n5 = [\"Up\", \"tab\"]"), "line 2: 'tab' is a hotkey and can't be bound to key 5");
+        assert!(Keymap::parse("[controller]\n5 = \"back\"").is_ok());
+    }
+
+    #[test]
+    fn rejects_malformed_lines() {
+        assert_eq!(error("1 = \"A\""), "line 1: bindings must be inside a [keyboard] or [controller] table");
+        assert_eq!(error("[keyboard"), "line 1: expected ']' after the table name");
+        assert_eq!(error("[keyboard]\n1 \"A\""), "line 2: expected '=' after the key");
+        assert_eq!(error("[keyboard]\n1 = \"A"), "line 2: unterminated string");
+        assert_eq!(error("[keyboard]\n1 = \"A\" \"B\""), "line 2: unexpected text after the value");
+        assert_eq!(error("[keyboard]\n1 = [\"A\" \"B\"]"), "line 2: expected ',' or ']' in array");
+        assert_eq!(error("[keyboard]\n1 = A"), "line 2: expected a string");
+        assert_eq!(error("[keyboard]\n1 = \"\\n\""), "line 2: unsupported escape in string");
+    }
+}
@@ -0,0 +1,4 @@
+// Frontend code that doesn't need SDL, shared by the binaries.
+
+pub mod args;
+pub mod keymap;
@@ -1,96 +1,474 @@
 extern crate sdl2;
 use sdl2::event::Event;
-use sdl2::keyboard::Keycode;
-use sdl2::pixels::Color;
-use sdl2::rect::Rect;
+use sdl2::keyboard::{Keycode, Mod};
 
-use std::collections::HashMap;
-use std::time::Duration;
-use std::thread;
-use std::fs::File;
-use std::io::Read;
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process;
 
-mod chip8;
-use chip8::Chip8;
+use chip8_core::audio::{AudioSink, NullSink, WavSink};
+use chip8_core::chip8::{Chip8, XO_CHIP_MEMORY_SIZE};
+use chip8_core::debugger::{ConsoleAction, Debugger, StopReason};
+use chip8_core::headless::{self, Outcome, RunConfig};
+use chip8_core::image;
+use chip8_core::movie::{Desync, Movie, Player, Recorder};
+use chip8_core::quirks::QuirkProfile;
+use chip8_core::rewind::Rewind;
+use chip8_core::savestate;
+use chip8_core::scheduler::{FramePacer, Scheduler, FRAME_RATE};
+use chip8_rust::keymap::{self, Keymap};
+
+mod cli;
+mod sdl_audio;
+mod sdl_input;
+mod sdl_video;
+use cli::{Command, Options};
+use sdl_audio::SdlAudioSink;
+use sdl_input::{InputMap, Rebind, Rebinder};
+use sdl_video::Renderer;
 
 fn main() {
-    let sdl_context = sdl2::init().unwrap();
-    let video_subsystem = sdl_context.video();
-
-    let window = video_subsystem.expect("Failed to get video subsystem").window("Chip8 Emulator", 640, 320)
-        .build()
-        .expect("Failed to create window");
-
-    let mut canvas = window.into_canvas().build().expect("Failed to create a canvas");
-    let mut event_pump = sdl_context.event_pump().expect("Failed to get event pump");
-
-    let file_path = "games/games/Soccer.ch8";
-    let mut file = File::open(file_path).unwrap();
-    let mut rom_data = Vec::new();
-    file.read_to_end(&mut rom_data).unwrap();
-    
-    let mut chip8 = Chip8::new();
-    
-    chip8.load_rom(&rom_data);
-
-    let key_map: HashMap<Keycode, usize> = [
-        (Keycode::Num1, 0x1), 
-        (Keycode::Num2, 0x2), 
-        (Keycode::Num3, 0x3), 
-        (Keycode::Num4, 0xC),
-        (Keycode::Q, 0x4), 
-        (Keycode::W, 0x5), 
-        (Keycode::E, 0x6), 
-        (Keycode::R, 0xD),
-        (Keycode::A, 0x7), 
-        (Keycode::S, 0x8), 
-        (Keycode::D, 0x9), 
-        (Keycode::F, 0xE),
-        (Keycode::Z, 0xA), 
-        (Keycode::X, 0x0), 
-        (Keycode::C, 0xB), 
-        (Keycode::V, 0xF),
-    ].iter().cloned().collect();
+    let options = match cli::parse(env::args().skip(1)) {
+        Ok(Command::Run(options)) => options,
+        Ok(Command::Help) => {
+            println!("{}", cli::USAGE);
+            return;
+        }
+        Err(e) => {
+            eprintln!("error: {}\n\n{}", e, cli::USAGE);
+            process::exit(2);
+        }
+    };
+
+    if let Err(e) = run(&options) {
+        eprintln!("error: {}", e);
+        process::exit(1);
+    }
+}
+
+fn run(options: &Options) -> Result<(), String> {
+    let rom_data = fs::read(&options.rom_path)
+        .map_err(|e| format!("cannot read ROM '{}': {}", options.rom_path, e))?;
+
+    let mut chip8 = if options.quirks == QuirkProfile::XoChip {
+        Chip8::with_memory_size(XO_CHIP_MEMORY_SIZE)
+    } else {
+        Chip8::new()
+    };
+    chip8.set_font(options.font, options.font_base).map_err(|e| format!("cannot install the font: {}", e))?;
+    chip8.error_policies = options.error_policies;
+    chip8.quirks = options.quirks.quirks();
+    chip8.rng = options.rng.create();
+    chip8.load_rom(&rom_data)
+        .map_err(|e| format!("cannot load ROM '{}': {}", options.rom_path, e))?;
+
+    let replay = options.replay_path.as_ref().map(Movie::read_file).transpose()?;
+    if options.headless {
+        run_headless(&mut chip8, options, replay)
+    } else {
+        run_window(&mut chip8, options, replay)
+    }
+}
+
+fn open_wav(options: &Options) -> Result<Option<Box<dyn AudioSink>>, String> {
+    match &options.wav_path {
+        Some(path) => {
+            let sink = WavSink::create(path, options.tone)
+                .map_err(|e| format!("cannot create WAV file '{}': {}", path, e))?;
+            Ok(Some(Box::new(sink)))
+        }
+        None => Ok(None),
+    }
+}
+
+fn run_headless(chip8: &mut Chip8, options: &Options, replay: Option<Movie>) -> Result<(), String> {
+    let mut audio = open_wav(options)?.unwrap_or_else(|| Box::new(NullSink));
+
+    let config = RunConfig {
+        instructions_per_second: options.instructions_per_second,
+        frames: options.frames,
+        record: options.record_path.is_some(),
+        replay,
+        ..RunConfig::default()
+    };
+    let report = headless::run(chip8, &config, audio.as_mut())?;
+    audio.finish()?;
+    if let (Some(movie), Some(path)) = (&report.movie, &options.record_path) {
+        movie.write_file(path)?;
+    }
+    match report.outcome {
+        Outcome::Error(e) => return Err(e.to_string()),
+        Outcome::Desync(desync) => return Err(desync.to_string()),
+        _ => {}
+    }
+
+    print!("{}", image::ascii(chip8));
+    Ok(())
+}
+
+fn run_window(chip8: &mut Chip8, options: &Options, replay: Option<Movie>) -> Result<(), String> {
+    let sdl_context = sdl2::init()?;
+    let video_subsystem = sdl_context.video()?;
+
+    let title = format!("Chip8 Emulator - {} ({})", options.rom_path, options.quirks);
+    let mut window = video_subsystem.window(&title, 64 * options.scale, 32 * options.scale);
+    window.resizable();
+    if options.fullscreen {
+        window.fullscreen_desktop();
+    }
+    let window = window.build().map_err(|e| format!("failed to create window: {}", e))?;
+
+    // Keep pixels sharp when the texture is scaled up.
+    sdl2::hint::set("SDL_RENDER_SCALE_QUALITY", "nearest");
+    let mut canvas = window.into_canvas().build().map_err(|e| format!("failed to create a canvas: {}", e))?;
+    let texture_creator = canvas.texture_creator();
+    let mut renderer = Renderer::new(&texture_creator, options.palette, options.scaling, options.ghosting)?;
+    let mut event_pump = sdl_context.event_pump()?;
+
+    let mut audio: Box<dyn AudioSink> = match open_wav(options)? {
+        Some(sink) => sink,
+        None => {
+            let sink = sdl_context.audio().and_then(|audio| SdlAudioSink::open(&audio, options.tone));
+            match sink {
+                Ok(sink) => Box::new(sink),
+                Err(e) => {
+                    eprintln!("warning: audio disabled: {}", e);
+                    Box::new(NullSink)
+                }
+            }
+        }
+    };
+
+    let keymap_path = options.keymap_path.clone().map(PathBuf::from).or_else(keymap::default_path);
+    let mut keymap = match &keymap_path {
+        Some(path) => Keymap::load(path)?,
+        None => Keymap::default(),
+    };
+    let rom_name = Path::new(&options.rom_path)
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let mut input = InputMap::new(&keymap.resolve(&rom_name))?;
+    let mut rebinding: Option<Rebinder> = None;
+
+    let controller_subsystem = sdl_context.game_controller()?;
+    let mut controllers = Vec::new();
+    for index in 0..controller_subsystem.num_joysticks()? {
+        if controller_subsystem.is_game_controller(index) {
+            match controller_subsystem.open(index) {
+                Ok(controller) => controllers.push(controller),
+                Err(e) => eprintln!("warning: cannot open game controller {}: {}", index, e),
+            }
+        }
+    }
+
+    let mut awaiting_key = false;
+    let mut paused = options.start_paused;
+    let mut muted = options.tone.muted;
+    audio.set_muted(muted || paused);
+    let mut frame: u64 = 0;
+    let mut turbo = false;
+    let mut slow_motion = false;
+    let mut scheduler = Scheduler::new(options.instructions_per_second);
+    let mut pacer = FramePacer::new(FRAME_RATE);
+
+    let mut session = MovieSession { recorder: None, player: None, record_path: options.record_path.clone() };
+    if let Some(movie) = replay {
+        let player = Player::start(movie, chip8)?;
+        scheduler.instructions_per_second = player.movie().instructions_per_second;
+        session.player = Some(player);
+    }
+    if options.record_path.is_some() {
+        session.recorder = Some(Recorder::start(chip8, scheduler.instructions_per_second));
+    }
+
+    let mut history = Rewind::new(options.rewind_budget);
+    history.record(chip8);
+    let mut rewinding = false;
+
+    let mut debugger = Debugger::new();
+    let mut debugging = options.debug;
+    let mut stop = None;
+    if debugging {
+        debugger.attach(chip8);
+        stop = Some(StopReason::Interrupted);
+    }
 
     'running: loop {
         for event in event_pump.poll_iter() {
+            if let Some(rebinder) = &mut rebinding {
+                if let Event::Quit { .. } = event {
+                    break 'running;
+                }
+                match rebinder.handle(&event) {
+                    Rebind::Continue => continue,
+                    Rebind::Cancelled => println!("rebinding cancelled"),
+                    Rebind::Done(rebound) => {
+                        input = InputMap::new(&rebound.resolve(&rom_name))?;
+                        keymap = *rebound;
+                        match &keymap_path {
+                            Some(path) => match keymap.save(path) {
+                                Ok(()) => println!("saved key bindings to '{}'", path.display()),
+                                Err(e) => eprintln!("error: {}", e),
+                            },
+                            None => eprintln!("warning: no keymap path to save to; pass --keymap"),
+                        }
+                    }
+                }
+                rebinding = None;
+                audio.set_muted(muted || paused);
+                continue;
+            }
+
             match event {
                 Event::Quit { .. } |
                 Event::KeyDown { keycode: Some(Keycode::Escape), .. } => break 'running,
-                Event::KeyDown { keycode: Some(keycode), .. } => {
-                    if let Some(&key) = key_map.get(&keycode) {
-                        chip8.set_key(key, true);
+                Event::KeyDown { keycode: Some(Keycode::P), repeat: false, .. } => {
+                    paused = !paused;
+                    audio.set_muted(muted || paused);
+                }
+                Event::KeyDown { keycode: Some(Keycode::Tab), .. } => turbo = true,
+                Event::KeyUp { keycode: Some(Keycode::Tab), .. } => turbo = false,
+                Event::KeyDown { keycode: Some(Keycode::L), repeat: false, .. } => slow_motion = !slow_motion,
+                Event::KeyDown { keycode: Some(Keycode::M), repeat: false, .. } => {
+                    muted = !muted;
+                    audio.set_muted(muted || paused);
+                }
+                Event::KeyDown { keycode: Some(Keycode::Backspace), .. } => rewinding = true,
+                Event::KeyUp { keycode: Some(Keycode::Backspace), .. } => rewinding = false,
+                Event::KeyDown { keycode: Some(Keycode::F10), repeat: false, .. } => {
+                    for key in 0..16 {
+                        chip8.set_key(key, false);
+                    }
+                    audio.set_muted(true);
+                    rebinding = Some(Rebinder::new(keymap.clone(), rom_name.clone()));
+                }
+                Event::KeyDown { keycode: Some(Keycode::F11), repeat: false, .. } => {
+                    sdl_video::toggle_fullscreen(&mut canvas)?;
+                }
+                Event::KeyDown { keycode: Some(Keycode::F12), repeat: false, .. } => {
+                    if !debugging {
+                        debugging = true;
+                        debugger.attach(chip8);
+                    }
+                    stop = Some(StopReason::Interrupted);
+                }
+                Event::KeyDown { keycode: Some(Keycode::R), keymod, repeat: false, .. }
+                    if keymod.intersects(Mod::LCTRLMOD | Mod::RCTRLMOD) => {
+                    chip8.reset();
+                    session.reset()?;
+                }
+                Event::KeyDown { keycode: Some(keycode), keymod, repeat, .. } => {
+                    if let Some(key) = input.key(keycode) {
+                        if session.player.is_none() {
+                            chip8.set_key(key, true);
+                        }
+                    } else if let (Some(slot), false) = (state_slot(keycode), repeat) {
+                        let path = state_path(&options.rom_path, slot);
+                        let result = if keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD) {
+                            save_state(chip8, &path).map(|_| "saved")
+                        } else {
+                            load_state(chip8, &path).map(|_| "loaded")
+                        };
+                        match result {
+                            Ok("loaded") => {
+                                println!("loaded state '{}'", path);
+                                session.stop("state loaded")?;
+                            }
+                            Ok(action) => println!("{} state '{}'", action, path),
+                            Err(e) => eprintln!("error: {}", e),
+                        }
                     }
                 },
                 Event::KeyUp { keycode: Some(keycode), .. } => {
-                    if let Some(&key) = key_map.get(&keycode) {
+                    if let (Some(key), None) = (input.key(keycode), &session.player) {
                         chip8.set_key(key, false);
                     }
                 },
+                Event::ControllerButtonDown { button, .. } => {
+                    if let (Some(key), None) = (input.button(button), &session.player) {
+                        chip8.set_key(key, true);
+                    }
+                }
+                Event::ControllerButtonUp { button, .. } => {
+                    if let (Some(key), None) = (input.button(button), &session.player) {
+                        chip8.set_key(key, false);
+                    }
+                }
+                Event::ControllerDeviceAdded { which, .. } => match controller_subsystem.open(which) {
+                    Ok(controller) => controllers.push(controller),
+                    Err(e) => eprintln!("warning: cannot open game controller {}: {}", which, e),
+                },
+                Event::ControllerDeviceRemoved { which, .. } => {
+                    controllers.retain(|controller| controller.instance_id() != which);
+                }
                 _ => {}
             }
         }
 
-        chip8.emulate_cycle();
+        scheduler.speed = if turbo {
+            options.turbo_speed
+        } else if slow_motion {
+            options.slow_speed
+        } else {
+            1.0
+        };
 
-        canvas.set_draw_color(Color::RGB(0, 0, 0));
-        canvas.clear();
-        canvas.set_draw_color(Color::RGB(255, 255, 255));
+        if rewinding && stop.is_none() {
+            session.stop("rewound")?;
+            history.rewind(chip8, options.rewind_speed);
+            audio.frame(chip8)?;
+            scheduler.reset_clock();
+        } else if paused || stop.is_some() || rebinding.is_some() {
+            scheduler.reset_clock();
+        } else {
+            for _ in 0..scheduler.frames_due() {
+                if let Some(player) = &mut session.player {
+                    player.begin_frame(chip8);
+                }
+                let cycles = scheduler.cycles_for_frame();
+                if debugging {
+                    if let Some(reason) = debugger.run_frame(chip8, cycles) {
+                        stop = Some(reason);
+                        break;
+                    }
+                } else if let Err(e) = chip8.run_frame(cycles) {
+                    eprintln!("error: {} (paused; Ctrl+R to reset, F12 to debug)", e);
+                    paused = true;
+                    session.stop("program faulted")?;
+                    break;
+                }
+                audio.frame(chip8)?;
+                history.record(chip8);
+                if let Err(desync) = session.end_frame(chip8) {
+                    eprintln!("error: {} (paused)", desync);
+                    paused = true;
+                    break;
+                }
+                frame += 1;
+                if options.frames == Some(frame) {
+                    break 'running;
+                }
+                if chip8.exited {
+                    eprintln!("program exited (paused; Ctrl+R to reset)");
+                    paused = true;
+                    break;
+                }
+            }
+            audio.set_muted(muted || paused);
+        }
 
-        let graphics = chip8.get_graphics();
+        if chip8.awaiting_key() != awaiting_key {
+            awaiting_key = chip8.awaiting_key();
+            let title = if awaiting_key { format!("{} - press a key", title) } else { title.clone() };
+            canvas.window_mut().set_title(&title).map_err(|e| e.to_string())?;
+        }
 
-        for (i, &pixel) in graphics.iter().enumerate() {
-            if pixel == 1 {
-                let x = (i % 64) as i32;
-                let y = (i / 64) as i32;
-                canvas.fill_rect(Rect::new(x * 10, y * 10, 10, 10)).unwrap();
-            }
+        if let Some(rebinder) = &rebinding {
+            let (width, height) = sdl_input::SCREEN;
+            renderer.present(&mut canvas, width, height, &rebinder.frame(&renderer.palette))?;
+        } else {
+            renderer.draw(&mut canvas, chip8)?;
         }
 
         canvas.present();
 
-        chip8.tick();
+        if let Some(reason) = stop.take() {
+            session.stop("stopped in the debugger")?;
+            audio.set_muted(true);
+            let action = debugger.console(chip8, &reason, &mut io::stdin().lock(), &mut io::stdout())
+                .map_err(|e| format!("debugger console failed: {}", e))?;
+            match action {
+                ConsoleAction::Resume => {}
+                ConsoleAction::Quit => break 'running,
+                ConsoleAction::Rewind(frames) => stop = Some(StopReason::Rewound(history.rewind(chip8, frames))),
+            }
+            audio.set_muted(muted || paused);
+            scheduler.reset_clock();
+        }
+
+        pacer.wait();
+    }
+
+    session.stop("quit")?;
+    audio.finish()
+}
+
+// The movie being recorded and the one being replayed, if any. Re-recording
+// a replay records both at once.
+struct MovieSession {
+    recorder: Option<Recorder>,
+    player: Option<Player>,
+    record_path: Option<String>,
+}
 
-        thread::sleep(Duration::from_millis(16));
+impl MovieSession {
+    fn end_frame(&mut self, chip8: &Chip8) -> Result<(), Desync> {
+        if let Some(recorder) = &mut self.recorder {
+            recorder.frame(chip8);
+        }
+        let Some(player) = &mut self.player else {
+            return Ok(());
+        };
+        if let Err(desync) = player.end_frame(chip8) {
+            self.player = None;
+            return Err(desync);
+        }
+        if player.finished() {
+            println!("replay finished after {} frames; the keypad is live again", player.frame());
+            self.player = None;
+        }
+        Ok(())
     }
+
+    fn reset(&mut self) -> Result<(), String> {
+        if let Some(recorder) = &mut self.recorder {
+            recorder.reset();
+        }
+        if self.player.is_some() {
+            self.stop("machine reset")?;
+        }
+        Ok(())
+    }
+
+    // Loading a state, rewinding and stopping in the debugger change the
+    // machine in ways a movie can't capture, so they end recording and replay.
+    fn stop(&mut self, reason: &str) -> Result<(), String> {
+        if let Some(player) = self.player.take() {
+            println!("replay stopped at frame {}: {}", player.frame(), reason);
+        }
+        if let (Some(recorder), Some(path)) = (self.recorder.take(), &self.record_path) {
+            let frames = recorder.frames();
+            recorder.finish().write_file(path)?;
+            println!("recording stopped after {} frames ({}); saved '{}'", frames, reason, path);
+        }
+        Ok(())
+    }
+}
+
+// F1 to F9 pick a save state slot: Shift saves to it, the key alone loads it.
+fn state_slot(keycode: Keycode) -> Option<u8> {
+    let slots = [
+        Keycode::F1, Keycode::F2, Keycode::F3, Keycode::F4, Keycode::F5,
+        Keycode::F6, Keycode::F7, Keycode::F8, Keycode::F9,
+    ];
+    slots.iter().position(|&slot| slot == keycode).map(|index| index as u8 + 1)
+}
+
+fn state_path(rom_path: &str, slot: u8) -> String {
+    format!("{}.state{}", rom_path, slot)
+}
+
+fn save_state(chip8: &Chip8, path: &str) -> Result<(), String> {
+    fs::write(path, savestate::save(chip8))
+        .map_err(|e| format!("cannot write save state '{}': {}", path, e))
+}
+
+fn load_state(chip8: &mut Chip8, path: &str) -> Result<(), String> {
+    let data = fs::read(path)
+        .map_err(|e| format!("cannot read save state '{}': {}", path, e))?;
+    savestate::load(chip8, &data)
+        .map_err(|e| format!("cannot load save state '{}': {}", path, e))
 }
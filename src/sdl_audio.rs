@@ -0,0 +1,47 @@
+use sdl2::audio::{AudioCallback, AudioDevice, AudioSpecDesired};
+use sdl2::AudioSubsystem;
+
+use chip8_core::audio::{AudioSink, ToneGenerator, ToneSettings, SAMPLE_RATE};
+use chip8_core::chip8::Chip8;
+
+struct Callback {
+    generator: ToneGenerator,
+}
+
+impl AudioCallback for Callback {
+    type Channel = f32;
+
+    fn callback(&mut self, out: &mut [f32]) {
+        self.generator.fill(out);
+    }
+}
+
+pub struct SdlAudioSink {
+    device: AudioDevice<Callback>,
+}
+
+impl SdlAudioSink {
+    pub fn open(audio: &AudioSubsystem, settings: ToneSettings) -> Result<Self, String> {
+        let desired = AudioSpecDesired {
+            freq: Some(SAMPLE_RATE as i32),
+            channels: Some(1),
+            samples: Some(512),
+        };
+        let device = audio.open_playback(None, &desired, |spec| Callback {
+            generator: ToneGenerator::new(spec.freq as u32, settings),
+        })?;
+        device.resume();
+        Ok(Self { device })
+    }
+}
+
+impl AudioSink for SdlAudioSink {
+    fn frame(&mut self, chip8: &Chip8) -> Result<(), String> {
+        self.device.lock().generator.update(chip8);
+        Ok(())
+    }
+
+    fn set_muted(&mut self, muted: bool) {
+        self.device.lock().generator.settings.muted = muted;
+    }
+}
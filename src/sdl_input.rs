@@ -0,0 +1,133 @@
+use std::collections::HashMap;
+
+use sdl2::controller::Button;
+use sdl2::event::Event;
+use sdl2::keyboard::Keycode;
+
+use chip8_core::font::FontStyle;
+
+use chip8_rust::keymap::{is_hotkey, Device, Keymap, Layout, KEYPAD_ORDER};
+
+use crate::sdl_video::Palette;
+
+// The size of the rebinding screen, the low-resolution CHIP-8 screen.
+pub const SCREEN: (usize, usize) = (64, 32);
+
+pub struct InputMap {
+    keyboard: HashMap<Keycode, usize>,
+    controller: HashMap<Button, usize>,
+}
+
+impl InputMap {
+    pub fn new(layout: &Layout) -> Result<InputMap, String> {
+        let mut keyboard = HashMap::new();
+        let mut controller = HashMap::new();
+        for key in 0..16 {
+            for name in &layout.keyboard[key] {
+                let keycode = Keycode::from_name(name).ok_or_else(|| format!("unknown key name '{}' for key {:X}", name, key))?;
+                if is_hotkey(&keycode.name()) {
+                    return Err(format!("'{}' is a hotkey and can't be bound to key {:X}", name, key));
+                }
+                keyboard.insert(keycode, key);
+            }
+            for name in &layout.controller[key] {
+                let button = Button::from_string(name).ok_or_else(|| format!("unknown controller button '{}' for key {:X}", name, key))?;
+                controller.insert(button, key);
+            }
+        }
+        Ok(InputMap { keyboard, controller })
+    }
+
+    pub fn key(&self, keycode: Keycode) -> Option<usize> {
+        self.keyboard.get(&keycode).copied()
+    }
+
+    pub fn button(&self, button: Button) -> Option<usize> {
+        self.controller.get(&button).copied()
+    }
+}
+
+pub enum Rebind {
+    Continue,
+    Cancelled,
+    Done(Box<Keymap>),
+}
+
+// The rebinding screen: asks for a key or button for each hex key in keypad
+// order, working on a copy of the keymap that is only kept once all sixteen
+// have been asked for.
+pub struct Rebinder {
+    keymap: Keymap,
+    rom: String,
+    step: usize,
+}
+
+impl Rebinder {
+    pub fn new(keymap: Keymap, rom: String) -> Rebinder {
+        let rebinder = Rebinder { keymap, rom, step: 0 };
+        rebinder.prompt();
+        rebinder
+    }
+
+    fn prompt(&self) {
+        println!("press a key or controller button for {:X} (Backspace skips, Escape cancels)", KEYPAD_ORDER[self.step]);
+    }
+
+    pub fn handle(&mut self, event: &Event) -> Rebind {
+        match *event {
+            Event::KeyDown { keycode: Some(Keycode::Escape), .. } => Rebind::Cancelled,
+            Event::KeyDown { keycode: Some(Keycode::Backspace), .. } => self.next(),
+            Event::KeyDown { keycode: Some(keycode), repeat: false, .. } => {
+                if is_hotkey(&keycode.name()) {
+                    println!("'{}' is a hotkey; pick another key", keycode.name());
+                    return Rebind::Continue;
+                }
+                self.keymap.bind(&self.rom, Device::Keyboard, KEYPAD_ORDER[self.step], &keycode.name());
+                self.next()
+            }
+            Event::ControllerButtonDown { button, .. } => {
+                self.keymap.bind(&self.rom, Device::Controller, KEYPAD_ORDER[self.step], &button.string());
+                self.next()
+            }
+            _ => Rebind::Continue,
+        }
+    }
+
+    fn next(&mut self) -> Rebind {
+        self.step += 1;
+        if self.step == KEYPAD_ORDER.len() {
+            return Rebind::Done(Box::new(std::mem::take(&mut self.keymap)));
+        }
+        self.prompt();
+        Rebind::Continue
+    }
+
+    // The keypad as a grid of font digits in RGB, with the key being asked
+    // for lit and the rest dimmed.
+    pub fn frame(&self, palette: &Palette) -> Vec<u8> {
+        let (width, height) = SCREEN;
+        let background = palette[0];
+        let mut rgb = [background.0, background.1, background.2].repeat(width * height);
+
+        let font = FontStyle::Standard.sprites();
+        let dim = |from: u8, to: u8| ((from as u16 + to as u16) / 2) as u8;
+        for (position, &key) in KEYPAD_ORDER.iter().enumerate() {
+            let (r, g, b) = palette[1];
+            let color = if position == self.step {
+                [r, g, b]
+            } else {
+                [dim(background.0, r), dim(background.1, g), dim(background.2, b)]
+            };
+            let (left, top) = (18 + position % 4 * 8, 3 + position / 4 * 7);
+            for (row, &bits) in font[key * 5..key * 5 + 5].iter().enumerate() {
+                for column in 0..4 {
+                    if bits & (0x80 >> column) != 0 {
+                        let i = ((top + row) * width + left + column) * 3;
+                        rgb[i..i + 3].copy_from_slice(&color);
+                    }
+                }
+            }
+        }
+        rgb
+    }
+}
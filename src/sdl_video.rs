@@ -0,0 +1,150 @@
+use std::str::FromStr;
+
+use sdl2::pixels::{Color, PixelFormatEnum};
+use sdl2::rect::Rect;
+use sdl2::render::{Canvas, Texture, TextureCreator};
+use sdl2::video::{FullscreenType, Window, WindowContext};
+
+use chip8_core::chip8::{Chip8, SCREEN_HEIGHT, SCREEN_WIDTH};
+
+pub type Palette = [(u8, u8, u8); 16];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Scaling {
+    // Whole multiples of the CHIP-8 pixel size, so every pixel is the same size.
+    Integer,
+    // As large as the window allows at the screen's 2:1 aspect ratio.
+    #[default]
+    Aspect,
+}
+
+impl FromStr for Scaling {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "integer" => Ok(Scaling::Integer),
+            "aspect" => Ok(Scaling::Aspect),
+            _ => Err(format!("unknown scaling mode '{}'", s)),
+        }
+    }
+}
+
+// Parses `RRGGBB`, optionally prefixed with `#` or `0x`.
+pub fn parse_color(s: &str) -> Result<(u8, u8, u8), String> {
+    let hex = s.trim_start_matches('#').trim_start_matches("0x");
+    match u32::from_str_radix(hex, 16) {
+        Ok(rgb) if hex.len() == 6 => Ok(((rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8)),
+        _ => Err(format!("invalid colour '{}', expected RRGGBB", s)),
+    }
+}
+
+// Draws the screen through a streaming texture sized for high resolution,
+// of which only the top-left `width() x height()` is used in low resolution.
+pub struct Renderer<'a> {
+    texture: Texture<'a>,
+    pixels: Vec<u8>,
+    pub palette: Palette,
+    pub scaling: Scaling,
+    // Fraction of a pixel's brightness kept each frame after it goes dark,
+    // from 0 (off) towards 1 (long trails).
+    pub persistence: f32,
+    // Per pixel, the palette index it last lit up with and how much of that
+    // colour is left.
+    glow: Vec<(u8, f32)>,
+    glow_width: usize,
+}
+
+impl<'a> Renderer<'a> {
+    pub fn new(creator: &'a TextureCreator<WindowContext>, palette: Palette, scaling: Scaling, persistence: f32) -> Result<Self, String> {
+        let texture = creator
+            .create_texture_streaming(PixelFormatEnum::RGB24, SCREEN_WIDTH as u32, SCREEN_HEIGHT as u32)
+            .map_err(|e| e.to_string())?;
+        Ok(Self {
+            texture,
+            pixels: vec![0; SCREEN_WIDTH * SCREEN_HEIGHT * 3],
+            palette,
+            scaling,
+            persistence,
+            glow: vec![(0, 0.0); SCREEN_WIDTH * SCREEN_HEIGHT],
+            glow_width: 0,
+        })
+    }
+
+    pub fn draw(&mut self, canvas: &mut Canvas<Window>, chip8: &Chip8) -> Result<(), String> {
+        let (width, height) = (chip8.width(), chip8.height());
+        if width != self.glow_width {
+            // The resolution changed, so the old trails no longer line up.
+            self.glow.fill((0, 0.0));
+            self.glow_width = width;
+        }
+
+        let background = self.palette[0];
+        let graphics = &chip8.get_graphics()[..width * height];
+        for (i, &pixel) in graphics.iter().enumerate() {
+            let glow = &mut self.glow[i];
+            if pixel != 0 {
+                *glow = (pixel & 0xF, 1.0);
+            } else {
+                glow.1 *= self.persistence;
+            }
+            let (index, amount) = *glow;
+            let lit = self.palette[index as usize];
+            let mix = |from: u8, to: u8| (from as f32 + (to as f32 - from as f32) * amount).round() as u8;
+            self.pixels[i * 3..i * 3 + 3].copy_from_slice(&[
+                mix(background.0, lit.0),
+                mix(background.1, lit.1),
+                mix(background.2, lit.2),
+            ]);
+        }
+
+        let pixels = std::mem::take(&mut self.pixels);
+        let result = self.present(canvas, width, height, &pixels[..width * height * 3]);
+        self.pixels = pixels;
+        result
+    }
+
+    // Shows a `width x height` RGB image, scaled to the window and centred
+    // on the background colour.
+    pub fn present(&mut self, canvas: &mut Canvas<Window>, width: usize, height: usize, rgb: &[u8]) -> Result<(), String> {
+        let source = Rect::new(0, 0, width as u32, height as u32);
+        self.texture.update(source, rgb, width * 3).map_err(|e| e.to_string())?;
+
+        let (r, g, b) = self.palette[0];
+        canvas.set_draw_color(Color::RGB(r, g, b));
+        canvas.clear();
+        let target = self.target(canvas.output_size()?, width as u32, height as u32);
+        canvas.copy(&self.texture, source, target)
+    }
+
+    fn target(&self, (window_width, window_height): (u32, u32), width: u32, height: u32) -> Rect {
+        let (scaled_width, scaled_height) = match self.scaling {
+            Scaling::Integer => {
+                let scale = (window_width / width).min(window_height / height).max(1);
+                (width * scale, height * scale)
+            }
+            Scaling::Aspect => {
+                if window_width * height > window_height * width {
+                    (window_height * width / height, window_height)
+                } else {
+                    (window_width, window_width * height / width)
+                }
+            }
+        };
+        Rect::new(
+            (window_width as i32 - scaled_width as i32) / 2,
+            (window_height as i32 - scaled_height as i32) / 2,
+            scaled_width.max(1),
+            scaled_height.max(1),
+        )
+    }
+}
+
+pub fn toggle_fullscreen(canvas: &mut Canvas<Window>) -> Result<(), String> {
+    let window = canvas.window_mut();
+    let state = match window.fullscreen_state() {
+        FullscreenType::Off => FullscreenType::Desktop,
+        _ => FullscreenType::Off,
+    };
+    window.set_fullscreen(state)
+}